@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 use structopt::{clap::AppSettings::ColoredHelp, StructOpt};
 
+use crate::exit::EXIT_CODES_HELP;
+
 #[derive(StructOpt)]
 #[structopt(global_setting(ColoredHelp))]
 pub enum CargoSpawnedArguments {
@@ -9,6 +11,7 @@ pub enum CargoSpawnedArguments {
 }
 
 #[derive(StructOpt)]
+#[structopt(after_help = EXIT_CODES_HELP)]
 pub struct Arguments {
     #[structopt(subcommand)]
     pub command: PreflightCommand,
@@ -30,8 +33,30 @@ pub enum PreflightCommand {
         #[structopt(flatten)]
         sim: SimulationArguments,
         #[structopt(flatten)]
+        log: LogArguments,
+        #[structopt(flatten)]
+        replay: ReplayArguments,
+        #[structopt(flatten)]
         display: DisplayArguments,
     },
+    /// Tune parameters of the avionics by flying Monte Carlo batches of the scenarios
+    Tune {
+        #[structopt(flatten)]
+        cargo: CargoArguments,
+        #[structopt(flatten)]
+        panic: PanicHandleArguments,
+        #[structopt(flatten)]
+        sim: SimulationArguments,
+        #[structopt(flatten)]
+        tune: TuneArguments,
+    },
+    /// List the parameters of the avionics along with their values once initialized
+    Parameters {
+        #[structopt(flatten)]
+        cargo: CargoArguments,
+        #[structopt(flatten)]
+        panic: PanicHandleArguments,
+    },
     // /// Run a simulation on the project
     // Simulate {
     //     #[structopt(flatten)]
@@ -40,7 +65,115 @@ pub enum PreflightCommand {
 }
 
 #[derive(StructOpt)]
-pub struct SimulationArguments {}
+pub struct SimulationArguments {
+    /// Directory to load the scenarios from, defaults to the `scenarios`
+    /// directory of the package
+    #[structopt(long, name = "SCENARIO-DIRECTORY", parse(from_os_str))]
+    pub scenarios: Option<PathBuf>,
+    /// Time in milliseconds that the avionics are given to produce a control signal
+    /// before the watchdog trips
+    #[structopt(long, name = "MILLISECONDS", default_value = "1000")]
+    pub watchdog: u64,
+    /// Set one of the parameters of the avionics before every scenario,
+    /// overriding its value in the scenarios
+    #[structopt(long = "set", name = "NAME=VALUE", parse(try_from_str = parse_parameter), number_of_values = 1)]
+    pub parameters: Vec<(String, f64)>,
+}
+
+#[derive(StructOpt)]
+pub struct LogArguments {
+    /// Directory to record every sensor and guidance channel of each scenario
+    /// into, as a csv file named after the scenario
+    #[structopt(long, name = "LOG-DIRECTORY", parse(from_os_str))]
+    pub signal_log: Option<PathBuf>,
+    /// Directory to record the telemetry published by the avionics in each
+    /// scenario into, alongside the true state of the simulated vehicle, as a
+    /// csv file named after the scenario
+    #[structopt(long, name = "TELEMETRY-DIRECTORY", parse(from_os_str))]
+    pub telemetry_log: Option<PathBuf>,
+}
+
+#[derive(StructOpt)]
+pub struct ReplayArguments {
+    /// Checkpoint each scenario at the given time in seconds and fly the rest of
+    /// it a second time from the checkpoint, checking that the replay matches the
+    /// original flight. Defaults to the `branch` time of each scenario that has one
+    #[structopt(long, name = "SECONDS", parse(try_from_str = parse_time))]
+    pub checkpoint: Option<f64>,
+}
+
+#[derive(StructOpt)]
+pub struct TuneArguments {
+    /// Parameter of the avionics to tune, starting from its value once the
+    /// avionics are initialized and the parameters given with `--set` are set.
+    /// One that a scenario sets has to be given its starting value with `--set`
+    #[structopt(
+        long = "tune",
+        name = "PARAMETER",
+        required = true,
+        number_of_values = 1
+    )]
+    pub parameters: Vec<String>,
+    /// Only fly the scenarios with the given names, defaults to every scenario
+    #[structopt(long = "scenario", name = "SCENARIO", number_of_values = 1)]
+    pub scenarios: Vec<String>,
+    /// Number of runs of each scenario in every Monte Carlo batch, each with
+    /// its own sensor noise and dispersion of the vehicle
+    #[structopt(long, name = "RUNS", default_value = "8", parse(try_from_str = parse_runs))]
+    pub runs: u64,
+    /// Largest number of iterations of the optimizer
+    #[structopt(long, name = "ITERATIONS", default_value = "100")]
+    pub iterations: usize,
+    /// Size of the first steps of the optimizer, as a fraction of the starting
+    /// value of each parameter, or as an absolute step for parameters starting at zero
+    #[structopt(long, name = "FRACTION", default_value = "0.25")]
+    pub step: f64,
+    /// Cost of each degree of root mean square tilt from vertical on the way up
+    #[structopt(long, name = "TILT-WEIGHT", default_value = "1.0")]
+    pub tilt_weight: f64,
+    /// Cost of each degree of root mean square gimbal deflection on the way up
+    #[structopt(long, name = "EFFORT-WEIGHT", default_value = "0.1")]
+    pub effort_weight: f64,
+    /// Cost of each meter of apogee missed, in scenarios with a `target_apogee`
+    #[structopt(long, name = "APOGEE-WEIGHT", default_value = "0.1")]
+    pub apogee_weight: f64,
+}
+
+/// Parse a parameter given as `NAME=VALUE`
+fn parse_parameter(parameter: &str) -> Result<(String, f64), String> {
+    let mut parts = parameter.splitn(2, '=');
+    let (name, value) = match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) => (name, value),
+        _ => return Err(format!("expected `NAME=VALUE`, found `{}`", parameter)),
+    };
+    let value = value
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid value for parameter `{}`: {}", name.trim(), e))?;
+
+    Ok((name.trim().to_string(), value))
+}
+
+/// Parse a time in seconds, which can not be before the start of a scenario
+fn parse_time(time: &str) -> Result<f64, String> {
+    match time.parse::<f64>() {
+        Ok(time) if time.is_finite() && time >= 0.0 => Ok(time),
+        Ok(time) => Err(format!(
+            "the time has to be a non-negative number of seconds, found {}",
+            time
+        )),
+        Err(e) => Err(format!("invalid time: {}", e)),
+    }
+}
+
+/// Parse a number of runs, of which there has to be at least one
+fn parse_runs(runs: &str) -> Result<u64, String> {
+    match runs.parse() {
+        Ok(0) => Err("there has to be at least one run".to_string()),
+        Ok(runs) => Ok(runs),
+        Err(e) => Err(format!("invalid number of runs: {}", e)),
+    }
+}
 
 #[derive(StructOpt)]
 pub struct DisplayArguments {
@@ -60,16 +193,42 @@ pub struct PanicHandleArguments {
 pub struct CargoArguments {
     #[structopt(long, parse(from_os_str), env = "CARGO", default_value = "cargo")]
     pub cargo_path: PathBuf,
-    // TODO: more
+    /// Package with the avionics to build
+    #[structopt(long, short, name = "SPEC")]
+    pub package: Option<String>,
+    /// Space or comma separated list of features to activate
+    #[structopt(long, name = "FEATURES")]
+    pub features: Vec<String>,
+    /// Activate all available features
+    #[structopt(long)]
+    pub all_features: bool,
+    /// Do not activate the `default` feature
+    #[structopt(long)]
+    pub no_default_features: bool,
+    /// Build for the target triple, defaults to the host
+    #[structopt(long, name = "TRIPLE")]
+    pub target: Option<String>,
+    /// Build artifacts in release mode, with optimizations
+    #[structopt(long)]
+    pub release: bool,
+    /// Build artifacts with the specified profile
+    #[structopt(long, name = "PROFILE-NAME", conflicts_with = "release")]
+    pub profile: Option<String>,
+    /// Number of parallel jobs, defaults to # of CPUs
+    #[structopt(long, short, name = "N")]
+    pub jobs: Option<u32>,
     /// Path to Cargo.toml
     #[structopt(long, name = "FILE", parse(from_os_str))]
     pub manifest_path: Option<PathBuf>,
+    /// Require Cargo.lock is up to date
+    #[structopt(long)]
+    pub locked: bool,
+    /// Require Cargo.lock and cache are up to date
+    #[structopt(long)]
+    pub frozen: bool,
     /// Run without accessing the network
     #[structopt(long)]
     pub offline: bool,
-    /// Build artifacts in release mode, with optimizations
-    #[structopt(long)]
-    pub release: bool,
     /// Directory for all generated artifacts
     #[structopt(long, name = "DIRECTORY", parse(from_os_str))]
     pub target_dir: Option<PathBuf>,
@@ -1,23 +1,95 @@
-use anyhow::{anyhow, bail, ensure, Context};
-use cargo_metadata::{Metadata, MetadataCommand, Package};
-use dlopen::utils::{PLATFORM_FILE_EXTENSION, PLATFORM_FILE_PREFIX};
+use anyhow::{anyhow, ensure, Context};
+use cargo_metadata::{
+    Artifact, CargoOpt, CompilerMessage, Message, Metadata, MetadataCommand, Package,
+};
+use dlopen::utils::PLATFORM_FILE_EXTENSION;
 use std::{
+    ffi::{OsStr, OsString},
     io::{BufRead, BufReader},
     path::PathBuf,
     process::{Command, Stdio},
 };
 
-use crate::args::CargoArguments;
+use crate::{args::CargoArguments, exit::BuildFailed};
 
 pub fn get_metadata(args: &CargoArguments) -> cargo_metadata::Result<Metadata> {
     let mut metadata_command = MetadataCommand::new();
+    metadata_command.cargo_path(&args.cargo_path);
+
     if let Some(manifest_path) = &args.manifest_path {
         metadata_command.manifest_path(manifest_path);
     }
 
+    if !args.features.is_empty() {
+        metadata_command.features(CargoOpt::SomeFeatures(args.features.clone()));
+    }
+    if args.all_features {
+        metadata_command.features(CargoOpt::AllFeatures);
+    }
+    if args.no_default_features {
+        metadata_command.features(CargoOpt::NoDefaultFeatures);
+    }
+
+    metadata_command.other_options(
+        [
+            (args.locked, "--locked"),
+            (args.frozen, "--frozen"),
+            (args.offline, "--offline"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, flag)| flag.to_string())
+        .collect::<Vec<_>>(),
+    );
+
     metadata_command.exec()
 }
 
+/// Translate the arguments into the flags to pass along to `cargo` when building
+/// the given package
+fn cargo_flags(args: &CargoArguments, target: &str, package: &Package) -> Vec<OsString> {
+    let mut flags: Vec<OsString> = vec![
+        format!("--package={}", package.name).into(),
+        format!("--target={}", target).into(),
+    ];
+
+    if !args.features.is_empty() {
+        flags.push(format!("--features={}", args.features.join(",")).into());
+    }
+
+    let switches = [
+        (args.all_features, "--all-features"),
+        (args.no_default_features, "--no-default-features"),
+        (args.release, "--release"),
+        (args.locked, "--locked"),
+        (args.frozen, "--frozen"),
+        (args.offline, "--offline"),
+    ];
+    flags.extend(
+        switches
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, flag)| OsString::from(flag)),
+    );
+
+    if let Some(profile) = &args.profile {
+        flags.push(format!("--profile={}", profile).into());
+    }
+    if let Some(jobs) = args.jobs {
+        flags.push(format!("--jobs={}", jobs).into());
+    }
+    if let Some(manifest_path) = &args.manifest_path {
+        flags.push("--manifest-path".into());
+        flags.push(manifest_path.into());
+    }
+    if let Some(target_dir) = &args.target_dir {
+        flags.push("--target-dir".into());
+        flags.push(target_dir.into());
+    }
+
+    flags
+}
+
 pub fn get_host_target() -> anyhow::Result<String> {
     let output = Command::new("rustc")
         .args(&["-Vv"])
@@ -54,61 +126,65 @@ pub fn get_host_target() -> anyhow::Result<String> {
 
 pub fn build_artifact<'a>(
     cargo_args: &'a CargoArguments,
-    target_override: &str,
+    target: &str,
     package: &'a Package,
 ) -> anyhow::Result<Option<PathBuf>> {
     // Build the program
     let mut build_command = Command::new(&cargo_args.cargo_path)
-        .args(
-            [
-                "rustc",
-                &format!("--target={}", target_override),
-                if cargo_args.offline { "--offline" } else { "" },
-                if cargo_args.release { "--release" } else { "" },
-                "--",
-                "--crate-type=dylib",
-                "--cfg=preflight",
-            ]
-            .iter()
-            .filter(|x| !x.is_empty()),
-        )
+        .args(&[
+            "rustc",
+            "--lib",
+            // Build the library as a C dynamic library regardless of the crate
+            // types in the manifest, so that it can be loaded by the runner
+            "--crate-type=cdylib",
+            "--message-format=json-diagnostic-rendered-ansi",
+        ])
+        .args(cargo_flags(cargo_args, target, package))
+        // The avionics have no unwinder to unwind with
+        .args(&["--", "--cfg=preflight", "-Cpanic=abort"])
         .env("__PREFLIGHT", "")
+        .stdout(Stdio::piped())
         .spawn()
         .context("`cargo` failed to run to completion")?;
 
+    let stdout = build_command
+        .stdout
+        .take()
+        .context("failed to capture the output of `cargo`")?;
+
+    let mut artifact = None;
+
+    for message in Message::parse_stream(BufReader::new(stdout)) {
+        match message.context("failed to read the output of `cargo`")? {
+            // Forward the diagnostics as rustc would have rendered them
+            Message::CompilerMessage(CompilerMessage { message, .. }) => {
+                if let Some(rendered) = message.rendered {
+                    eprint!("{}", rendered);
+                }
+            }
+            Message::CompilerArtifact(Artifact {
+                package_id,
+                target,
+                filenames,
+                ..
+            }) if package_id == package.id && target.kind.iter().any(|k| k == "cdylib") => {
+                artifact = filenames.into_iter().find(|file| {
+                    file.extension().and_then(OsStr::to_str) == Some(PLATFORM_FILE_EXTENSION)
+                });
+            }
+            Message::TextLine(line) => println!("{}", line),
+            _ => {}
+        }
+    }
+
     // Wait for cargo to finish
     let status = build_command
         .wait()
         .context("`cargo` failed to run to completion")?;
 
-    // Get the dylib artifacts
-    let mut artifacts = glob::glob(&format!(
-        "target/{target}/{profile}/deps/{prefix}{package}*.{ext}",
-        target = target_override,
-        profile = if cargo_args.release {
-            "release"
-        } else {
-            "debug"
-        },
-        prefix = PLATFORM_FILE_PREFIX,
-        package = package.name,
-        ext = PLATFORM_FILE_EXTENSION
-    ))
-    .context("Failed to read glob pattern")?
-    .collect::<Result<Vec<_>, _>>()
-    .context("Failed to get artifact path")?;
-
-    // Get the one artifact if there is only one, if there are multiple, error out
-    let artifact = match artifacts.len() {
-        0 | 1 => artifacts.pop(),
-        _ => bail!("found ambiguous dylib artifacts: {:?}", artifacts),
-    };
-
-    ensure!(
-        status.success(),
-        "`rustc` exited with a failure code [{0}]",
-        status
-    );
+    if !status.success() {
+        return Err(BuildFailed(status).into());
+    }
 
     Ok(artifact)
 }
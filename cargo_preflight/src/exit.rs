@@ -0,0 +1,88 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    process::{self, ExitStatus},
+};
+
+use indoc::indoc;
+
+/// Description of the exit codes, shown in the help message
+pub const EXIT_CODES_HELP: &str = indoc! {"
+    EXIT CODES:
+          0    Success
+          1    Internal error in the preflight runner
+          2    The arguments could not be parsed
+          3    The avionics harness could not be found or loaded
+          4    A scenario failed
+          5    The avionics panicked
+          6    The avionics watchdog tripped
+        any    The build failed, with the exit code of cargo, or 101 if it was
+               killed before it could exit. Cargo's exit code can be any of the
+               codes above
+"};
+
+/// The exit codes reported by `cargo preflight`
+///
+/// Keep in sync with [`EXIT_CODES_HELP`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The runner itself failed
+    InternalError = 1,
+    /// The arguments could not be parsed, as clap reports it
+    UsageError = 2,
+    /// The avionics harness could not be found or loaded
+    HarnessMissing = 3,
+    /// A scenario failed
+    ScenarioFailed = 4,
+    /// The avionics panicked
+    AvionicsPanicked = 5,
+    /// The avionics did not produce a control signal in time
+    WatchdogTripped = 6,
+    /// Cargo failed to build the avionics without reporting an exit code, such
+    /// as when it was killed by a signal
+    BuildFailed = 101,
+}
+
+impl ExitCode {
+    /// Exit the process with this exit code
+    pub fn exit(self) -> ! {
+        process::exit(self as i32)
+    }
+
+    /// Exit the process with the exit code that best describes the error
+    pub fn exit_with_error(error: &anyhow::Error) -> ! {
+        if let Some(BuildFailed(status)) = error.downcast_ref::<BuildFailed>() {
+            match status.code() {
+                Some(code) => process::exit(code),
+                None => ExitCode::BuildFailed.exit(),
+            }
+        } else if error.downcast_ref::<HarnessMissing>().is_some() {
+            ExitCode::HarnessMissing.exit()
+        } else {
+            ExitCode::InternalError.exit()
+        }
+    }
+}
+
+/// Error signaling that cargo failed to build the avionics
+#[derive(Debug)]
+pub struct BuildFailed(pub ExitStatus);
+
+impl Display for BuildFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "`rustc` exited with a failure code [{}]", self.0)
+    }
+}
+
+impl std::error::Error for BuildFailed {}
+
+/// Error signaling that the avionics harness could not be found or loaded
+#[derive(Debug)]
+pub struct HarnessMissing;
+
+impl Display for HarnessMissing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load the avionics harness")
+    }
+}
+
+impl std::error::Error for HarnessMissing {}
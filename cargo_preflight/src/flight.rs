@@ -0,0 +1,532 @@
+//! Flying the avionics through a scenario a step at a time, so that a flight can
+//! be checkpointed part of the way through and resumed from there
+
+use std::io;
+
+use preflight::{
+    estimation::{ApogeePredictor, AttitudeEstimator, Complementary, Madgwick, Mahony},
+    uom::si::{angle::degree, length::meter},
+    CommandResponse, Control, EventFlags, FlightPhase,
+};
+
+use crate::{
+    harness::{AvionicsHarness, PanicCaught},
+    scenario::{
+        AbortEvent, ApogeeTrace, AscentTrace, AttitudeTrace, Expectation, ParameterReading,
+        PhaseEvent, ProcessorReset, PyroEvent, ResetEvent, Scenario, ScenarioReport, UplinkCommand,
+    },
+    shell::Shell,
+    signals::SignalLog,
+    sim::{Dispersion, Simulation},
+    telemetry::TelemetryLog,
+    watchdog::Watchdog,
+};
+
+/// Time in seconds that the avionics keep being run for after the vehicle lands,
+/// so that they have time to notice that it has
+const AFTER_LANDING: f64 = 10.0;
+
+/// One of preflight's attitude filters, fed the same sensor readings as the
+/// avionics
+#[derive(Debug, Clone)]
+pub enum Estimator {
+    Complementary(Complementary),
+    Mahony(Mahony),
+    Madgwick(Madgwick),
+}
+
+impl Estimator {
+    fn filter(&mut self) -> &mut dyn AttitudeEstimator {
+        match self {
+            Estimator::Complementary(filter) => filter,
+            Estimator::Mahony(filter) => filter,
+            Estimator::Madgwick(filter) => filter,
+        }
+    }
+}
+
+/// A scenario being flown, along with everything that has happened so far
+///
+/// Cloning a flight along with a snapshot of the avionics, as a [`Checkpoint`],
+/// lets it be flown on from the same moment any number of times.
+#[derive(Debug, Clone)]
+pub struct Flight<'a> {
+    scenario: &'a Scenario,
+    simulation: Simulation,
+    /// The commands of the scenario in the order that they are sent
+    commands: Vec<&'a UplinkCommand>,
+    /// Index of the next command to be sent
+    next_command: usize,
+    /// The resets of the flight computer in the order that they happen
+    resets: Vec<&'a ProcessorReset>,
+    /// Index of the next reset to happen
+    next_reset: usize,
+    /// Time in seconds that the flight computer comes back up at, while it is down
+    down_until: Option<f64>,
+    reset_events: Vec<ResetEvent>,
+    /// Names of the parameters that are read after each reset
+    checked_parameters: Vec<&'a str>,
+    parameters: Vec<ParameterReading>,
+    abort: Option<AbortEvent>,
+    pyro: Vec<PyroEvent>,
+    flags: EventFlags,
+    phases: Vec<PhaseEvent>,
+    phase: FlightPhase,
+    filters: Vec<(AttitudeTrace, Estimator)>,
+    predictor: Option<ApogeePredictor>,
+    apogee: ApogeeTrace,
+    /// Time in seconds that the vehicle landed at
+    landed: Option<f64>,
+    /// Sums of the squares of the tilt and deflection on the way up, and the
+    /// number of steps that they were summed over
+    ascent: (f64, f64, u32),
+    finished: bool,
+}
+
+/// A flight paused between two steps, along with a snapshot of the avionics at
+/// that moment
+#[derive(Debug, Clone)]
+pub struct Checkpoint<'a> {
+    flight: Flight<'a>,
+    avionics: Vec<u8>,
+}
+
+impl<'a> Flight<'a> {
+    /// Place the vehicle of the scenario on the pad
+    pub fn new(scenario: &'a Scenario, shell: &mut Shell) -> io::Result<Self> {
+        let simulation = Simulation::new(
+            scenario.vehicle.clone(),
+            scenario.motor,
+            scenario.site.clone(),
+            scenario.gnss.clone(),
+            scenario.fault.clone(),
+            scenario.seed,
+        );
+        for fault in &scenario.fault {
+            shell.status("Injecting", fault.to_string())?;
+        }
+
+        let mut filters: Vec<(AttitudeTrace, Estimator)> = Vec::new();
+        for expectation in &scenario.expect {
+            if let Expectation::Attitude { filter, .. } = expectation {
+                if !filters.iter().any(|(trace, _)| trace.filter == *filter) {
+                    filters.push((
+                        AttitudeTrace {
+                            filter: *filter,
+                            error: Vec::new(),
+                        },
+                        filter.estimator(),
+                    ));
+                }
+            }
+        }
+
+        let predictor = scenario
+            .expect
+            .iter()
+            .any(|expectation| matches!(expectation, Expectation::Apogee { .. }))
+            .then(ApogeePredictor::default);
+        let apogee = ApogeeTrace {
+            detected: None,
+            truth: (simulation.time(), simulation.altitude()),
+        };
+
+        let mut commands = scenario.command.iter().collect::<Vec<_>>();
+        commands.sort_by(|a, b| a.time().total_cmp(&b.time()));
+
+        let checked_parameters = scenario
+            .expect
+            .iter()
+            .filter_map(|expectation| match expectation {
+                Expectation::Parameter { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        let mut resets = scenario.reset.iter().collect::<Vec<_>>();
+        resets.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self {
+            scenario,
+            simulation,
+            commands,
+            next_command: 0,
+            resets,
+            next_reset: 0,
+            down_until: None,
+            reset_events: Vec::new(),
+            checked_parameters,
+            parameters: Vec::new(),
+            abort: None,
+            pyro: Vec::new(),
+            flags: EventFlags::NONE,
+            phases: Vec::new(),
+            phase: FlightPhase::Pad,
+            filters,
+            predictor,
+            apogee,
+            landed: None,
+            ascent: (0.0, 0.0, 0),
+            finished: false,
+        })
+    }
+
+    /// Time in seconds since the start of the scenario that the flight is at
+    pub fn time(&self) -> f64 {
+        self.simulation.time()
+    }
+
+    /// Send the flight down another path from here on, with different sensor
+    /// noise and the vehicle varied by the dispersion, as a run of a Monte Carlo
+    /// batch would be
+    pub fn branch(&mut self, dispersion: &Dispersion, seed: u64) {
+        self.simulation.branch(dispersion, seed);
+    }
+
+    /// Fly the avionics until the given time in seconds, recording every call
+    /// into the avionics to the logs that are given
+    ///
+    /// The flight ends early once the vehicle has been on the ground for a while,
+    /// and never goes on past the duration of the scenario.
+    pub fn fly(
+        &mut self,
+        until: f64,
+        harness: &mut AvionicsHarness<PanicCaught>,
+        watchdog: &Watchdog,
+        shell: &mut Shell,
+        log: &mut Option<SignalLog>,
+        telemetry: &mut Option<TelemetryLog>,
+    ) -> anyhow::Result<()> {
+        let until = until.min(self.scenario.duration);
+
+        while !self.finished && self.simulation.time() < until {
+            self.step(harness, watchdog, shell, log, telemetry)?;
+        }
+
+        if self.simulation.time() >= self.scenario.duration {
+            self.finished = true;
+        }
+
+        Ok(())
+    }
+
+    /// Run the avionics and the simulation for a single timestep
+    fn step(
+        &mut self,
+        harness: &mut AvionicsHarness<PanicCaught>,
+        watchdog: &Watchdog,
+        shell: &mut Shell,
+        log: &mut Option<SignalLog>,
+        telemetry: &mut Option<TelemetryLog>,
+    ) -> anyhow::Result<()> {
+        let simulation = &mut self.simulation;
+
+        if simulation.has_landed() {
+            let landed = *self.landed.get_or_insert(simulation.time());
+
+            if simulation.time() - landed >= AFTER_LANDING {
+                self.finished = true;
+
+                return Ok(());
+            }
+        }
+
+        let sensors = simulation.sensors();
+
+        if simulation.altitude() > self.apogee.truth.1 {
+            self.apogee.truth = (simulation.time(), simulation.altitude());
+        }
+
+        if let Some(detected) = self
+            .predictor
+            .as_mut()
+            .and_then(|predictor| predictor.update(&sensors))
+        {
+            self.apogee.detected = Some((
+                simulation.time(),
+                f64::from(detected.altitude.get::<meter>()),
+            ));
+        }
+
+        for (trace, estimator) in &mut self.filters {
+            let filter = estimator.filter();
+            filter.update(&sensors);
+
+            let error = f64::from(
+                filter
+                    .attitude()
+                    .quaternion()
+                    .angle_to(&simulation.attitude().quaternion())
+                    .get::<degree>(),
+            );
+            trace.error.push((simulation.time(), error));
+        }
+
+        // The avionics are created anew once the flight computer comes back up
+        // from a reset, and are not run at all while it is down
+        while let Some(reset) = self
+            .resets
+            .get(self.next_reset)
+            .filter(|reset| reset.time <= simulation.time())
+        {
+            self.next_reset += 1;
+
+            shell.status("Reset", reset.to_string())?;
+
+            self.reset_events.push(ResetEvent {
+                time: simulation.time(),
+                phase: self.phase,
+                recovered: None,
+            });
+            self.down_until = Some(simulation.time() + reset.downtime);
+        }
+
+        let running = match self.down_until {
+            Some(down_until) if simulation.time() < down_until => false,
+            Some(_) => {
+                watchdog.watch(|| harness.reboot())?;
+                log_messages(harness, shell, simulation.time())?;
+
+                for name in &self.checked_parameters {
+                    self.parameters.push(ParameterReading {
+                        name: name.to_string(),
+                        time: simulation.time(),
+                        value: watchdog.watch(|| harness.parameter(name))?,
+                    });
+                }
+
+                self.down_until = None;
+                true
+            }
+            None => true,
+        };
+
+        // Commands are sent between calls into the guidance, even after an abort,
+        // and wait for the flight computer to come back up if it is down
+        while let Some(command) = self
+            .commands
+            .get(self.next_command)
+            .filter(|command| running && command.time() <= simulation.time())
+        {
+            self.next_command += 1;
+
+            let response = watchdog.watch(|| harness.command(&command.command()));
+            log_messages(harness, shell, simulation.time())?;
+
+            match response {
+                CommandResponse::Accepted => shell.status("Commanded", command.to_string())?,
+                CommandResponse::Rejected => {
+                    shell.warning(format!("the avionics rejected the command to {}", command))?
+                }
+                CommandResponse::Unsupported => shell.warning(format!(
+                    "the avionics do not support the command to {}",
+                    command
+                ))?,
+            }
+        }
+
+        // Guidance is no longer requested once the avionics have aborted
+        if running && self.abort.is_none() {
+            let control = watchdog.watch(|| harness.guide(&sensors));
+            log_messages(harness, shell, simulation.time())?;
+
+            if let Some(log) = log {
+                let (sensors, guidance) = harness.last_signals();
+
+                log.record(simulation.time(), sensors, guidance)?;
+            }
+
+            if let Some(telemetry) = telemetry {
+                let published = watchdog.watch(|| harness.telemetry());
+
+                telemetry.record(simulation.time(), simulation.truth(), published)?;
+            }
+
+            match control {
+                Control::Guidance(guidance) => {
+                    simulation.actuate(&guidance.tvc);
+
+                    for (channel, device) in simulation.fire(guidance.pyro) {
+                        let event = PyroEvent {
+                            channel,
+                            device,
+                            time: simulation.time(),
+                        };
+
+                        shell.status("Fired", event.to_string())?;
+
+                        self.pyro.push(event);
+                    }
+
+                    let raised = guidance.flags.difference(self.flags);
+                    if !raised.is_empty() {
+                        shell.status(
+                            "Flagged",
+                            format!("{:?} at T+{:.2}s", raised, simulation.time()),
+                        )?;
+                    }
+
+                    self.flags = guidance.flags;
+
+                    if guidance.phase != self.phase {
+                        let event = PhaseEvent {
+                            phase: guidance.phase,
+                            time: simulation.time(),
+                        };
+
+                        shell.status("Entered", event.to_string())?;
+
+                        self.phases.push(event);
+                        self.phase = guidance.phase;
+                    }
+
+                    if let Some(reset) = self
+                        .reset_events
+                        .last_mut()
+                        .filter(|reset| reset.recovered.is_none() && guidance.phase >= reset.phase)
+                    {
+                        reset.recovered = Some(simulation.time());
+
+                        shell.status(
+                            "Recovered",
+                            format!("{:?} at T+{:.2}s", reset.phase, simulation.time()),
+                        )?;
+                    }
+                }
+                Control::ABORT(cause) => {
+                    watchdog.watch(|| harness.abort(&cause));
+                    log_messages(harness, shell, simulation.time())?;
+                    simulation.enter_recovery();
+
+                    let event = AbortEvent {
+                        cause,
+                        time: simulation.time(),
+                    };
+
+                    shell.status("Aborted", event.to_string())?;
+
+                    self.abort = Some(event);
+                }
+                // The last good control signal is held until the next step
+                Control::RecoverableFailure => {}
+            }
+        }
+
+        if simulation.is_ascending() {
+            let (tilt, effort, ascending) = &mut self.ascent;
+
+            *tilt += simulation.tilt().powi(2);
+            *effort += simulation.deflection().powi(2);
+            *ascending += 1;
+        }
+
+        simulation.step(self.scenario.timestep);
+
+        Ok(())
+    }
+
+    /// Checkpoint the flight, along with the avionics as they are right now
+    pub fn checkpoint(
+        &self,
+        harness: &mut AvionicsHarness<PanicCaught>,
+        watchdog: &Watchdog,
+    ) -> anyhow::Result<Checkpoint<'a>> {
+        Ok(Checkpoint {
+            flight: self.clone(),
+            avionics: watchdog.watch(|| harness.snapshot())?,
+        })
+    }
+
+    /// Report on everything that happened during the flight
+    pub fn finish(
+        self,
+        shell: &mut Shell,
+        telemetry: Option<TelemetryLog>,
+    ) -> anyhow::Result<ScenarioReport> {
+        for (trace, _) in &self.filters {
+            let worst = trace
+                .error
+                .iter()
+                .map(|(_, error)| *error)
+                .fold(0.0, f64::max);
+
+            shell.status(
+                "Estimated",
+                format!(
+                    "attitude with the {} filter, at worst {:.1}° off",
+                    trace.filter, worst
+                ),
+            )?;
+        }
+
+        if self.predictor.is_some() {
+            shell.status("Detected", self.apogee.to_string())?;
+        }
+
+        if let Some(telemetry) = telemetry {
+            telemetry.finish()?;
+        }
+
+        let (tilt, effort, ascending) = self.ascent;
+        let apogee = self.apogee;
+
+        Ok(ScenarioReport {
+            abort: self.abort,
+            pyro: self.pyro,
+            phases: self.phases,
+            attitude: self.filters.into_iter().map(|(trace, _)| trace).collect(),
+            apogee: self.predictor.map(|_| apogee),
+            resets: self.reset_events,
+            parameters: self.parameters,
+            ascent: AscentTrace {
+                tilt: (tilt / f64::from(ascending.max(1))).sqrt(),
+                effort: (effort / f64::from(ascending.max(1))).sqrt(),
+                apogee: apogee.truth.1,
+            },
+            duration: self.simulation.time(),
+        })
+    }
+}
+
+impl<'a> Checkpoint<'a> {
+    /// Restore the avionics to the checkpoint, and carry on with the flight from
+    /// the moment that it was taken
+    pub fn resume(
+        &self,
+        harness: &mut AvionicsHarness<PanicCaught>,
+        watchdog: &Watchdog,
+    ) -> anyhow::Result<Flight<'a>> {
+        watchdog.watch(|| harness.restore(&self.avionics))?;
+
+        Ok(self.flight.clone())
+    }
+}
+
+/// Print every message logged by the avionics since the last time this was
+/// called, as having been logged at the given time in seconds
+fn log_messages(
+    harness: &mut AvionicsHarness<PanicCaught>,
+    shell: &mut Shell,
+    time: f64,
+) -> io::Result<()> {
+    for (level, message) in harness.logs() {
+        shell.log(level, format!("T+{:.2}s {}", time, message))?;
+    }
+
+    Ok(())
+}
+
+/// Print every message logged by the avionics since the last time this was
+/// called, as having been logged during the given event outside of a flight,
+/// such as `init`
+pub fn log_event_messages(
+    harness: &mut AvionicsHarness<PanicCaught>,
+    shell: &mut Shell,
+    event: &str,
+) -> io::Result<()> {
+    for (level, message) in harness.logs() {
+        shell.log(level, format!("during {}: {}", event, message))?;
+    }
+
+    Ok(())
+}
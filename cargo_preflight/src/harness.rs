@@ -1,40 +1,101 @@
-use core::panic::PanicInfo;
-use std::{marker::PhantomData, mem::MaybeUninit, path::Path, sync::RwLock};
+use std::{
+    marker::PhantomData,
+    mem,
+    path::Path,
+    sync::{Mutex, RwLock},
+};
 
-use dlopen::symbor::{Container, Ref, SymBorApi, Symbol};
+use anyhow::{bail, Context};
+use dlopen::symbor::{Container, Library, SymBorApi, Symbol};
 use lazy_static::lazy_static;
 use preflight::{
-    abi::{AvionicsGuide, SetPanicCallback},
-    Avionics, Control, Sensors,
+    abi::{
+        AbiHeader, AvionicsAbort, AvionicsChannels, AvionicsCommand, AvionicsGetParameter,
+        AvionicsGuide, AvionicsInit, AvionicsLogs, AvionicsParameterNames, AvionicsRecreate,
+        AvionicsReset, AvionicsRestore, AvionicsRestorePersistent, AvionicsSave,
+        AvionicsSavePersistent, AvionicsSetParameter, AvionicsShutdown, AvionicsTelemetry,
+        ChannelEvent, FfiStr, GuideOutcome, PanicEvent, RestoreOutcome, SetPanicCallback,
+        SignalLayout, TelemetryEvent, ABI_VERSION,
+    },
+    log::{Level, Record},
+    snapshot::SnapshotError,
+    telemetry::TelemetryValue,
+    AbortCause, Command, CommandResponse, Control, Sensors,
 };
 
-use crate::{args::PanicHandleArguments, panic::panic_handle};
+use crate::{args::PanicHandleArguments, panic::panic_handle, signals::SignalMap};
+
+/// Symbol under which the harness exports its [`AbiHeader`]
+const ABI_HEADER_SYMBOL: &str = "__PREFLIGHT_ABI";
+/// Symbol under which the harness exports its [`SignalLayout`]
+const SIGNAL_LAYOUT_SYMBOL: &str = "__PREFLIGHT_SIGNALS";
 
 #[derive(SymBorApi)]
 struct HarnessImpl<'a> {
+    /// Names of the channels of the avionics' sensors
+    avionics_sensor_channels: Symbol<'a, AvionicsChannels>,
+    /// Names of the channels of the avionics' guidance
+    avionics_guidance_channels: Symbol<'a, AvionicsChannels>,
+    /// Lifecycle hook to initialize the avionics
+    avionics_init: Symbol<'a, AvionicsInit>,
     /// The callback into the avionics to request a control signal for guidance
-    avionics_guide: Ref<'a, AvionicsGuide>,
-    /// A flag to ensure that the shared object was created with preflight
-    #[dlopen_name = "__PREFLIGHT"]
-    preflight: Ref<'a, bool>,
+    avionics_guide: Symbol<'a, AvionicsGuide>,
+    /// Method to send a command to the avionics
+    avionics_command: Symbol<'a, AvionicsCommand>,
+    /// Names of the avionics' parameters
+    avionics_parameter_names: Symbol<'a, AvionicsParameterNames>,
+    /// Method to read one of the avionics' parameters
+    avionics_get_parameter: Symbol<'a, AvionicsGetParameter>,
+    /// Method to set one of the avionics' parameters
+    avionics_set_parameter: Symbol<'a, AvionicsSetParameter>,
+    /// Method to stream the values that the avionics publish about themselves
+    avionics_telemetry: Symbol<'a, AvionicsTelemetry>,
+    /// Method to stream a snapshot of the avionics' state
+    avionics_save: Symbol<'a, AvionicsSave>,
+    /// Method to restore the avionics' state from a snapshot
+    avionics_restore: Symbol<'a, AvionicsRestore>,
+    /// Method to stream the avionics' persistent storage
+    avionics_save_persistent: Symbol<'a, AvionicsSavePersistent>,
+    /// Method to restore the avionics' persistent storage
+    avionics_restore_persistent: Symbol<'a, AvionicsRestorePersistent>,
+    /// Method to create the avionics anew, as if the flight computer had reset
+    avionics_recreate: Symbol<'a, AvionicsRecreate>,
+    /// Lifecycle hook to handle an abort
+    avionics_abort: Symbol<'a, AvionicsAbort>,
+    /// Lifecycle hook to warm reset the avionics
+    avionics_reset: Symbol<'a, AvionicsReset>,
+    /// Lifecycle hook to shut down the avionics
+    avionics_shutdown: Symbol<'a, AvionicsShutdown>,
     /// Method to set the panic callback in order to be able to handle avionic panics
-    set_panic_callback: Ref<'a, SetPanicCallback>,
+    set_panic_callback: Symbol<'a, SetPanicCallback>,
+    /// Method to receive the messages logged by the avionics since it was last called
+    avionics_logs: Symbol<'a, AvionicsLogs>,
 }
 
 pub struct AvionicsHarness<P: AvionicsHarnessState> {
     harness: Container<HarnessImpl<'static>>,
+    signals: SignalMap,
+    /// Names of the avionics' parameters, in the order of their indices
+    parameters: Vec<String>,
+    /// The value of each parameter once the avionics were initialized, if the
+    /// avionics reported it
+    initial_parameters: Vec<Option<f64>>,
+    /// The value that each parameter was set to since they were last restored,
+    /// which a reboot of the avionics would otherwise lose
+    overrides: Vec<Option<f64>>,
+    /// Buffer that the sensors are flattened into before being passed to the avionics
+    sensors: Vec<f64>,
+    /// Buffer that the avionics flatten their guidance into
+    guidance: Vec<f64>,
     _panic: PhantomData<P>,
 }
 
 lazy_static! {
-    static ref LAST_SENSORS: RwLock<Sensors> = RwLock::new(
-        #[allow(unsafe_code, clippy::clippy::uninit_assumed_init)]
-        // This is safe, since the sensors are always set before they are ever going to be read from
-        // since a panic cannot happen until after this has been set at least once
-        unsafe {
-            MaybeUninit::uninit().assume_init()
-        }
-    );
+    /// The sensor channels last passed to the avionics, for the panic report
+    static ref LAST_SENSORS: RwLock<Vec<f64>> = RwLock::new(Vec::new());
+    /// The format string and frame of each message logged by the avionics that
+    /// has not yet been taken by [`AvionicsHarness::logs`]
+    static ref LOGS: Mutex<Vec<(String, Vec<u8>)>> = Mutex::new(Vec::new());
 }
 
 pub struct PanicHang;
@@ -45,50 +106,427 @@ impl AvionicsHarnessState for PanicCaught {}
 
 pub trait AvionicsHarnessState {}
 
+impl<P: AvionicsHarnessState> AvionicsHarness<P> {
+    /// The avionics' sensor and guidance channels
+    pub fn signals(&self) -> &SignalMap {
+        &self.signals
+    }
+
+    /// The names of the avionics' parameters
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+}
+
 impl AvionicsHarness<PanicHang> {
-    pub fn load(so: &Path) -> Result<Option<Self>, dlopen::Error> {
+    /// Load the avionics harness from the shared object, ensuring that it speaks
+    /// the same ABI as this runner
+    pub fn load(so: &Path) -> anyhow::Result<Self> {
+        let library = Library::open(so).context("failed to open shared library")?;
+
         #[allow(unsafe_code)]
-        let harness: Container<HarnessImpl> = unsafe { Container::load(so) }?;
+        let header: &AbiHeader = unsafe { library.reference(ABI_HEADER_SYMBOL) }.context(
+            "the library was not setup using the `#[avionics_harness]` macro or is using an out of date dependency to preflight",
+        )?;
 
-        if *harness.preflight {
-            Ok(Some(AvionicsHarness {
-                harness,
-                _panic: PhantomData,
-            }))
-        } else {
-            Ok(None)
+        if header.version != ABI_VERSION {
+            bail!(
+                "the avionics were built against version {} of the preflight ABI, but this runner speaks version {}. \
+                make sure that the versions of `preflight` and `cargo-preflight` match",
+                header.version,
+                ABI_VERSION
+            );
+        }
+
+        if *header != AbiHeader::CURRENT {
+            bail!(
+                "the layout of the sensor and control types in the avionics does not match the layout expected by this runner \
+                ({:#018x} != {:#018x}). make sure that the versions of `preflight` and `cargo-preflight` match",
+                header.layout_fingerprint,
+                AbiHeader::CURRENT.layout_fingerprint
+            );
         }
+
+        #[allow(unsafe_code)]
+        let layout: SignalLayout = *unsafe { library.reference(SIGNAL_LAYOUT_SYMBOL) }
+            .context("the library does not describe its sensor and guidance signals")?;
+
+        #[allow(unsafe_code)]
+        let harness: Container<HarnessImpl> =
+            unsafe { Container::load(so) }.context("failed to load the avionics harness")?;
+
+        let sensors = channel_names(*harness.avionics_sensor_channels);
+        let guidance = channel_names(*harness.avionics_guidance_channels);
+
+        let parameters = channel_names(*harness.avionics_parameter_names);
+
+        if sensors.len() != layout.sensors || guidance.len() != layout.guidance {
+            bail!(
+                "the avionics named {} sensor and {} guidance channels, but have {} and {}",
+                sensors.len(),
+                guidance.len(),
+                layout.sensors,
+                layout.guidance
+            );
+        }
+
+        Ok(AvionicsHarness {
+            harness,
+            signals: SignalMap::new(sensors, guidance),
+            overrides: vec![None; parameters.len()],
+            parameters,
+            initial_parameters: Vec::new(),
+            sensors: vec![0.0; layout.sensors],
+            guidance: vec![0.0; layout.guidance],
+            _panic: PhantomData,
+        })
     }
 
     /// Setup panic handling for the guidance system using the given arguments
     pub fn setup_panic(self, args: PanicHandleArguments) -> AvionicsHarness<PanicCaught> {
         lazy_static! {
             static ref PANIC_ARGS: RwLock<PanicHandleArguments> = RwLock::new(Default::default());
+            static ref PANIC_REPORT: RwLock<(String, String)> = RwLock::new(Default::default());
+            static ref SENSOR_CHANNELS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+        }
+
+        extern "C" fn panic_callback(event: PanicEvent<'_>) {
+            match event {
+                PanicEvent::Info(fragment) => PANIC_REPORT.write().unwrap().0 += ffi_str(&fragment),
+                PanicEvent::AvionicsState(fragment) => {
+                    PANIC_REPORT.write().unwrap().1 += ffi_str(&fragment)
+                }
+                PanicEvent::Finished => {
+                    let (info, avionics) = &*PANIC_REPORT.read().unwrap();
+
+                    let sensors = SENSOR_CHANNELS
+                        .read()
+                        .unwrap()
+                        .iter()
+                        .zip(LAST_SENSORS.read().unwrap().iter())
+                        .map(|(name, value)| format!("{} = {}\n", name, value))
+                        .collect::<String>();
+
+                    panic_handle(info, avionics, &sensors, &PANIC_ARGS.read().unwrap());
+                }
+            }
         }
 
         *PANIC_ARGS.write().unwrap() = args;
+        *SENSOR_CHANNELS.write().unwrap() = self.signals.sensors.clone();
 
-        (self.harness.set_panic_callback)(|panic_info: &PanicInfo, avionics: &dyn Avionics| {
-            panic_handle(
-                panic_info,
-                avionics,
-                &LAST_SENSORS.read().unwrap(),
-                &PANIC_ARGS.read().unwrap(),
-            );
-        });
+        (self.harness.set_panic_callback)(panic_callback);
 
         AvionicsHarness {
             _panic: PhantomData,
             harness: self.harness,
+            signals: self.signals,
+            parameters: self.parameters,
+            initial_parameters: self.initial_parameters,
+            overrides: self.overrides,
+            sensors: self.sensors,
+            guidance: self.guidance,
         }
     }
 }
 
 impl AvionicsHarness<PanicCaught> {
+    /// Initialize the avionics as if the flight computer had just booted,
+    /// remembering the value of each parameter for
+    /// [`AvionicsHarness::restore_parameters`]
+    pub fn init(&mut self) {
+        (self.harness.avionics_init)();
+
+        self.initial_parameters = (0..self.parameters.len())
+            .map(|index| {
+                let mut value = 0.0;
+                let reported = (self.harness.avionics_get_parameter)(index, &mut value);
+
+                reported.then_some(value)
+            })
+            .collect();
+    }
+
     /// Call into the avionics to request a guidance control signal given the inputted sensor data
-    pub fn guide(&mut self, sensors: Sensors) -> Control {
-        *LAST_SENSORS.write().unwrap() = sensors;
+    pub fn guide(&mut self, sensors: &Sensors) -> Control {
+        self.signals.fill_sensors(sensors, &mut self.sensors);
+        LAST_SENSORS.write().unwrap().clone_from(&self.sensors);
+
+        match (self.harness.avionics_guide)(self.sensors.as_ptr(), self.guidance.as_mut_ptr()) {
+            GuideOutcome::Guidance => Control::Guidance(self.signals.read_guidance(&self.guidance)),
+            GuideOutcome::Abort(cause) => {
+                // The avionics leave the guidance untouched when they abort, which
+                // would otherwise be logged as if they had produced it again
+                self.guidance.iter_mut().for_each(|value| *value = 0.0);
+
+                Control::ABORT(cause)
+            }
+            GuideOutcome::RecoverableFailure => Control::RecoverableFailure,
+        }
+    }
+
+    /// The sensor and guidance channels of the last call to [`AvionicsHarness::guide`],
+    /// with every guidance channel zeroed if the avionics aborted
+    pub fn last_signals(&self) -> (&[f64], &[f64]) {
+        (&self.sensors, &self.guidance)
+    }
+
+    /// Send a command to the avionics, as if it had been received from the ground
+    pub fn command(&mut self, command: &Command<'_>) -> CommandResponse {
+        (self.harness.avionics_command)(command.into())
+    }
+
+    /// The current value of the parameter with the given name
+    pub fn parameter(&mut self, name: &str) -> anyhow::Result<f64> {
+        let index = self.parameter_index(name)?;
+        let mut value = 0.0;
+
+        if !(self.harness.avionics_get_parameter)(index, &mut value) {
+            bail!(
+                "the avionics did not report the value of parameter `{}`",
+                name
+            );
+        }
+
+        Ok(value)
+    }
+
+    /// Set the parameter with the given name, as if it had been set before the
+    /// avionics were flashed
+    pub fn set_parameter(&mut self, name: &str, value: f64) -> anyhow::Result<()> {
+        let index = self.parameter_index(name)?;
+
+        if !(self.harness.avionics_set_parameter)(index, value) {
+            bail!(
+                "the avionics did not set parameter `{}` to {}, which it may not be able to hold",
+                name,
+                value
+            );
+        }
+        self.overrides[index] = Some(value);
+
+        Ok(())
+    }
+
+    /// Set every parameter back to the value it had once the avionics were
+    /// initialized, undoing any that were set since
+    pub fn restore_parameters(&mut self) -> anyhow::Result<()> {
+        for (index, value) in self.initial_parameters.iter().enumerate() {
+            if let Some(value) = *value {
+                if !(self.harness.avionics_set_parameter)(index, value) {
+                    bail!(
+                        "the avionics did not set parameter `{}` back to {}",
+                        self.parameters[index],
+                        value
+                    );
+                }
+            }
+        }
+        self.overrides.iter_mut().for_each(|value| *value = None);
+
+        Ok(())
+    }
+
+    /// The index of the parameter with the given name
+    fn parameter_index(&self, name: &str) -> anyhow::Result<usize> {
+        self.parameters
+            .iter()
+            .position(|parameter| parameter == name)
+            .with_context(|| {
+                if self.parameters.is_empty() {
+                    format!(
+                        "the avionics have no parameters, so `{}` can not be set",
+                        name
+                    )
+                } else {
+                    format!(
+                        "the avionics have no parameter named `{}`, expected one of: {}",
+                        name,
+                        self.parameters.join(", ")
+                    )
+                }
+            })
+    }
+
+    /// Every message logged by the avionics since this was last called, decoded
+    pub fn logs(&mut self) -> Vec<(Level, String)> {
+        (self.harness.avionics_logs)(log_callback);
+
+        mem::take(&mut *LOGS.lock().unwrap())
+            .into_iter()
+            .map(|(format, frame)| match Record::decode(&frame) {
+                Some(record) => (record.level, record.message(&format).to_string()),
+                None => (
+                    Level::Error,
+                    format!("malformed log frame for `{}`: {:02x?}", format, frame),
+                ),
+            })
+            .collect()
+    }
+
+    /// Every value that the avionics publish, in the order that they published them
+    pub fn telemetry(&mut self) -> Vec<(String, TelemetryValue)> {
+        lazy_static! {
+            static ref TELEMETRY: Mutex<Vec<(String, TelemetryValue)>> = Mutex::new(Vec::new());
+        }
+
+        extern "C" fn telemetry_callback(event: TelemetryEvent<'_>) {
+            let (name, value) = match event {
+                TelemetryEvent::Scalar(name, value) => (name, TelemetryValue::Scalar(value)),
+                TelemetryEvent::Vector(name, value) => (name, TelemetryValue::Vector(value)),
+            };
+
+            TELEMETRY
+                .lock()
+                .unwrap()
+                .push((ffi_str(&name).to_string(), value));
+        }
+
+        (self.harness.avionics_telemetry)(telemetry_callback);
+
+        mem::take(&mut TELEMETRY.lock().unwrap())
+    }
+
+    /// Snapshot the state of the avionics, so that they can be restored to it
+    /// with [`AvionicsHarness::restore`]
+    pub fn snapshot(&mut self) -> anyhow::Result<Vec<u8>> {
+        match collect_snapshot(*self.harness.avionics_save) {
+            Some(snapshot) => Ok(snapshot),
+            None => bail!(
+                "the avionics can not be snapshotted, since they do not implement `Avionics::snapshot`"
+            ),
+        }
+    }
+
+    /// Restore the avionics to the state of a snapshot taken by
+    /// [`AvionicsHarness::snapshot`]
+    pub fn restore(&mut self, snapshot: &[u8]) -> anyhow::Result<()> {
+        match (self.harness.avionics_restore)(snapshot.as_ptr(), snapshot.len()) {
+            RestoreOutcome::Restored => Ok(()),
+            RestoreOutcome::Failed(SnapshotError::Unsupported) => bail!(
+                "the avionics can not be restored, since they do not implement `Avionics::snapshot`"
+            ),
+            RestoreOutcome::Failed(err) => bail!(
+                "the avionics could not be restored from a snapshot of {} bytes ({:?}), \
+                it may have been taken from a different build of the avionics",
+                snapshot.len(),
+                err
+            ),
+        }
+    }
+
+    /// Reset the flight computer, as if it had browned out, tearing down the
+    /// avionics and creating them anew with only their persistent storage kept
+    ///
+    /// The parameters set since they were last restored are set again once the
+    /// avionics are initialized, as they are before every scenario, since they
+    /// stand in for values that the avionics were flashed with
+    pub fn reboot(&mut self) -> anyhow::Result<()> {
+        let persistent = collect_snapshot(*self.harness.avionics_save_persistent);
+
+        (self.harness.avionics_recreate)();
+
+        if let Some(persistent) = persistent {
+            if let RestoreOutcome::Failed(err) =
+                (self.harness.avionics_restore_persistent)(persistent.as_ptr(), persistent.len())
+            {
+                bail!(
+                    "the persistent storage of the avionics could not be restored after a reset ({:?})",
+                    err
+                );
+            }
+        }
+
+        (self.harness.avionics_init)();
+
+        for (index, value) in self.overrides.iter().enumerate() {
+            if let Some(value) = *value {
+                if !(self.harness.avionics_set_parameter)(index, value) {
+                    bail!(
+                        "the avionics did not set parameter `{}` to {} again after a reset",
+                        self.parameters[index],
+                        value
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Notify the avionics of the abort that they signaled
+    pub fn abort(&mut self, cause: &AbortCause) {
+        (self.harness.avionics_abort)(cause)
+    }
+
+    /// Warm reset the avionics
+    pub fn reset(&mut self) {
+        (self.harness.avionics_reset)()
+    }
+
+    /// Shut down the avionics as if the flight computer was powering off
+    pub fn shutdown(&mut self) {
+        (self.harness.avionics_shutdown)()
+    }
+}
+
+/// Collect each message logged by the avionics for [`AvionicsHarness::logs`]
+extern "C" fn log_callback(format: FfiStr<'_>, frame: *const u8, len: usize) {
+    #[allow(unsafe_code)]
+    // The avionics pass the frame that they encoded into as a pointer and length
+    let frame = unsafe { std::slice::from_raw_parts(frame, len) };
+
+    LOGS.lock()
+        .unwrap()
+        .push((ffi_str(&format).to_string(), frame.to_vec()));
+}
+
+/// Collect every piece of the state streamed by the given function, if it has
+/// any state to stream
+fn collect_snapshot(save: AvionicsSave) -> Option<Vec<u8>> {
+    lazy_static! {
+        static ref SNAPSHOT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+    }
+
+    extern "C" fn snapshot_callback(bytes: *const u8, len: usize) {
+        #[allow(unsafe_code)]
+        // The avionics pass each piece of the snapshot as a pointer and length
+        let bytes = unsafe { std::slice::from_raw_parts(bytes, len) };
+
+        SNAPSHOT.lock().unwrap().extend_from_slice(bytes);
+    }
+
+    let saved = save(snapshot_callback);
+    let snapshot = mem::take(&mut *SNAPSHOT.lock().unwrap());
+
+    saved.then_some(snapshot)
+}
+
+/// Collect the name of every channel streamed by the given function
+fn channel_names(channels: AvionicsChannels) -> Vec<String> {
+    lazy_static! {
+        static ref CHANNEL_NAMES: Mutex<(String, Vec<String>)> = Mutex::new(Default::default());
+    }
+
+    extern "C" fn channel_callback(event: ChannelEvent<'_>) {
+        let (name, names) = &mut *CHANNEL_NAMES.lock().unwrap();
+
+        match event {
+            ChannelEvent::Name(fragment) => *name += ffi_str(&fragment),
+            ChannelEvent::Finished => names.push(mem::take(name)),
+        }
+    }
+
+    channels(channel_callback);
+
+    mem::take(&mut CHANNEL_NAMES.lock().unwrap().1)
+}
 
-        (self.harness.avionics_guide)(&LAST_SENSORS.read().unwrap())
+/// Borrow the string passed over the ABI
+fn ffi_str<'a>(string: &FfiStr<'a>) -> &'a str {
+    #[allow(unsafe_code)]
+    // The avionics only ever construct `FfiStr`s out of valid `&str`s
+    unsafe {
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(string.as_ptr(), string.len()))
     }
 }
@@ -4,45 +4,61 @@
 extern crate dlopen_derive;
 
 use std::{
-    io, process,
-    time::{SystemTime, UNIX_EPOCH},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context, Result};
-use args::{CargoArguments, CargoSpawnedArguments, PreflightCommand};
+use args::{
+    CargoArguments, CargoSpawnedArguments, LogArguments, PreflightCommand, ReplayArguments,
+    SimulationArguments, TuneArguments,
+};
 use cargo::{build_artifact, get_host_target, get_metadata};
+use exit::{ExitCode, HarnessMissing};
+use flight::log_event_messages;
 use harness::{AvionicsHarness, PanicCaught, PanicHang};
-use preflight::{
-    uom::{
-        self,
-        si::{
-            length::{meter, Length},
-            SI,
-        },
-    },
-    Sensors, Vector3,
-};
+use scenario::Scenario;
 use shell::Shell;
+use signals::SignalLog;
 use structopt::StructOpt;
-use uom::si::time::{second, Time};
+use telemetry::TelemetryLog;
+use watchdog::Watchdog;
 
 mod args;
 mod cargo;
+mod exit;
+mod flight;
 mod harness;
 mod panic;
+mod scenario;
 mod shell;
+mod signals;
+mod sim;
+mod telemetry;
+mod tune;
+mod watchdog;
 
 fn main() -> io::Result<()> {
     let mut shell = Shell::new();
 
-    let CargoSpawnedArguments::Preflight(args) = CargoSpawnedArguments::from_args();
+    let CargoSpawnedArguments::Preflight(args) = match CargoSpawnedArguments::from_args_safe() {
+        Ok(args) => args,
+        // The help and version are printed as usual, and exit successfully
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            eprintln!("{}", e.message);
+
+            ExitCode::UsageError.exit();
+        }
+    };
 
     match args.command {
         PreflightCommand::Check { cargo } => {
             if let Err(e) = load_harness(&cargo, &mut shell) {
                 shell.error(format!("{:#}", e))?;
 
-                process::exit(1);
+                ExitCode::exit_with_error(&e);
             } else {
                 shell.status("Success", "built and loaded avionics harness successfully")?;
             }
@@ -52,25 +68,75 @@ fn main() -> io::Result<()> {
             panic,
             display,
             sim,
+            log,
+            replay,
         } => match load_harness(&cargo, &mut shell) {
             Err(e) => {
                 shell.error(format!("{:#}", e))?;
 
-                process::exit(1); // TODO: get status code from rustc
+                ExitCode::exit_with_error(&e);
             }
-            Ok(harness) => match test_harness(harness.setup_panic(panic)) {
-                Err(e) => {
+            Ok((harness, package_root)) => {
+                match test_harness(
+                    harness.setup_panic(panic),
+                    &sim,
+                    &log,
+                    &replay,
+                    &package_root,
+                    &mut shell,
+                ) {
+                    Err(e) => {
+                        shell.error(format!("{:#}", e))?;
+
+                        ExitCode::InternalError.exit();
+                    }
+                    Ok(false) => {
+                        shell.error("one or more scenarios failed")?;
+
+                        ExitCode::ScenarioFailed.exit();
+                    }
+                    Ok(true) => shell.status("Finished", "all scenarios passed")?,
+                }
+            }
+        },
+        PreflightCommand::Tune {
+            cargo,
+            panic,
+            sim,
+            tune,
+        } => match load_harness(&cargo, &mut shell) {
+            Err(e) => {
+                shell.error(format!("{:#}", e))?;
+
+                ExitCode::exit_with_error(&e);
+            }
+            Ok((harness, package_root)) => {
+                if let Err(e) = tune_harness(
+                    harness.setup_panic(panic),
+                    &sim,
+                    &tune,
+                    &package_root,
+                    &mut shell,
+                ) {
                     shell.error(format!("{:#}", e))?;
 
-                    process::exit(1);
+                    ExitCode::InternalError.exit();
                 }
-                Ok(false) => {
-                    shell.error("harness failed to run")?;
+            }
+        },
+        PreflightCommand::Parameters { cargo, panic } => match load_harness(&cargo, &mut shell) {
+            Err(e) => {
+                shell.error(format!("{:#}", e))?;
+
+                ExitCode::exit_with_error(&e);
+            }
+            Ok((harness, _)) => {
+                if let Err(e) = list_parameters(harness.setup_panic(panic), &mut shell) {
+                    shell.error(format!("{:#}", e))?;
 
-                    process::exit(1);
+                    ExitCode::InternalError.exit();
                 }
-                Ok(true) => shell.status("Finished", "TODO:")?,
-            },
+            }
         },
         // PreflightCommand::Simulate { .. } => unimplemented!(),
     }
@@ -78,40 +144,181 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
-fn test_harness(mut harness: AvionicsHarness<PanicCaught>) -> Result<bool> {
-    let timestep = 1.0/60.0;
-
-    let mut running_time = 0.0;
-
-    for _ in 0..10 {
-        println!(
-            "{:?}",
-            Length::<SI<f64>, _>::new::<meter>(0.0f64)
-                .into_format_args(meter, preflight::uom::fmt::DisplayStyle::Description),
-        );
-
-        let result = harness.guide(Sensors {
-            altitude: Length::new::<meter>(0.0),
-            linear_acceleration: Vector3::zero(),
-            gravity_acceleration: Vector3::zero(),
-            both_acceleration: Vector3::zero(),
-            orientation: Vector3::zero(),
-            angular_velocity: Vector3::zero(),
-            magnetic_field: Vector3::zero(),
-            running_time: Time::new::<second>(running_time),
-        });
-        dbg!(&result); // TODO:
-
-        running_time += timestep;
+/// Load the scenarios to fly, falling back to the default scenario if there are none
+fn load_scenarios(
+    sim: &SimulationArguments,
+    package_root: &Path,
+    shell: &mut Shell,
+) -> Result<Vec<Scenario>> {
+    let scenarios_directory = sim
+        .scenarios
+        .clone()
+        .unwrap_or_else(|| package_root.join("scenarios"));
+    let mut scenarios = Scenario::load_all(&scenarios_directory)?;
+
+    if scenarios.is_empty() {
+        shell.note(format!(
+            "no scenarios were found in {:?}, flying the default scenario",
+            scenarios_directory
+        ))?;
+
+        scenarios.push(Scenario::default());
+    }
+
+    Ok(scenarios)
+}
+
+fn test_harness(
+    mut harness: AvionicsHarness<PanicCaught>,
+    sim: &SimulationArguments,
+    log_args: &LogArguments,
+    replay: &ReplayArguments,
+    package_root: &Path,
+    shell: &mut Shell,
+) -> Result<bool> {
+    let watchdog = Watchdog::spawn(Duration::from_millis(sim.watchdog));
+    let scenarios = load_scenarios(sim, package_root, shell)?;
+
+    watchdog.watch(|| harness.init());
+    log_event_messages(&mut harness, shell, "init")?;
+
+    let mut passed = true;
+
+    for (i, scenario) in scenarios.iter().enumerate() {
+        // Every scenario after the first starts from a warm reset of the avionics
+        if i != 0 {
+            watchdog.watch(|| harness.reset());
+            log_event_messages(&mut harness, shell, "reset")?;
+        }
+
+        shell.status("Running", format!("scenario `{}`", scenario.name))?;
+
+        // The parameters set for one scenario are not carried over into the next
+        harness.restore_parameters()?;
+
+        // The parameters given on the command line take precedence over the scenario's
+        for (name, value) in scenario.parameters.iter().chain(&sim.parameters) {
+            harness.set_parameter(name, *value)?;
+            shell.status("Setting", format!("parameter `{}` to {}", name, value))?;
+        }
+
+        let log = match &log_args.signal_log {
+            Some(directory) => Some(SignalLog::create(
+                &directory.join(format!("{}.csv", scenario.name)),
+                harness.signals(),
+            )?),
+            None => None,
+        };
+
+        let telemetry = match &log_args.telemetry_log {
+            Some(directory) => Some(TelemetryLog::create(
+                &directory.join(format!("{}.csv", scenario.name)),
+            )?),
+            None => None,
+        };
+
+        let (report, mut failures) = match replay.checkpoint.or(scenario.branch) {
+            Some(checkpoint) => {
+                scenario.replay(&mut harness, &watchdog, shell, log, telemetry, checkpoint)?
+            }
+            None => (
+                scenario.run(&mut harness, &watchdog, shell, log, telemetry)?,
+                Vec::new(),
+            ),
+        };
+        failures.extend(scenario.check(&report));
+
+        if failures.is_empty() {
+            shell.status(
+                "Passed",
+                format!(
+                    "scenario `{}` after T+{:.2}s",
+                    scenario.name, report.duration
+                ),
+            )?;
+        } else {
+            passed = false;
+
+            for failure in failures {
+                shell.error(format!("scenario `{}` failed: {}", scenario.name, failure))?;
+            }
+        }
+    }
+
+    watchdog.watch(|| harness.shutdown());
+    log_event_messages(&mut harness, shell, "shutdown")?;
+
+    Ok(passed)
+}
+
+fn tune_harness(
+    mut harness: AvionicsHarness<PanicCaught>,
+    sim: &SimulationArguments,
+    tune_args: &TuneArguments,
+    package_root: &Path,
+    shell: &mut Shell,
+) -> Result<()> {
+    let watchdog = Watchdog::spawn(Duration::from_millis(sim.watchdog));
+    let mut scenarios = load_scenarios(sim, package_root, shell)?;
+
+    if !tune_args.scenarios.is_empty() {
+        for name in &tune_args.scenarios {
+            if !scenarios.iter().any(|scenario| &scenario.name == name) {
+                return Err(anyhow!(
+                    "there is no scenario named `{}` to tune over",
+                    name
+                ));
+            }
+        }
+
+        scenarios.retain(|scenario| tune_args.scenarios.contains(&scenario.name));
+    }
+
+    watchdog.watch(|| harness.init());
+    log_event_messages(&mut harness, shell, "init")?;
+
+    let values = tune::tune(&mut harness, &watchdog, &scenarios, sim, tune_args, shell)?;
+
+    watchdog.watch(|| harness.shutdown());
+    log_event_messages(&mut harness, shell, "shutdown")?;
+
+    // Printed as a `[parameters]` table that can be pasted into a scenario
+    for (name, value) in tune_args.parameters.iter().zip(values) {
+        println!("{} = {}", name, value);
+    }
+
+    Ok(())
+}
+
+fn list_parameters(mut harness: AvionicsHarness<PanicCaught>, shell: &mut Shell) -> Result<()> {
+    harness.init();
+    log_event_messages(&mut harness, shell, "init")?;
+
+    if harness.parameters().is_empty() {
+        shell.note("the avionics do not have any parameters")?;
     }
 
-    Ok(true)
+    for name in harness.parameters().to_vec() {
+        let value = harness.parameter(&name)?;
+
+        // Most parameters are f32s, which would otherwise print with spurious digits
+        if f64::from(value as f32) == value {
+            println!("{} = {}", name, value as f32);
+        } else {
+            println!("{} = {}", name, value);
+        }
+    }
+
+    harness.shutdown();
+    log_event_messages(&mut harness, shell, "shutdown")?;
+
+    Ok(())
 }
 
 fn load_harness(
     cargo_args: &CargoArguments,
     shell: &mut Shell,
-) -> anyhow::Result<AvionicsHarness<PanicHang>> {
+) -> anyhow::Result<(AvionicsHarness<PanicHang>, PathBuf)> {
     let host_target = get_host_target()?;
 
     let metadata = get_metadata(&cargo_args).map_err(|e| match e {
@@ -121,37 +328,78 @@ fn load_harness(
         e => anyhow!("{}", e),
     })?;
 
-    let package = metadata
-        .root_package()
-        .context("could not find the root package for this workspace")?;
+    let package = match &cargo_args.package {
+        Some(name) => metadata
+            .packages
+            .iter()
+            .find(|p| &p.name == name && metadata.workspace_members.contains(&p.id))
+            .with_context(|| format!("package `{}` is not a member of this workspace", name))?,
+        None => metadata.root_package().context(
+            "could not find the root package for this workspace, use `--package` to select one",
+        )?,
+    };
+
+    let target = match &cargo_args.target {
+        Some(target) if target != &host_target => {
+            shell.warning(format!(
+                "avionics built for `{}` may not be able to be loaded on this host",
+                target
+            ))?;
+            shell.note(format!("the host target is `{}`", host_target))?;
+
+            target
+        }
+        Some(target) => target,
+        None => &host_target,
+    };
 
-    let has_dylib_target = package
-        .targets
-        .iter()
-        .any(|t| t.kind.contains(&"dylib".to_string()));
+    let has_lib_target = package.targets.iter().any(|t| {
+        t.kind.iter().any(|kind| {
+            matches!(
+                kind.as_str(),
+                "lib" | "rlib" | "dylib" | "cdylib" | "staticlib"
+            )
+        })
+    });
 
-    if has_dylib_target {
-        shell.warning(
-            "the crate probably should not have a library target with a crate_type of 'dylib'",
-        )?;
-        shell.note("this will be added automatically when this command is run. crate_type should be `staticlib` or `cdylib`")?;
+    if !has_lib_target {
+        return Err(anyhow!(
+            "the package `{}` does not have a library target to build the avionics from",
+            package.name
+        ))
+        .context(HarnessMissing);
     }
 
-    match build_artifact(&cargo_args, &host_target, package)? {
+    match build_artifact(&cargo_args, target, package)? {
         None => Err(anyhow!(
             "the cargo build did not produce any valid artifacts"
-        )),
+        ))
+        .context(HarnessMissing),
         Some(artifact_file) => {
             shell.status("Loading", artifact_file.to_string_lossy())?;
 
-            let harness = AvionicsHarness::load(&artifact_file)
-                .context("failed to load built shared library")?;
+            let package_root = package
+                .manifest_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
 
-            if let Some(harness) = harness {
-                Ok(harness)
-            } else {
-                Err(anyhow!("the dylib was not setup using the `#[avionics_harness]` macro or is using an out of date dependency to preflight"))
+            let harness = AvionicsHarness::load(&artifact_file).context(HarnessMissing)?;
+
+            for channel in harness.signals().unsimulated_sensors() {
+                shell.warning(format!(
+                    "sensor channel `{}` is not simulated and will always read zero",
+                    channel
+                ))?;
             }
+            for channel in harness.signals().unsimulated_guidance() {
+                shell.note(format!(
+                    "guidance channel `{}` is not simulated and will only be logged",
+                    channel
+                ))?;
+            }
+
+            Ok((harness, package_root))
         }
     }
 }
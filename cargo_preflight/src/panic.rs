@@ -1,25 +1,18 @@
-use core::panic::PanicInfo;
 use std::{
     env,
     fs::File,
     io::{self, Write},
     path::{Path, PathBuf},
-    process, writeln,
+    writeln,
 };
 
 use indoc::indoc;
-use preflight::{Avionics, Sensors};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use uuid::Uuid;
 
-use crate::args::PanicHandleArguments;
+use crate::{args::PanicHandleArguments, exit::ExitCode};
 
-pub fn panic_handle(
-    panic_info: &PanicInfo,
-    avionics: &dyn Avionics,
-    sensors: &Sensors,
-    args: &PanicHandleArguments,
-) {
+pub fn panic_handle(panic_info: &str, avionics: &str, sensors: &str, args: &PanicHandleArguments) {
     let file_path = panic_file();
 
     {
@@ -28,15 +21,17 @@ pub fn panic_handle(
         write!(
             file,
             indoc! {"
-                {:#?}
+                {}
                 
                 //----INPUT----
-                {:#?}
+                {}
                 
                 //----CURRENT STATE----
-                {:#?}
+                {}
             "},
-            panic_info, sensors, avionics
+            textwrap::indent(panic_info, "// "),
+            sensors,
+            avionics
         )
         .expect("Failed to write to the panic report");
     }
@@ -47,7 +42,7 @@ pub fn panic_handle(
         open::that(file_path).expect("Failed to open the panic report");
     }
 
-    process::exit(1);
+    ExitCode::AvionicsPanicked.exit();
 }
 
 pub fn panic_file() -> PathBuf {
@@ -57,7 +52,7 @@ pub fn panic_file() -> PathBuf {
     Path::new(&tmp_dir).join(file_name)
 }
 
-pub fn panic_alert(panic_info: &PanicInfo, file: &Path) -> io::Result<()> {
+pub fn panic_alert(panic_info: &str, file: &Path) -> io::Result<()> {
     let mut stderr = StandardStream::stderr(ColorChoice::Auto);
 
     stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
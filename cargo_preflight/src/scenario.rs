@@ -0,0 +1,1034 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+    path::Path,
+};
+
+use anyhow::{ensure, Context};
+use preflight::{
+    estimation::{Complementary, Madgwick, Mahony},
+    AbortCause, Command, FlightPhase, PyroChannel,
+};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::{
+    flight::{Estimator, Flight},
+    harness::{AvionicsHarness, PanicCaught},
+    shell::Shell,
+    signals::SignalLog,
+    sim::{Dispersion, Fault, GnssParameters, LaunchSite, Motor, PyroDevice, VehicleParameters},
+    telemetry::TelemetryLog,
+    watchdog::Watchdog,
+};
+
+/// A simulated flight that the avionics are put through
+///
+/// Scenarios are loaded from the toml files in the `scenarios` directory of the
+/// package with the avionics
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    /// Name of the scenario, taken from the name of the file it was loaded from
+    #[serde(skip)]
+    pub name: String,
+    /// Motor that propels the vehicle
+    pub motor: Motor,
+    /// Longest amount of time to simulate in seconds
+    pub duration: f64,
+    /// Time between each step of the simulation in seconds
+    pub timestep: f64,
+    /// Seed of the random sensor noise, the same seed always produces the same flight
+    pub seed: u64,
+    /// Apogee in meters that the vehicle is meant to reach, which
+    /// `cargo preflight tune` penalizes missing
+    pub target_apogee: Option<f64>,
+    /// Physical parameters of the vehicle
+    pub vehicle: VehicleParameters,
+    /// Where the vehicle is launched from
+    pub site: LaunchSite,
+    /// Characteristics of the GNSS receiver
+    pub gnss: GnssParameters,
+    /// How much the vehicle varies between the runs of a Monte Carlo batch
+    pub dispersion: Dispersion,
+    /// Time in seconds at which the runs of a Monte Carlo batch branch off from a
+    /// single flight of the scenario, rather than each being flown from the pad
+    pub branch: Option<f64>,
+    /// Faults to inject into the sensors
+    pub fault: Vec<Fault>,
+    /// Parameters of the avionics to set before the flight, by their dotted names
+    #[serde(deserialize_with = "parameters")]
+    pub parameters: Vec<(String, f64)>,
+    /// Commands to send to the avionics as if from the ground
+    pub command: Vec<UplinkCommand>,
+    /// Resets of the flight computer, during which the vehicle keeps flying
+    pub reset: Vec<ProcessorReset>,
+    /// Expectations that the flight has to meet for the scenario to pass
+    pub expect: Vec<Expectation>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            name: "default".into(),
+            motor: Motor::default(),
+            duration: 30.0,
+            timestep: 0.01,
+            seed: 0,
+            target_apogee: None,
+            vehicle: VehicleParameters::default(),
+            site: LaunchSite::default(),
+            gnss: GnssParameters::default(),
+            dispersion: Dispersion::default(),
+            branch: None,
+            fault: Vec::new(),
+            parameters: Vec::new(),
+            command: Vec::new(),
+            reset: Vec::new(),
+            expect: Vec::new(),
+        }
+    }
+}
+
+/// Flatten the table of parameters into their dotted names, so that both
+/// `tilt.proportional = 0.2` and `[parameters.tilt]` tables can be used
+fn parameters<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, f64)>, D::Error> {
+    fn flatten(
+        prefix: Option<&str>,
+        table: toml::value::Table,
+        parameters: &mut Vec<(String, f64)>,
+    ) -> Result<(), String> {
+        for (key, value) in table {
+            let name = match prefix {
+                Some(prefix) => format!("{}.{}", prefix, key),
+                None => key,
+            };
+
+            match value {
+                toml::Value::Float(value) => parameters.push((name, value)),
+                toml::Value::Integer(value) => parameters.push((name, value as f64)),
+                toml::Value::Boolean(value) => {
+                    parameters.push((name, if value { 1.0 } else { 0.0 }))
+                }
+                toml::Value::Table(table) => flatten(Some(&name), table, parameters)?,
+                value => {
+                    return Err(format!(
+                        "parameter `{}` has to be a number or a boolean, found a {}",
+                        name,
+                        value.type_str()
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut parameters = Vec::new();
+    flatten(
+        None,
+        Deserialize::deserialize(deserializer)?,
+        &mut parameters,
+    )
+    .map_err(D::Error::custom)?;
+
+    Ok(parameters)
+}
+
+/// A command sent to the avionics at the given time in seconds, as if it had
+/// been received from the ground
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum UplinkCommand {
+    /// See [`Command::Arm`]
+    Arm { time: f64 },
+    /// See [`Command::Disarm`]
+    Disarm { time: f64 },
+    /// See [`Command::Abort`]
+    Abort { time: f64 },
+    /// See [`Command::SetParameter`]
+    SetParameter { time: f64, name: String, value: f64 },
+    /// See [`Command::EnterTestMode`]
+    EnterTestMode { time: f64 },
+}
+
+impl UplinkCommand {
+    /// Time in seconds at which the command is sent
+    pub fn time(&self) -> f64 {
+        match *self {
+            UplinkCommand::Arm { time }
+            | UplinkCommand::Disarm { time }
+            | UplinkCommand::Abort { time }
+            | UplinkCommand::SetParameter { time, .. }
+            | UplinkCommand::EnterTestMode { time } => time,
+        }
+    }
+
+    /// The command as it is sent to the avionics
+    pub fn command(&self) -> Command<'_> {
+        match self {
+            UplinkCommand::Arm { .. } => Command::Arm,
+            UplinkCommand::Disarm { .. } => Command::Disarm,
+            UplinkCommand::Abort { .. } => Command::Abort,
+            UplinkCommand::SetParameter { name, value, .. } => Command::SetParameter {
+                name,
+                value: *value,
+            },
+            UplinkCommand::EnterTestMode { .. } => Command::EnterTestMode,
+        }
+    }
+}
+
+impl Display for UplinkCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UplinkCommand::Arm { .. } => write!(f, "arm")?,
+            UplinkCommand::Disarm { .. } => write!(f, "disarm")?,
+            UplinkCommand::Abort { .. } => write!(f, "abort")?,
+            UplinkCommand::SetParameter { name, value, .. } => {
+                write!(f, "set `{}` to {}", name, value)?
+            }
+            UplinkCommand::EnterTestMode { .. } => write!(f, "enter test mode")?,
+        }
+
+        write!(f, " at T+{:.2}s", self.time())
+    }
+}
+
+/// A reset of the flight computer at the given time in seconds, such as from a
+/// brownout when the motor is ignited
+///
+/// The avionics are created anew from the `default` of their harness, with only
+/// their persistent storage and the parameters that were set surviving, and
+/// initialized once the flight computer comes back up.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessorReset {
+    /// Time in seconds since the start of the scenario that the flight computer
+    /// resets at
+    pub time: f64,
+    /// Time in seconds that the flight computer is down for, during which the
+    /// gimbal holds its last deflection and the avionics are not run
+    #[serde(default)]
+    pub downtime: f64,
+}
+
+impl Display for ProcessorReset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "flight computer at T+{:.2}s", self.time)?;
+
+        if self.downtime > 0.0 {
+            write!(f, ", down for {:.2}s", self.downtime)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Something that has to happen, or not happen, during a scenario
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Expectation {
+    /// The avionics abort, optionally with a specific cause or before a given time
+    Abort {
+        cause: Option<ExpectedAbortCause>,
+        before: Option<f64>,
+    },
+    /// The avionics never abort
+    NoAbort,
+    /// The avionics fire the given pyro channel, optionally within a window of time
+    Fire {
+        channel: u8,
+        after: Option<f64>,
+        before: Option<f64>,
+    },
+    /// The avionics never fire the given pyro channel
+    NoFire { channel: u8 },
+    /// The avionics enter the given phase of flight, optionally within a window
+    /// of time
+    Phase {
+        phase: ExpectedPhase,
+        after: Option<f64>,
+        before: Option<f64>,
+    },
+    /// One of preflight's attitude filters, fed the same sensor readings as the
+    /// avionics, stays within `max_error` degrees of the true attitude from the
+    /// given time onwards
+    Attitude {
+        filter: AttitudeFilter,
+        after: Option<f64>,
+        max_error: f64,
+    },
+    /// Preflight's apogee predictor, fed the same sensor readings as the
+    /// avionics, detects apogee within `within` seconds of the true apogee
+    Apogee { within: f64 },
+    /// After each reset of the flight computer, the avionics get back to at
+    /// least the phase of flight that they were in before it, optionally within
+    /// `within` seconds of the reset
+    Recover { within: Option<f64> },
+    /// After each reset of the flight computer, the avionics hold the given
+    /// parameter at `value` once they are back up, such as the value that the
+    /// scenario set it to
+    Parameter { name: String, value: f64 },
+}
+
+/// The attitude filters of [`preflight::estimation`], as they are written in
+/// scenario files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttitudeFilter {
+    Complementary,
+    Mahony,
+    Madgwick,
+}
+
+impl AttitudeFilter {
+    /// The filter with its default tuning
+    pub fn estimator(self) -> Estimator {
+        match self {
+            AttitudeFilter::Complementary => Estimator::Complementary(Complementary::default()),
+            AttitudeFilter::Mahony => Estimator::Mahony(Mahony::default()),
+            AttitudeFilter::Madgwick => Estimator::Madgwick(Madgwick::default()),
+        }
+    }
+}
+
+impl Display for AttitudeFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AttitudeFilter::Complementary => write!(f, "complementary"),
+            AttitudeFilter::Mahony => write!(f, "Mahony"),
+            AttitudeFilter::Madgwick => write!(f, "Madgwick"),
+        }
+    }
+}
+
+/// Mirror of [`AbortCause`] as it is written in scenario files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedAbortCause {
+    ExcessiveTilt,
+    SensorFailure,
+    LossOfThrust,
+    RangeSafety,
+    EstimatorDivergence,
+    User(u32),
+}
+
+impl From<ExpectedAbortCause> for AbortCause {
+    fn from(cause: ExpectedAbortCause) -> Self {
+        match cause {
+            ExpectedAbortCause::ExcessiveTilt => AbortCause::ExcessiveTilt,
+            ExpectedAbortCause::SensorFailure => AbortCause::SensorFailure,
+            ExpectedAbortCause::LossOfThrust => AbortCause::LossOfThrust,
+            ExpectedAbortCause::RangeSafety => AbortCause::RangeSafety,
+            ExpectedAbortCause::EstimatorDivergence => AbortCause::EstimatorDivergence,
+            ExpectedAbortCause::User(code) => AbortCause::User(code),
+        }
+    }
+}
+
+/// Mirror of [`FlightPhase`] as it is written in scenario files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedPhase {
+    Pad,
+    Armed,
+    Boost,
+    Coast,
+    Apogee,
+    DrogueDescent,
+    MainDescent,
+    Landed,
+}
+
+impl From<ExpectedPhase> for FlightPhase {
+    fn from(phase: ExpectedPhase) -> Self {
+        match phase {
+            ExpectedPhase::Pad => FlightPhase::Pad,
+            ExpectedPhase::Armed => FlightPhase::Armed,
+            ExpectedPhase::Boost => FlightPhase::Boost,
+            ExpectedPhase::Coast => FlightPhase::Coast,
+            ExpectedPhase::Apogee => FlightPhase::Apogee,
+            ExpectedPhase::DrogueDescent => FlightPhase::DrogueDescent,
+            ExpectedPhase::MainDescent => FlightPhase::MainDescent,
+            ExpectedPhase::Landed => FlightPhase::Landed,
+        }
+    }
+}
+
+/// An abort signaled by the avionics
+#[derive(Debug, Clone, Copy)]
+pub struct AbortEvent {
+    /// Why the avionics aborted
+    pub cause: AbortCause,
+    /// Time in seconds since the start of the scenario that the abort was signaled
+    pub time: f64,
+}
+
+impl Display for AbortEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at T+{:.2}s", self.cause, self.time)
+    }
+}
+
+/// The first time that the avionics fired a pyro channel
+#[derive(Debug, Clone, Copy)]
+pub struct PyroEvent {
+    /// The channel that was fired
+    pub channel: PyroChannel,
+    /// The device wired to the channel, if any
+    pub device: Option<PyroDevice>,
+    /// Time in seconds since the start of the scenario that the channel was fired
+    pub time: f64,
+}
+
+impl Display for PyroEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "pyro channel {}", self.channel.id())?;
+
+        if let Some(device) = self.device {
+            write!(f, " ({})", device)?;
+        }
+
+        write!(f, " at T+{:.2}s", self.time)
+    }
+}
+
+/// A phase of flight that the avionics reported entering
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseEvent {
+    /// The phase that was entered
+    pub phase: FlightPhase,
+    /// Time in seconds since the start of the scenario that the phase was entered
+    pub time: f64,
+}
+
+impl Display for PhaseEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at T+{:.2}s", self.phase, self.time)
+    }
+}
+
+/// How far the estimate of an attitude filter was from the true attitude over
+/// the course of a scenario
+#[derive(Debug, Clone)]
+pub struct AttitudeTrace {
+    /// The filter that produced the estimate
+    pub filter: AttitudeFilter,
+    /// Time in seconds since the start of the scenario and the angle in degrees
+    /// between the estimated and true attitude at that time
+    pub error: Vec<(f64, f64)>,
+}
+
+/// When preflight's apogee predictor detected apogee, against the true apogee
+#[derive(Debug, Clone, Copy)]
+pub struct ApogeeTrace {
+    /// Time in seconds that apogee was detected at, and the estimated altitude
+    /// in meters of it, if it was detected at all
+    pub detected: Option<(f64, f64)>,
+    /// Time in seconds that the vehicle was highest at, and its altitude in
+    /// meters at that time
+    pub truth: (f64, f64),
+}
+
+impl Display for ApogeeTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (time, altitude) = self.truth;
+
+        match self.detected {
+            Some((detected, estimate)) => write!(
+                f,
+                "apogee of {:.1} m at T+{:.2}s, {:+.2}s from the true apogee of {:.1} m",
+                estimate,
+                detected,
+                detected - time,
+                altitude
+            ),
+            None => write!(
+                f,
+                "no apogee detected, with the true apogee of {:.1} m at T+{:.2}s",
+                altitude, time
+            ),
+        }
+    }
+}
+
+/// How the vehicle was flown from the pad to apogee
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AscentTrace {
+    /// Root mean square of the true tilt of the vehicle from vertical, in degrees
+    pub tilt: f64,
+    /// Root mean square of the deflection of the gimbal, in degrees
+    pub effort: f64,
+    /// The highest altitude that the vehicle reached, in meters
+    pub apogee: f64,
+}
+
+/// A reset of the flight computer, and when the avionics recovered from it
+#[derive(Debug, Clone, Copy)]
+pub struct ResetEvent {
+    /// Time in seconds since the start of the scenario that the reset happened
+    pub time: f64,
+    /// The phase that the avionics were in before the reset
+    pub phase: FlightPhase,
+    /// Time in seconds that the avionics were back in at least that phase, if
+    /// they ever were
+    pub recovered: Option<f64>,
+}
+
+impl Display for ResetEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "reset in {:?} at T+{:.2}s", self.phase, self.time)?;
+
+        match self.recovered {
+            Some(recovered) => write!(f, ", recovered at T+{:.2}s", recovered),
+            None => write!(f, ", never recovered"),
+        }
+    }
+}
+
+/// The value that the avionics held a parameter at after a reset
+#[derive(Debug, Clone)]
+pub struct ParameterReading {
+    pub name: String,
+    /// Time in seconds since the start of the scenario that the parameter was read
+    pub time: f64,
+    pub value: f64,
+}
+
+impl Display for ParameterReading {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` = {} at T+{:.2}s", self.name, self.value, self.time)
+    }
+}
+
+/// What happened during a scenario
+#[derive(Debug)]
+pub struct ScenarioReport {
+    /// The abort signaled by the avionics, if any
+    pub abort: Option<AbortEvent>,
+    /// Every pyro channel that was fired, in the order that they were fired
+    pub pyro: Vec<PyroEvent>,
+    /// Every phase of flight that the avionics entered, in order
+    pub phases: Vec<PhaseEvent>,
+    /// The error of each attitude filter that the scenario checks
+    pub attitude: Vec<AttitudeTrace>,
+    /// When apogee was detected, if the scenario checks it
+    pub apogee: Option<ApogeeTrace>,
+    /// How the vehicle was flown on its way up
+    pub ascent: AscentTrace,
+    /// Every reset of the flight computer, in order
+    pub resets: Vec<ResetEvent>,
+    /// The parameters that the scenario checks, as they were read after each reset
+    pub parameters: Vec<ParameterReading>,
+    /// Time in seconds that was simulated
+    pub duration: f64,
+}
+
+impl Scenario {
+    /// Load all of the scenarios in the given directory, sorted by name
+    pub fn load_all(directory: &Path) -> anyhow::Result<Vec<Scenario>> {
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut scenarios = Vec::new();
+
+        for entry in fs::read_dir(directory)
+            .with_context(|| format!("failed to read scenarios from {:?}", directory))?
+        {
+            let path = entry?.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+                continue;
+            }
+
+            let mut scenario: Scenario = toml::from_str(
+                &fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read scenario {:?}", path))?,
+            )
+            .with_context(|| format!("failed to parse scenario {:?}", path))?;
+
+            scenario
+                .vehicle
+                .pyro
+                .validate()
+                .with_context(|| format!("invalid pyro wiring in scenario {:?}", path))?;
+            scenario
+                .validate()
+                .with_context(|| format!("invalid scenario {:?}", path))?;
+
+            scenario.name = path
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            scenarios.push(scenario);
+        }
+
+        scenarios.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(scenarios)
+    }
+
+    /// Ensure that the scenario can be simulated, with a positive timestep and
+    /// duration and with every time in it finite
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.timestep > 0.0 && self.timestep.is_finite(),
+            "the timestep has to be a positive number of seconds, found {}",
+            self.timestep
+        );
+        ensure!(
+            self.duration > 0.0 && self.duration.is_finite(),
+            "the duration has to be a positive number of seconds, found {}",
+            self.duration
+        );
+
+        let finite = |what: &dyn Display, value: Option<f64>| -> anyhow::Result<()> {
+            match value {
+                Some(value) => {
+                    ensure!(
+                        value.is_finite(),
+                        "{} has to be finite, found {}",
+                        what,
+                        value
+                    );
+
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        };
+
+        finite(&"the branch time", self.branch)?;
+        finite(&"the target apogee", self.target_apogee)?;
+
+        for fault in &self.fault {
+            let fault_time = |what: &str, value: Option<f64>| {
+                finite(
+                    &format_args!(
+                        "the {} of the {:?} {:?} fault",
+                        what, fault.kind, fault.sensor
+                    ),
+                    value,
+                )
+            };
+
+            fault_time("start", Some(fault.start))?;
+            fault_time("end", fault.end)?;
+            fault_time("standard deviation", Some(fault.std_dev))?;
+        }
+
+        for (i, command) in self.command.iter().enumerate() {
+            finite(
+                &format_args!("the time of command {}", i + 1),
+                Some(command.time()),
+            )?;
+        }
+
+        for (i, reset) in self.reset.iter().enumerate() {
+            finite(
+                &format_args!("the time of reset {}", i + 1),
+                Some(reset.time),
+            )?;
+            ensure!(
+                reset.downtime >= 0.0 && reset.downtime.is_finite(),
+                "the downtime of reset {} has to be a non-negative number of seconds, found {}",
+                i + 1,
+                reset.downtime
+            );
+        }
+
+        for expectation in &self.expect {
+            let times = match *expectation {
+                Expectation::Abort { before, .. } => vec![before],
+                Expectation::NoAbort | Expectation::NoFire { .. } => vec![],
+                Expectation::Fire { after, before, .. }
+                | Expectation::Phase { after, before, .. } => vec![after, before],
+                Expectation::Attitude {
+                    after, max_error, ..
+                } => vec![after, Some(max_error)],
+                Expectation::Apogee { within } => vec![Some(within)],
+                Expectation::Recover { within } => vec![within],
+                Expectation::Parameter { value, .. } => {
+                    finite(
+                        &format_args!("the value of the {:?} expectation", expectation),
+                        Some(value),
+                    )?;
+
+                    vec![]
+                }
+            };
+
+            for time in times {
+                finite(
+                    &format_args!("every time of the {:?} expectation", expectation),
+                    time,
+                )?;
+            }
+        }
+
+        self.gnss.validate()?;
+        self.dispersion.validate()
+    }
+
+    /// The seed of the sensor noise and dispersion of the given run of a Monte
+    /// Carlo batch, whether it is flown from the pad or branched off mid flight
+    pub fn run_seed(&self, run: u64) -> u64 {
+        self.seed.wrapping_add(run)
+    }
+
+    /// The scenario flown by the given run of a Monte Carlo batch, with its own
+    /// sensor noise and with the vehicle varied by the [`Dispersion`]
+    pub fn dispersed(&self, run: u64) -> Scenario {
+        let seed = self.run_seed(run);
+
+        Scenario {
+            name: format!("{}#{}", self.name, run),
+            seed,
+            vehicle: self.dispersion.disperse(&self.vehicle, seed),
+            ..self.clone()
+        }
+    }
+
+    /// Fly the avionics through the scenario, recording every call into the
+    /// avionics to the log if one is given
+    pub fn run(
+        &self,
+        harness: &mut AvionicsHarness<PanicCaught>,
+        watchdog: &Watchdog,
+        shell: &mut Shell,
+        mut log: Option<SignalLog>,
+        mut telemetry: Option<TelemetryLog>,
+    ) -> anyhow::Result<ScenarioReport> {
+        let mut flight = Flight::new(self, shell)?;
+        flight.fly(
+            self.duration,
+            harness,
+            watchdog,
+            shell,
+            &mut log,
+            &mut telemetry,
+        )?;
+
+        flight.finish(shell, telemetry)
+    }
+
+    /// Fly the avionics through the scenario as [`Scenario::run`] does, while
+    /// checkpointing the flight at the given time in seconds and flying the rest
+    /// of it a second time from the checkpoint
+    ///
+    /// The report is of the original flight, and comes along with a description
+    /// of everything that the replay did differently, which would mean that the
+    /// avionics were not restored to exactly the state that they were in.
+    pub fn replay(
+        &self,
+        harness: &mut AvionicsHarness<PanicCaught>,
+        watchdog: &Watchdog,
+        shell: &mut Shell,
+        mut log: Option<SignalLog>,
+        mut telemetry: Option<TelemetryLog>,
+        checkpoint: f64,
+    ) -> anyhow::Result<(ScenarioReport, Vec<String>)> {
+        let mut flight = Flight::new(self, shell)?;
+        flight.fly(
+            checkpoint,
+            harness,
+            watchdog,
+            shell,
+            &mut log,
+            &mut telemetry,
+        )?;
+
+        let checkpoint = flight.checkpoint(harness, watchdog)?;
+        let time = flight.time();
+        shell.status("Checkpointed", format!("the flight at T+{:.2}s", time))?;
+
+        flight.fly(
+            self.duration,
+            harness,
+            watchdog,
+            shell,
+            &mut log,
+            &mut telemetry,
+        )?;
+        let report = flight.finish(shell, telemetry)?;
+
+        // The replay goes over what has already been reported, so it is kept quiet
+        let mut quiet = Shell::quiet();
+        let mut replay = checkpoint.resume(harness, watchdog)?;
+        replay.fly(
+            self.duration,
+            harness,
+            watchdog,
+            &mut quiet,
+            &mut None,
+            &mut None,
+        )?;
+        let replayed = replay.finish(&mut quiet, None)?;
+        shell.status("Replayed", format!("the flight from T+{:.2}s", time))?;
+
+        let differences = differences(&report, &replayed)
+            .into_iter()
+            .map(|what| {
+                format!(
+                    "expected the flight replayed from T+{:.2}s to match the original, but {}",
+                    time, what
+                )
+            })
+            .collect();
+
+        Ok((report, differences))
+    }
+
+    /// Check the report against the expectations of this scenario, returning a
+    /// description of each expectation that was not met
+    pub fn check(&self, report: &ScenarioReport) -> Vec<String> {
+        self.expect
+            .iter()
+            .filter_map(|expectation| match expectation {
+                Expectation::Abort { cause, before } => match report.abort {
+                    None => Some("expected the avionics to abort, but they never did".to_string()),
+                    Some(abort) => cause
+                        .filter(|cause| AbortCause::from(*cause) != abort.cause)
+                        .map(|cause| {
+                            format!(
+                                "expected an abort caused by {:?}, but found {}",
+                                cause, abort
+                            )
+                        })
+                        .or_else(|| {
+                            before.filter(|before| abort.time >= *before).map(|before| {
+                                format!(
+                                    "expected an abort before T+{:.2}s, but found {}",
+                                    before, abort
+                                )
+                            })
+                        }),
+                },
+                Expectation::NoAbort => report.abort.map(|abort| {
+                    format!("expected the avionics to never abort, but found {}", abort)
+                }),
+                Expectation::Fire {
+                    channel,
+                    after,
+                    before,
+                } => match report.pyro.iter().find(|event| event.channel.id() == *channel) {
+                    None => Some(format!(
+                        "expected pyro channel {} to fire, but it never did",
+                        channel
+                    )),
+                    Some(event) => after
+                        .filter(|after| event.time <= *after)
+                        .map(|after| {
+                            format!(
+                                "expected pyro channel {} to fire after T+{:.2}s, but found {}",
+                                channel, after, event
+                            )
+                        })
+                        .or_else(|| {
+                            before.filter(|before| event.time >= *before).map(|before| {
+                                format!(
+                                    "expected pyro channel {} to fire before T+{:.2}s, but found {}",
+                                    channel, before, event
+                                )
+                            })
+                        }),
+                },
+                Expectation::NoFire { channel } => report
+                    .pyro
+                    .iter()
+                    .find(|event| event.channel.id() == *channel)
+                    .map(|event| {
+                        format!(
+                            "expected pyro channel {} to never fire, but found {}",
+                            channel, event
+                        )
+                    }),
+                Expectation::Phase {
+                    phase,
+                    after,
+                    before,
+                } => match report
+                    .phases
+                    .iter()
+                    .find(|event| event.phase == FlightPhase::from(*phase))
+                {
+                    None => Some(format!(
+                        "expected the avionics to enter {:?}, but they never did",
+                        FlightPhase::from(*phase)
+                    )),
+                    Some(event) => after
+                        .filter(|after| event.time <= *after)
+                        .map(|after| {
+                            format!(
+                                "expected the avionics to enter {:?} after T+{:.2}s, but found {}",
+                                event.phase, after, event
+                            )
+                        })
+                        .or_else(|| {
+                            before.filter(|before| event.time >= *before).map(|before| {
+                                format!(
+                                    "expected the avionics to enter {:?} before T+{:.2}s, but found {}",
+                                    event.phase, before, event
+                                )
+                            })
+                        }),
+                },
+                Expectation::Attitude {
+                    filter,
+                    after,
+                    max_error,
+                } => report
+                    .attitude
+                    .iter()
+                    .filter(|trace| trace.filter == *filter)
+                    .flat_map(|trace| &trace.error)
+                    .find(|(time, error)| *time >= after.unwrap_or(0.0) && error > max_error)
+                    .map(|(time, error)| {
+                        format!(
+                            "expected the {} filter to stay within {:.1}° of the true attitude{}, but it was {:.1}° off at T+{:.2}s",
+                            filter,
+                            max_error,
+                            after
+                                .map(|after| format!(" after T+{:.2}s", after))
+                                .unwrap_or_default(),
+                            error,
+                            time
+                        )
+                    }),
+                Expectation::Apogee { within } => report.apogee.and_then(|apogee| {
+                    let (time, _) = apogee.truth;
+
+                    match apogee.detected {
+                        Some((detected, _)) if (detected - time).abs() <= *within => None,
+                        Some(_) => Some(format!(
+                            "expected apogee to be detected within {:.2}s of the true apogee, but found {}",
+                            within, apogee
+                        )),
+                        None => Some(format!(
+                            "expected apogee to be detected within {:.2}s of the true apogee at T+{:.2}s, but it never was",
+                            within, time
+                        )),
+                    }
+                }),
+                Expectation::Recover { within } => report
+                    .resets
+                    .iter()
+                    .find(|reset| match (reset.recovered, within) {
+                        (None, _) => true,
+                        (Some(recovered), Some(within)) => recovered - reset.time > *within,
+                        (Some(_), None) => false,
+                    })
+                    .map(|reset| {
+                        format!(
+                            "expected the avionics to get back to {:?}{} after each reset, but found {}",
+                            reset.phase,
+                            within
+                                .map(|within| format!(" within {:.2}s", within))
+                                .unwrap_or_default(),
+                            reset
+                        )
+                    }),
+                // Parameters held in an `f32` can not be read back exactly
+                Expectation::Parameter { name, value } => report
+                    .parameters
+                    .iter()
+                    .find(|reading| &reading.name == name && reading.value as f32 != *value as f32)
+                    .map(|reading| {
+                        format!(
+                            "expected the avionics to hold parameter `{}` at {} after each reset, but found {}",
+                            name, value, reading
+                        )
+                    }),
+            })
+            .collect()
+    }
+}
+
+/// Describe everything that happened differently in two reports of the same
+/// scenario, which have to match exactly when it is flown the same way twice
+fn differences(original: &ScenarioReport, replay: &ScenarioReport) -> Vec<String> {
+    let compare = |what: &str, original: String, replay: String| {
+        (original != replay).then(|| format!("{} {} rather than {}", what, replay, original))
+    };
+
+    vec![
+        compare(
+            "it aborted with",
+            format!("{:?}", original.abort),
+            format!("{:?}", replay.abort),
+        ),
+        compare(
+            "it fired",
+            format!("{:?}", original.pyro),
+            format!("{:?}", replay.pyro),
+        ),
+        compare(
+            "it entered",
+            format!("{:?}", original.phases),
+            format!("{:?}", replay.phases),
+        ),
+        compare(
+            "it reset with",
+            format!("{:?}", original.resets),
+            format!("{:?}", replay.resets),
+        ),
+        compare(
+            "it flew",
+            format!("{:?}", original.ascent),
+            format!("{:?}", replay.ascent),
+        ),
+        compare(
+            "it lasted",
+            format!("{:.2}s", original.duration),
+            format!("{:.2}s", replay.duration),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ScenarioReport {
+        ScenarioReport {
+            abort: None,
+            pyro: Vec::new(),
+            phases: vec![PhaseEvent {
+                phase: FlightPhase::Boost,
+                time: 0.5,
+            }],
+            attitude: Vec::new(),
+            apogee: None,
+            ascent: AscentTrace::default(),
+            resets: Vec::new(),
+            parameters: Vec::new(),
+            duration: 20.0,
+        }
+    }
+
+    #[test]
+    fn identical_flights_have_no_differences() {
+        assert!(differences(&report(), &report()).is_empty());
+    }
+
+    #[test]
+    fn replays_that_diverge_are_described() {
+        let mut replay = report();
+        replay.phases[0].time = 0.51;
+        replay.abort = Some(AbortEvent {
+            cause: AbortCause::RangeSafety,
+            time: 1.0,
+        });
+
+        let differences = differences(&report(), &replay);
+        assert_eq!(differences.len(), 2, "{:?}", differences);
+        assert!(differences[0].starts_with("it aborted with Some"));
+        assert!(differences[1].starts_with("it entered"));
+    }
+}
@@ -1,9 +1,13 @@
 use std::io::{self, Write};
+
+use preflight::log::Level;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 pub struct Shell {
     stderr: StandardStream,
     // stdout: StandardStream,
+    /// Only warnings and errors are shown
+    quiet: bool,
 }
 
 impl Shell {
@@ -11,6 +15,16 @@ impl Shell {
         Self {
             stderr: StandardStream::stderr(ColorChoice::Auto),
             // stdout: StandardStream::stdout(ColorChoice::Auto),
+            quiet: false,
+        }
+    }
+
+    /// A shell that only shows warnings and errors, for flying many scenarios
+    /// without flooding the terminal
+    pub fn quiet() -> Self {
+        Self {
+            quiet: true,
+            ..Self::new()
         }
     }
 
@@ -19,6 +33,10 @@ impl Shell {
         S: AsRef<str>,
         M: AsRef<str>,
     {
+        if self.quiet {
+            return Ok(());
+        }
+
         self.stderr
             .set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
         write!(self.stderr, "{:>12}", status.as_ref())?;
@@ -55,6 +73,10 @@ impl Shell {
     where
         M: AsRef<str>,
     {
+        if self.quiet {
+            return Ok(());
+        }
+
         self.stderr
             .set_color(ColorSpec::new().set_fg(Some(Color::Blue)).set_bold(true))?;
         write!(self.stderr, "   = ")?;
@@ -65,4 +87,27 @@ impl Shell {
         self.stderr.reset()?;
         writeln!(self.stderr, " {}", message.as_ref().trim_end())
     }
+
+    pub fn log<M>(&mut self, level: Level, message: M) -> io::Result<()>
+    where
+        M: AsRef<str>,
+    {
+        if self.quiet {
+            return Ok(());
+        }
+
+        let color = match level {
+            Level::Trace | Level::Debug => Color::White,
+            Level::Info => Color::Cyan,
+            Level::Warn => Color::Yellow,
+            Level::Error => Color::Red,
+        };
+
+        self.stderr
+            .set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+        write!(self.stderr, "{:>12}", level.name())?;
+
+        self.stderr.reset()?;
+        writeln!(self.stderr, " {}", message.as_ref().trim_end())
+    }
 }
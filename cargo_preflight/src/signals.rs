@@ -0,0 +1,141 @@
+//! Matching of the avionics' sensor and guidance channels with the ones that the
+//! simulation provides and acts on
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use preflight::{Guidance, Sensors, Signals};
+
+/// The names of every channel of the given signals, in order
+pub fn channel_names<S: Signals>() -> Vec<String> {
+    let mut names = Vec::with_capacity(S::CHANNELS);
+
+    S::channels(None, &mut |name| names.push(name.to_string()));
+
+    names
+}
+
+/// The channels of the avionics' sensors and guidance, matched by name with the
+/// channels of the simulated [`Sensors`] and [`Guidance`]
+#[derive(Debug)]
+pub struct SignalMap {
+    /// Names of the channels of the avionics' sensors
+    pub sensors: Vec<String>,
+    /// Names of the channels of the avionics' guidance
+    pub guidance: Vec<String>,
+    /// Index of the simulated sensor channel to fill each of the avionics'
+    /// sensor channels with
+    sensor_sources: Vec<Option<usize>>,
+    /// Index of the avionics' guidance channel to read each of the simulated
+    /// guidance channels from
+    guidance_sources: Vec<Option<usize>>,
+}
+
+impl SignalMap {
+    /// Match the given channels of the avionics with the simulated channels
+    pub fn new(sensors: Vec<String>, guidance: Vec<String>) -> Self {
+        let simulated_sensors = channel_names::<Sensors>();
+        let simulated_guidance = channel_names::<Guidance>();
+
+        Self {
+            sensor_sources: sensors
+                .iter()
+                .map(|name| simulated_sensors.iter().position(|sim| sim == name))
+                .collect(),
+            guidance_sources: simulated_guidance
+                .iter()
+                .map(|sim| guidance.iter().position(|name| name == sim))
+                .collect(),
+            sensors,
+            guidance,
+        }
+    }
+
+    /// The avionics' sensor channels that the simulation does not provide
+    pub fn unsimulated_sensors(&self) -> impl Iterator<Item = &str> {
+        self.sensors
+            .iter()
+            .zip(&self.sensor_sources)
+            .filter(|(_, source)| source.is_none())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The avionics' guidance channels that the simulation does not act on
+    pub fn unsimulated_guidance(&self) -> impl Iterator<Item = &str> {
+        let simulated = channel_names::<Guidance>();
+
+        self.guidance
+            .iter()
+            .filter(move |name| !simulated.contains(name))
+            .map(String::as_str)
+    }
+
+    /// Fill the avionics' sensor buffer from the simulated sensors, leaving the
+    /// channels that are not simulated at zero
+    pub fn fill_sensors(&self, simulated: &Sensors, buffer: &mut [f64]) {
+        let mut values = vec![0.0; Sensors::CHANNELS];
+        simulated.write(&mut values);
+
+        for (channel, source) in buffer.iter_mut().zip(&self.sensor_sources) {
+            *channel = source.map_or(0.0, |source| values[source]);
+        }
+    }
+
+    /// Read the simulated guidance out of the avionics' guidance buffer, leaving
+    /// the channels that the avionics do not produce at zero
+    pub fn read_guidance(&self, buffer: &[f64]) -> Guidance {
+        let values = self
+            .guidance_sources
+            .iter()
+            .map(|source| source.map_or(0.0, |source| buffer[source]))
+            .collect::<Vec<_>>();
+
+        Guidance::read(&values)
+    }
+}
+
+/// A csv file recording every channel of the avionics' sensors and guidance at
+/// each step of a scenario
+pub struct SignalLog {
+    file: BufWriter<File>,
+}
+
+impl SignalLog {
+    /// Create the log file, writing the name of each channel as the header
+    pub fn create(path: &Path, signals: &SignalMap) -> anyhow::Result<Self> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).with_context(|| {
+                format!("failed to create signal log directory {:?}", directory)
+            })?;
+        }
+
+        let mut file = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create signal log {:?}", path))?,
+        );
+
+        write!(file, "time")?;
+        for name in &signals.sensors {
+            write!(file, ",sensors.{}", name)?;
+        }
+        for name in &signals.guidance {
+            write!(file, ",guidance.{}", name)?;
+        }
+        writeln!(file)?;
+
+        Ok(Self { file })
+    }
+
+    /// Record the value of every channel at the given time in seconds
+    pub fn record(&mut self, time: f64, sensors: &[f64], guidance: &[f64]) -> io::Result<()> {
+        write!(self.file, "{}", time)?;
+        for value in sensors.iter().chain(guidance) {
+            write!(self.file, ",{}", value)?;
+        }
+        writeln!(self.file)
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::ensure;
+use serde::Deserialize;
+
+use super::{noise::Noise, VehicleParameters};
+
+/// Mixed into the seed of a run, so that the vehicle is varied independently of
+/// the sensor noise generated from the same seed
+const DISPERSION_STREAM: u64 = 0x6469_7370_6572_7365;
+
+/// The standard deviations that the vehicle of a scenario is randomly varied by
+/// in each run of a Monte Carlo batch
+///
+/// Each run of a batch is given the same variations every time it is flown, so
+/// that batches flown with different avionics parameters can be compared. The
+/// fractions scale the vehicle log-normally, so that however far a run is
+/// varied its masses and lengths stay positive
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Dispersion {
+    /// Standard deviation of the angle from vertical that the vehicle starts at,
+    /// in degrees
+    pub launch_tilt: f64,
+    /// Standard deviation of the dry mass, as a fraction of it
+    pub dry_mass: f64,
+    /// Standard deviation of the drag coefficient of the body, as a fraction of it
+    pub drag_coefficient: f64,
+    /// Standard deviation of the distance from the center of mass to the gimbal,
+    /// as a fraction of it
+    pub gimbal_offset: f64,
+}
+
+impl Dispersion {
+    /// Ensure that every standard deviation is finite and not negative
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let std_devs = [
+            ("launch_tilt", self.launch_tilt),
+            ("dry_mass", self.dry_mass),
+            ("drag_coefficient", self.drag_coefficient),
+            ("gimbal_offset", self.gimbal_offset),
+        ];
+
+        for (name, std_dev) in &std_devs {
+            ensure!(
+                std_dev.is_finite() && *std_dev >= 0.0,
+                "the dispersion of `{}` has to be a finite standard deviation, found {}",
+                name,
+                std_dev
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Randomly vary the vehicle, the same way every time for the same seed
+    pub fn disperse(&self, vehicle: &VehicleParameters, seed: u64) -> VehicleParameters {
+        let mut noise = Noise::new(seed ^ DISPERSION_STREAM);
+        let mut scale = |std_dev: f64| noise.gaussian(std_dev).exp();
+
+        let (dry_mass, drag_coefficient, gimbal_offset) = (
+            scale(self.dry_mass),
+            scale(self.drag_coefficient),
+            scale(self.gimbal_offset),
+        );
+
+        VehicleParameters {
+            launch_tilt: vehicle.launch_tilt + noise.gaussian(self.launch_tilt),
+            dry_mass: vehicle.dry_mass * dry_mass,
+            drag_coefficient: vehicle.drag_coefficient * drag_coefficient,
+            gimbal_offset: vehicle.gimbal_offset * gimbal_offset,
+            ..vehicle.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dispersion far wider than any real vehicle would vary by
+    const WIDE: Dispersion = Dispersion {
+        launch_tilt: 5.0,
+        dry_mass: 2.0,
+        drag_coefficient: 2.0,
+        gimbal_offset: 2.0,
+    };
+
+    #[test]
+    fn the_same_seed_disperses_the_same_way() {
+        let vehicle = VehicleParameters::default();
+
+        assert_eq!(
+            format!("{:?}", WIDE.disperse(&vehicle, 42)),
+            format!("{:?}", WIDE.disperse(&vehicle, 42))
+        );
+        assert_ne!(
+            format!("{:?}", WIDE.disperse(&vehicle, 42)),
+            format!("{:?}", WIDE.disperse(&vehicle, 43))
+        );
+    }
+
+    #[test]
+    fn masses_and_lengths_stay_positive() {
+        let vehicle = VehicleParameters::default();
+
+        for seed in 0..1000 {
+            let dispersed = WIDE.disperse(&vehicle, seed);
+
+            assert!(dispersed.dry_mass > 0.0, "{:?}", dispersed);
+            assert!(dispersed.drag_coefficient > 0.0, "{:?}", dispersed);
+            assert!(dispersed.gimbal_offset > 0.0, "{:?}", dispersed);
+        }
+    }
+
+    #[test]
+    fn no_dispersion_leaves_the_vehicle_as_it_is() {
+        let vehicle = VehicleParameters::default();
+
+        assert_eq!(
+            format!("{:?}", Dispersion::default().disperse(&vehicle, 42)),
+            format!("{:?}", vehicle)
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::fmt::{self, Display, Formatter};
+
+use preflight::{SensorHealth, Sensors, Signals};
+use serde::Deserialize;
+
+use super::noise::Noise;
+use crate::signals::channel_names;
+
+/// A sensor on the vehicle that can have a fault injected into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultySensor {
+    Altimeter,
+    Accelerometer,
+    Gyroscope,
+    Orientation,
+    Magnetometer,
+    Gnss,
+}
+
+impl FaultySensor {
+    /// The health flag of the sensor
+    fn health(self) -> SensorHealth {
+        match self {
+            FaultySensor::Altimeter => SensorHealth::ALTIMETER,
+            FaultySensor::Accelerometer => SensorHealth::ACCELEROMETER,
+            FaultySensor::Gyroscope => SensorHealth::GYROSCOPE,
+            FaultySensor::Orientation => SensorHealth::ORIENTATION,
+            FaultySensor::Magnetometer => SensorHealth::MAGNETOMETER,
+            FaultySensor::Gnss => SensorHealth::GNSS,
+        }
+    }
+
+    /// The fields of [`Sensors`] that hold the readings of the sensor
+    fn fields(self) -> &'static [&'static str] {
+        match self {
+            FaultySensor::Altimeter => &["altitude"],
+            FaultySensor::Accelerometer => &[
+                "linear_acceleration",
+                "gravity_acceleration",
+                "both_acceleration",
+            ],
+            FaultySensor::Gyroscope => &["angular_velocity"],
+            FaultySensor::Orientation => &["orientation", "attitude"],
+            FaultySensor::Magnetometer => &["magnetic_field"],
+            FaultySensor::Gnss => &["gnss"],
+        }
+    }
+
+    /// If noise can be added to the channel of the sensor with the given name,
+    /// which is not the case for discrete channels such as the number of
+    /// satellites or the kind of fix
+    fn is_continuous(self, channel: &str) -> bool {
+        match self {
+            FaultySensor::Gnss => {
+                ["gnss.latitude", "gnss.longitude", "gnss.altitude"].contains(&channel)
+                    || channel.starts_with("gnss.velocity.")
+            }
+            _ => true,
+        }
+    }
+}
+
+/// How a faulty sensor misbehaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultKind {
+    /// The sensor stops producing readings, which its driver reports by lowering
+    /// its health flag
+    Dead,
+    /// The sensor repeats the reading from the start of the fault, without its
+    /// driver noticing
+    Stuck,
+    /// The sensor's continuous readings are corrupted by noise, without its
+    /// driver noticing, while discrete ones such as the kind of GNSS fix are left
+    /// untouched
+    Noisy,
+}
+
+/// A fault injected into one of the sensors for part of the flight
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fault {
+    /// The sensor that misbehaves
+    pub sensor: FaultySensor,
+    /// How the sensor misbehaves
+    pub kind: FaultKind,
+    /// Time in seconds at which the fault starts
+    #[serde(default)]
+    pub start: f64,
+    /// Time in seconds at which the sensor recovers, if it ever does
+    pub end: Option<f64>,
+    /// Standard deviation of the noise of a noisy sensor, in the base SI unit of
+    /// its readings
+    #[serde(default)]
+    pub std_dev: f64,
+}
+
+impl Fault {
+    /// If the fault is affecting the sensor at the given time
+    fn is_active(&self, time: f64) -> bool {
+        time >= self.start && self.end.map_or(true, |end| time < end)
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {:?}", self.kind, self.sensor)?;
+
+        if let Some(end) = self.end {
+            write!(f, " from T+{:.2}s to T+{:.2}s", self.start, end)
+        } else {
+            write!(f, " from T+{:.2}s", self.start)
+        }
+    }
+}
+
+/// Corrupts the readings of the sensors according to the faults of a scenario
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    faults: Vec<Fault>,
+    /// The readings of every channel at the moment that each fault started
+    snapshots: Vec<Option<Vec<f64>>>,
+    /// The sensor that produces each of the channels of [`Sensors`], and if the
+    /// channel is continuous
+    channels: Vec<Option<(FaultySensor, bool)>>,
+}
+
+impl FaultInjector {
+    /// Create an injector for the given faults
+    pub fn new(faults: Vec<Fault>) -> Self {
+        const SENSORS: [FaultySensor; 6] = [
+            FaultySensor::Altimeter,
+            FaultySensor::Accelerometer,
+            FaultySensor::Gyroscope,
+            FaultySensor::Orientation,
+            FaultySensor::Magnetometer,
+            FaultySensor::Gnss,
+        ];
+
+        Self {
+            snapshots: vec![None; faults.len()],
+            faults,
+            channels: channel_names::<Sensors>()
+                .iter()
+                .map(|name| {
+                    let field = name.split('.').next().unwrap_or_default();
+
+                    SENSORS
+                        .iter()
+                        .copied()
+                        .find(|sensor| sensor.fields().contains(&field))
+                        .map(|sensor| (sensor, sensor.is_continuous(name)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Apply every fault that is active at the given time to the readings
+    pub fn apply(&mut self, time: f64, sensors: Sensors, noise: &mut Noise) -> Sensors {
+        if self.faults.is_empty() {
+            return sensors;
+        }
+
+        let mut values = vec![0.0; Sensors::CHANNELS];
+        sensors.write(&mut values);
+
+        let mut unhealthy = SensorHealth::NONE;
+
+        for (fault, snapshot) in self.faults.iter().zip(&mut self.snapshots) {
+            if !fault.is_active(time) {
+                *snapshot = None;
+                continue;
+            }
+
+            let snapshot = snapshot.get_or_insert_with(|| values.clone());
+
+            for (i, continuous) in
+                self.channels
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, channel)| match channel {
+                        Some((sensor, continuous)) if *sensor == fault.sensor => {
+                            Some((i, *continuous))
+                        }
+                        _ => None,
+                    })
+            {
+                match fault.kind {
+                    FaultKind::Dead => values[i] = 0.0,
+                    FaultKind::Stuck => values[i] = snapshot[i],
+                    FaultKind::Noisy if continuous => values[i] += noise.gaussian(fault.std_dev),
+                    FaultKind::Noisy => {}
+                }
+            }
+
+            if fault.kind == FaultKind::Dead {
+                unhealthy |= fault.sensor.health();
+            }
+        }
+
+        let mut sensors = Sensors::read(&values);
+        sensors.health = sensors.health.difference(unhealthy);
+
+        sensors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{GnssParameters, LaunchSite, Motor, Simulation, VehicleParameters};
+
+    /// A vehicle launched off of a tilted pad, so that every sensor has
+    /// something to read
+    fn flight() -> Simulation {
+        let mut simulation = Simulation::new(
+            VehicleParameters {
+                launch_tilt: 5.0,
+                ..VehicleParameters::default()
+            },
+            Motor::default(),
+            LaunchSite::default(),
+            GnssParameters::default(),
+            Vec::new(),
+            7,
+        );
+
+        for _ in 0..50 {
+            simulation.sensors();
+            simulation.step(0.01);
+        }
+
+        simulation
+    }
+
+    fn values(sensors: &Sensors) -> Vec<f64> {
+        let mut values = vec![0.0; Sensors::CHANNELS];
+        sensors.write(&mut values);
+
+        values
+    }
+
+    fn fault(sensor: FaultySensor, kind: FaultKind, start: f64, end: Option<f64>) -> Fault {
+        Fault {
+            sensor,
+            kind,
+            start,
+            end,
+            std_dev: 1.0,
+        }
+    }
+
+    /// The sensor that produces each channel, if any
+    fn sensor_channels() -> Vec<(String, Option<FaultySensor>)> {
+        channel_names::<Sensors>()
+            .into_iter()
+            .zip(FaultInjector::new(Vec::new()).channels)
+            .map(|(name, channel)| (name, channel.map(|(sensor, _)| sensor)))
+            .collect()
+    }
+
+    #[test]
+    fn every_reading_comes_from_a_sensor() {
+        for (name, sensor) in sensor_channels() {
+            let field = name.split('.').next().unwrap();
+
+            if ["health", "running_time"].contains(&field) {
+                assert_eq!(sensor, None, "{}", name);
+            } else {
+                assert!(sensor.is_some(), "`{}` is not read by any sensor", name);
+            }
+        }
+    }
+
+    #[test]
+    fn a_dead_sensor_reads_nothing_and_is_unhealthy() {
+        let mut simulation = flight();
+        let mut injector = FaultInjector::new(vec![fault(
+            FaultySensor::Accelerometer,
+            FaultKind::Dead,
+            0.0,
+            None,
+        )]);
+
+        let healthy = simulation.sensors();
+        let dead = injector.apply(1.0, simulation.sensors(), &mut Noise::new(0));
+
+        assert_eq!(
+            dead.health,
+            SensorHealth::ALL.difference(SensorHealth::ACCELEROMETER)
+        );
+
+        let (healthy, dead) = (values(&healthy), values(&dead));
+        for ((name, sensor), (healthy, dead)) in
+            sensor_channels().iter().zip(healthy.iter().zip(&dead))
+        {
+            match sensor {
+                Some(FaultySensor::Accelerometer) => assert_eq!(*dead, 0.0, "{}", name),
+                _ if name != "health" => assert_eq!(healthy, dead, "{}", name),
+                _ => {}
+            }
+        }
+        assert!(healthy
+            .iter()
+            .zip(&dead)
+            .any(|(healthy, dead)| healthy != dead));
+    }
+
+    #[test]
+    fn a_stuck_sensor_repeats_the_reading_from_when_it_got_stuck() {
+        let mut simulation = flight();
+        let mut injector = FaultInjector::new(vec![
+            fault(FaultySensor::Altimeter, FaultKind::Stuck, 0.5, Some(0.7)),
+            fault(FaultySensor::Altimeter, FaultKind::Stuck, 0.9, None),
+        ]);
+        let mut noise = Noise::new(0);
+
+        let mut altitude = |time: f64| {
+            while simulation.time() < time - 1e-9 {
+                simulation.step(0.01);
+            }
+
+            let truth = simulation.sensors().altitude;
+            let read = injector
+                .apply(time, simulation.sensors(), &mut noise)
+                .altitude;
+
+            (truth, read)
+        };
+
+        let (stuck, read) = altitude(0.5);
+        assert_eq!(read, stuck);
+
+        let (truth, read) = altitude(0.6);
+        assert_eq!(read, stuck);
+        assert!(truth > stuck);
+
+        // Once the fault is over the sensor reads as it should again
+        let (truth, read) = altitude(0.8);
+        assert_eq!(read, truth);
+
+        // And getting stuck again holds the reading from then, not from before
+        let (stuck_again, read) = altitude(0.9);
+        assert_eq!(read, stuck_again);
+        assert!(stuck_again > stuck);
+
+        let (_, read) = altitude(1.0);
+        assert_eq!(read, stuck_again);
+    }
+
+    #[test]
+    fn a_noisy_receiver_keeps_its_discrete_readings() {
+        let mut simulation = flight();
+        let mut injector =
+            FaultInjector::new(vec![fault(FaultySensor::Gnss, FaultKind::Noisy, 0.0, None)]);
+
+        let clean = values(&simulation.sensors());
+        let noisy = values(&injector.apply(1.0, simulation.sensors(), &mut Noise::new(0)));
+
+        for ((name, sensor), (clean, noisy)) in
+            sensor_channels().iter().zip(clean.iter().zip(&noisy))
+        {
+            let continuous =
+                *sensor == Some(FaultySensor::Gnss) && FaultySensor::Gnss.is_continuous(name);
+
+            if continuous {
+                assert_ne!(clean, noisy, "{}", name);
+            } else {
+                assert_eq!(clean, noisy, "{}", name);
+            }
+        }
+
+        for discrete in &[
+            "gnss.available",
+            "gnss.fix",
+            "gnss.satellites",
+            "gnss.time_of_week",
+        ] {
+            assert!(!FaultySensor::Gnss.is_continuous(discrete));
+        }
+    }
+}
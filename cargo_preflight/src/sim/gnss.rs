@@ -0,0 +1,365 @@
+use anyhow::ensure;
+use nalgebra::Vector3;
+use preflight::{
+    frame::Enu,
+    uom::si::{
+        angle::degree,
+        f32::{Length, Velocity},
+        f64::Angle,
+        length::meter,
+        velocity::meter_per_second,
+    },
+    Gnss, GnssFix,
+};
+use serde::Deserialize;
+
+use super::{noise::Noise, vector, vehicle::VehicleState, GRAVITY};
+
+/// Mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
+/// Length of a GPS week in milliseconds
+const MILLISECONDS_PER_WEEK: u32 = 7 * 24 * 60 * 60 * 1000;
+
+/// Where on the earth the launch pad is
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaunchSite {
+    /// Latitude of the pad in degrees
+    pub latitude: f64,
+    /// Longitude of the pad in degrees
+    pub longitude: f64,
+    /// Altitude of the pad above mean sea level in meters
+    pub altitude: f64,
+}
+
+impl Default for LaunchSite {
+    fn default() -> Self {
+        // Spaceport America
+        Self {
+            latitude: 32.990,
+            longitude: -106.975,
+            altitude: 1401.0,
+        }
+    }
+}
+
+/// Characteristics of the simulated GNSS receiver
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GnssParameters {
+    /// Number of fixes produced per second
+    pub update_rate: f64,
+    /// Standard deviation of the horizontal position error in meters
+    pub horizontal_error: f64,
+    /// Standard deviation of the vertical position error in meters
+    pub vertical_error: f64,
+    /// Standard deviation of the error of each axis of velocity in meters per second
+    pub velocity_error: f64,
+    /// Time in seconds over which the position error wanders
+    pub error_time_constant: f64,
+    /// Number of satellites in view, at least 4 are needed for a fix
+    pub satellites: u8,
+    /// Acceleration in g above which the receiver loses its lock on the satellites
+    pub acceleration_limit: f64,
+    /// Time in seconds that the receiver takes to produce a fix after losing lock
+    pub reacquisition_time: f64,
+    /// Time in milliseconds since the start of the GPS week at the start of the flight
+    pub start_time_of_week: u32,
+}
+
+impl Default for GnssParameters {
+    fn default() -> Self {
+        Self {
+            update_rate: 5.0,
+            horizontal_error: 2.5,
+            vertical_error: 4.0,
+            velocity_error: 0.1,
+            error_time_constant: 60.0,
+            satellites: 12,
+            acceleration_limit: 4.0,
+            reacquisition_time: 2.0,
+            start_time_of_week: 0,
+        }
+    }
+}
+
+impl GnssParameters {
+    /// Ensure that the receiver produces fixes and starts within a GPS week
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.update_rate > 0.0 && self.update_rate.is_finite(),
+            "the update rate of the GNSS receiver has to be a positive number of fixes per second, found {}",
+            self.update_rate
+        );
+        ensure!(
+            self.start_time_of_week < MILLISECONDS_PER_WEEK,
+            "the start time of week of the GNSS receiver has to be within a week of {} ms, found {}",
+            MILLISECONDS_PER_WEEK,
+            self.start_time_of_week
+        );
+
+        Ok(())
+    }
+}
+
+/// A fix produced by the receiver, in the world frame
+#[derive(Debug, Clone, Copy)]
+struct Fix {
+    position: Vector3<f64>,
+    velocity: Vector3<f64>,
+    time: f64,
+}
+
+/// A GNSS receiver that measures the true state of the vehicle
+#[derive(Debug, Clone)]
+pub struct GnssReceiver {
+    parameters: GnssParameters,
+    site: LaunchSite,
+    fix: Option<Fix>,
+    /// Slowly wandering error of the position in the world frame
+    error: Vector3<f64>,
+    /// Time of the next fix
+    next_update: f64,
+    /// Time at which the receiver will have reacquired its lock
+    locked_at: f64,
+}
+
+impl GnssReceiver {
+    /// A receiver that has already locked on to the satellites on the pad
+    pub fn new(parameters: GnssParameters, site: LaunchSite) -> Self {
+        Self {
+            parameters,
+            site,
+            fix: None,
+            error: Vector3::zeros(),
+            next_update: 0.0,
+            locked_at: 0.0,
+        }
+    }
+
+    /// Advance the receiver to the given time in seconds, producing a new fix if
+    /// one is due
+    pub fn step(&mut self, time: f64, dt: f64, state: &VehicleState, noise: &mut Noise) {
+        let parameters = &self.parameters;
+
+        // High acceleration makes the receiver lose track of the satellites' signals
+        let specific_force = state.acceleration + Vector3::new(0.0, 0.0, GRAVITY);
+        if specific_force.norm() > parameters.acceleration_limit * GRAVITY {
+            self.fix = None;
+            self.locked_at = time + parameters.reacquisition_time;
+        }
+
+        // First order Gauss-Markov process, so that the error is correlated between fixes
+        let decay = (-dt / parameters.error_time_constant).exp();
+        let drive = (1.0 - decay * decay).sqrt();
+        self.error = decay * self.error
+            + drive
+                * Vector3::new(
+                    noise.gaussian(parameters.horizontal_error),
+                    noise.gaussian(parameters.horizontal_error),
+                    noise.gaussian(parameters.vertical_error),
+                );
+
+        if time >= self.next_update {
+            self.next_update += 1.0 / parameters.update_rate;
+
+            if time >= self.locked_at && parameters.satellites >= 4 {
+                self.fix = Some(Fix {
+                    position: state.position + self.error,
+                    velocity: state.velocity
+                        + Vector3::from_fn(|_, _| noise.gaussian(parameters.velocity_error)),
+                    time,
+                });
+            }
+        }
+    }
+
+    /// The reading of the receiver
+    pub fn reading(&self) -> Gnss {
+        let fix = match self.fix {
+            Some(fix) => fix,
+            None => {
+                return Gnss {
+                    available: false,
+                    fix: GnssFix::NoFix,
+                    satellites: 0,
+                    latitude: Angle::new::<degree>(0.0),
+                    longitude: Angle::new::<degree>(0.0),
+                    altitude: Length::new::<meter>(0.0),
+                    velocity: preflight::Vector3::zero(),
+                    time_of_week: 0,
+                }
+            }
+        };
+
+        let latitude = self.site.latitude + (fix.position.y / EARTH_RADIUS).to_degrees();
+        let longitude = self.site.longitude
+            + (fix.position.x / (EARTH_RADIUS * self.site.latitude.to_radians().cos()))
+                .to_degrees();
+
+        Gnss {
+            available: true,
+            fix: GnssFix::ThreeDimensional,
+            satellites: self.parameters.satellites,
+            latitude: Angle::new::<degree>(latitude),
+            longitude: Angle::new::<degree>(longitude),
+            altitude: Length::new::<meter>((self.site.altitude + fix.position.z) as f32),
+            velocity: vector::<Enu, _>(fix.velocity, Velocity::new::<meter_per_second>).to_ned(),
+            // Wraps around at the end of the week without overflowing on the way
+            time_of_week: ((u64::from(self.parameters.start_time_of_week)
+                + (fix.time * 1000.0) as u64)
+                % u64::from(MILLISECONDS_PER_WEEK)) as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Time in seconds between steps of the receiver
+    const DT: f64 = 0.01;
+
+    /// Step the receiver from one time in seconds until another, returning the
+    /// time of each step along with the reading of the receiver after it
+    fn run(
+        receiver: &mut GnssReceiver,
+        state: &VehicleState,
+        noise: &mut Noise,
+        from: f64,
+        until: f64,
+    ) -> Vec<(f64, Gnss)> {
+        let steps = ((until - from) / DT).round() as usize;
+
+        (0..steps)
+            .map(|step| {
+                let time = from + step as f64 * DT;
+                receiver.step(time, DT, state, noise);
+
+                (time, receiver.reading())
+            })
+            .collect()
+    }
+
+    /// Check that a fix was produced at each of the expected times of week in
+    /// milliseconds, or at most a step later, since the times of the steps are
+    /// not exact
+    fn assert_fix_times(readings: &[(f64, Gnss)], expected: &[u32]) {
+        let mut times = readings
+            .iter()
+            .map(|(_, reading)| reading.time_of_week)
+            .collect::<Vec<_>>();
+        times.dedup();
+
+        let late = |time: u32, expected: u32| {
+            (i64::from(time) - i64::from(expected)).rem_euclid(i64::from(MILLISECONDS_PER_WEEK))
+        };
+
+        assert_eq!(times.len(), expected.len(), "{:?}", times);
+        for (time, expected) in times.iter().zip(expected) {
+            assert!(
+                late(*time, *expected) <= (DT * 1000.0).round() as i64,
+                "{:?} != {:?}",
+                times,
+                expected
+            );
+        }
+    }
+
+    fn receiver(parameters: GnssParameters) -> GnssReceiver {
+        GnssReceiver::new(parameters, LaunchSite::default())
+    }
+
+    #[test]
+    fn fixes_are_produced_at_the_update_rate() {
+        let mut receiver = receiver(GnssParameters::default());
+        let readings = run(
+            &mut receiver,
+            &VehicleState::on_pad(0.0),
+            &mut Noise::new(0),
+            0.0,
+            1.0,
+        );
+
+        assert!(readings.iter().all(|(_, reading)| reading.available));
+        assert_fix_times(&readings, &[0, 200, 400, 600, 800]);
+    }
+
+    #[test]
+    fn time_of_week_wraps_around_at_the_end_of_the_week() {
+        let mut receiver = receiver(GnssParameters {
+            start_time_of_week: MILLISECONDS_PER_WEEK - 500,
+            ..GnssParameters::default()
+        });
+        let readings = run(
+            &mut receiver,
+            &VehicleState::on_pad(0.0),
+            &mut Noise::new(0),
+            0.0,
+            1.0,
+        );
+
+        assert_fix_times(
+            &readings,
+            &[
+                MILLISECONDS_PER_WEEK - 500,
+                MILLISECONDS_PER_WEEK - 300,
+                MILLISECONDS_PER_WEEK - 100,
+                100,
+                300,
+            ],
+        );
+    }
+
+    #[test]
+    fn lock_is_lost_under_acceleration_and_reacquired() {
+        let parameters = GnssParameters::default();
+        let (limit, reacquisition) = (parameters.acceleration_limit, parameters.reacquisition_time);
+        let mut receiver = receiver(parameters);
+        let mut noise = Noise::new(0);
+
+        let resting = VehicleState::on_pad(0.0);
+        let boosting = VehicleState {
+            acceleration: Vector3::new(0.0, 0.0, limit * GRAVITY),
+            ..VehicleState::on_pad(0.0)
+        };
+
+        assert!(run(&mut receiver, &resting, &mut noise, 0.0, 0.5)
+            .iter()
+            .all(|(_, reading)| reading.available));
+
+        let boost = run(&mut receiver, &boosting, &mut noise, 0.5, 1.0);
+        assert!(boost.iter().all(|(_, reading)| !reading.available
+            && reading.fix == GnssFix::NoFix
+            && reading.satellites == 0));
+
+        // The lock is reacquired a while after the last moment of high acceleration
+        let reacquired = 1.0 - DT + reacquisition;
+        let coast = run(&mut receiver, &resting, &mut noise, 1.0, reacquired + 0.5);
+        let (first, _) = coast
+            .iter()
+            .find(|(_, reading)| reading.available)
+            .expect("the receiver never reacquired its lock");
+
+        assert!(*first >= reacquired - 1e-9, "reacquired at {}", first);
+        assert!(*first < reacquired + 0.2 + DT, "reacquired at {}", first);
+    }
+
+    #[test]
+    fn no_fix_without_four_satellites() {
+        let mut receiver = receiver(GnssParameters {
+            satellites: 3,
+            ..GnssParameters::default()
+        });
+
+        assert!(run(
+            &mut receiver,
+            &VehicleState::on_pad(0.0),
+            &mut Noise::new(0),
+            0.0,
+            2.0
+        )
+        .iter()
+        .all(|(_, reading)| !reading.available));
+    }
+}
@@ -0,0 +1,547 @@
+//! Physics simulation of the vehicle being flown by the avionics
+
+use nalgebra::{UnitQuaternion, Vector3};
+use preflight::{
+    frame::{Body, Enu, Frame, Rotation},
+    uom::si::{
+        acceleration::meter_per_second_squared,
+        angle::radian,
+        angular_velocity::radian_per_second,
+        f32::{Acceleration, Angle, AngularVelocity, Length, MagneticFluxDensity, Time},
+        length::meter,
+        magnetic_flux_density::microtesla,
+        time::second,
+        Dimension,
+    },
+    PyroChannel, PyroCommands, Quantity, Quaternion, SensorHealth, Sensors, ThrustVector,
+};
+
+mod dispersion;
+mod fault;
+mod gnss;
+mod motor;
+mod noise;
+mod vehicle;
+
+pub use dispersion::Dispersion;
+pub use fault::Fault;
+pub use gnss::{GnssParameters, LaunchSite};
+pub use motor::Motor;
+pub use vehicle::{PyroDevice, VehicleParameters};
+
+use fault::FaultInjector;
+use gnss::GnssReceiver;
+use noise::Noise;
+use vehicle::VehicleState;
+
+/// Standard gravity in meters per second squared
+const GRAVITY: f64 = 9.80665;
+/// Density of air at sea level in kilograms per meter cubed
+const AIR_DENSITY: f64 = 1.225;
+/// Earth's magnetic field in the world frame in micro tesla
+const MAGNETIC_FIELD: [f64; 3] = [0.0, 20.0, -45.0];
+/// Time constant in seconds of the damping of the vehicle's rotation while
+/// hanging from its parachute
+const PARACHUTE_DAMPING: f64 = 0.5;
+
+/// Who is in control of the simulated vehicle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightMode {
+    /// The avionics are guiding the vehicle
+    Guided,
+    /// The avionics have aborted, so the gimbal has been centered and the
+    /// parachutes deployed
+    Recovery,
+}
+
+/// A simulation of the vehicle from the launch pad until it lands
+///
+/// A clone of the simulation carries on from the same moment as the original
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    parameters: VehicleParameters,
+    /// The parameters of the vehicle before it was varied by any dispersion
+    nominal: VehicleParameters,
+    motor: Motor,
+    state: VehicleState,
+    gnss: GnssReceiver,
+    faults: FaultInjector,
+    noise: Noise,
+    mode: FlightMode,
+    /// Deflection of the gimbal about the body's x and z axes in radians
+    gimbal: (f64, f64),
+    /// Pyrotechnic channels that have already been fired
+    fired: PyroCommands,
+    /// Time in seconds at which the motor was ignited
+    ignition: Option<f64>,
+    drogue_deployed: bool,
+    main_deployed: bool,
+    separated: bool,
+    time: f64,
+    launched: bool,
+    landed: bool,
+}
+
+impl Simulation {
+    /// Place the vehicle on the launch pad, with the motor ignited unless the
+    /// avionics are in charge of igniting it
+    ///
+    /// All of the sensor noise is generated from the given seed, and the given
+    /// faults are injected into the sensors
+    pub fn new(
+        parameters: VehicleParameters,
+        motor: Motor,
+        site: LaunchSite,
+        gnss: GnssParameters,
+        faults: Vec<Fault>,
+        seed: u64,
+    ) -> Self {
+        Self {
+            state: VehicleState::on_pad(parameters.launch_tilt),
+            gnss: GnssReceiver::new(gnss, site),
+            faults: FaultInjector::new(faults),
+            noise: Noise::new(seed),
+            ignition: match parameters.pyro.ignition {
+                Some(_) => None,
+                None => Some(0.0),
+            },
+            nominal: parameters.clone(),
+            parameters,
+            motor,
+            mode: FlightMode::Guided,
+            gimbal: (0.0, 0.0),
+            fired: PyroCommands::NONE,
+            drogue_deployed: false,
+            main_deployed: false,
+            separated: false,
+            time: 0.0,
+            launched: false,
+            landed: false,
+        }
+    }
+
+    /// Time in seconds since the start of the simulation
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The true altitude of the vehicle in meters above the launch site
+    pub fn altitude(&self) -> f64 {
+        self.state.position.z
+    }
+
+    /// The true rotation of the vehicle from its body frame into the world frame
+    pub fn attitude(&self) -> Rotation<Body, Enu> {
+        let attitude = &self.state.attitude;
+
+        Rotation::new_unchecked(Quaternion::new(
+            attitude.w as f32,
+            attitude.i as f32,
+            attitude.j as f32,
+            attitude.k as f32,
+        ))
+    }
+
+    /// The true state of the vehicle as named values in SI units, with vectors in
+    /// the world frame apart from the angular velocity, which is in the body frame
+    pub fn truth(&self) -> Vec<(&'static str, f64)> {
+        let state = &self.state;
+        let attitude = &state.attitude;
+
+        vec![
+            ("position.x", state.position.x),
+            ("position.y", state.position.y),
+            ("position.z", state.position.z),
+            ("velocity.x", state.velocity.x),
+            ("velocity.y", state.velocity.y),
+            ("velocity.z", state.velocity.z),
+            ("acceleration.x", state.acceleration.x),
+            ("acceleration.y", state.acceleration.y),
+            ("acceleration.z", state.acceleration.z),
+            ("attitude.w", attitude.w),
+            ("attitude.x", attitude.i),
+            ("attitude.y", attitude.j),
+            ("attitude.z", attitude.k),
+            ("angular_velocity.x", state.angular_velocity.x),
+            ("angular_velocity.y", state.angular_velocity.y),
+            ("angular_velocity.z", state.angular_velocity.z),
+        ]
+    }
+
+    /// Carry on from here with the sensor noise generated from a new seed, and
+    /// with the vehicle varied by the dispersion as if it had been from the
+    /// start, other than its launch tilt
+    ///
+    /// The vehicle is varied from its nominal parameters, so that branching a
+    /// simulation that has already branched does not compound the variations
+    pub fn branch(&mut self, dispersion: &Dispersion, seed: u64) {
+        self.parameters = dispersion.disperse(&self.nominal, seed);
+        self.noise = Noise::new(seed);
+    }
+
+    /// If the vehicle has left the pad and is still climbing
+    pub fn is_ascending(&self) -> bool {
+        self.launched && !self.landed && self.state.velocity.z > 0.0
+    }
+
+    /// The true angle of the vehicle from vertical in degrees
+    pub fn tilt(&self) -> f64 {
+        let up = self.state.attitude * Vector3::y();
+
+        up.z.clamp(-1.0, 1.0).acos().to_degrees()
+    }
+
+    /// The deflection of the thrust vectoring gimbal from center in degrees
+    pub fn deflection(&self) -> f64 {
+        self.gimbal.0.hypot(self.gimbal.1).to_degrees()
+    }
+
+    /// If the vehicle has flown and come back to the ground
+    pub fn has_landed(&self) -> bool {
+        self.landed
+    }
+
+    /// Move the thrust vectoring gimbal as commanded by the avionics, limited
+    /// to the range of motion of the gimbal
+    pub fn actuate(&mut self, tvc: &ThrustVector) {
+        if self.mode == FlightMode::Recovery {
+            return;
+        }
+
+        let limit = self.parameters.gimbal_limit.to_radians();
+
+        self.gimbal = (
+            f64::from(tvc.x.get::<radian>()).max(-limit).min(limit),
+            f64::from(tvc.z.get::<radian>()).max(-limit).min(limit),
+        );
+    }
+
+    /// Fire the pyrotechnic channels commanded by the avionics, returning each
+    /// channel that was fired for the first time along with the device wired to it
+    pub fn fire(&mut self, pyro: PyroCommands) -> Vec<(PyroChannel, Option<PyroDevice>)> {
+        let mut fired = Vec::new();
+
+        for channel in pyro.channels() {
+            if self.fired.is_fired(channel) {
+                continue;
+            }
+
+            self.fired = self.fired.fire(channel);
+
+            let device = self.parameters.pyro.device(channel);
+            if let Some(device) = device {
+                self.trigger(device);
+            }
+
+            fired.push((channel, device));
+        }
+
+        fired
+    }
+
+    /// Trigger the given device on the vehicle
+    fn trigger(&mut self, device: PyroDevice) {
+        match device {
+            PyroDevice::Drogue => self.drogue_deployed = true,
+            PyroDevice::Main => self.main_deployed = true,
+            PyroDevice::Separation => self.separated = true,
+            // A motor can only be ignited once
+            PyroDevice::Ignition => {
+                self.ignition.get_or_insert(self.time);
+            }
+        }
+    }
+
+    /// Take control from the avionics, centering the gimbal and deploying the
+    /// parachutes
+    pub fn enter_recovery(&mut self) {
+        self.mode = FlightMode::Recovery;
+        self.gimbal = (0.0, 0.0);
+        self.drogue_deployed = true;
+        self.main_deployed = true;
+    }
+
+    /// The readings of the sensors on the vehicle, which are ideal apart from the
+    /// GNSS receiver and any injected faults
+    pub fn sensors(&mut self) -> Sensors {
+        let to_body = self.state.attitude.inverse();
+
+        // An accelerometer at rest measures the reaction to gravity, pointing up
+        let gravity = to_body * Vector3::new(0.0, 0.0, GRAVITY);
+        let linear = to_body * self.state.acceleration;
+        let (roll, pitch, yaw) = self.state.attitude.euler_angles();
+
+        let sensors = Sensors {
+            health: SensorHealth::ALL,
+            altitude: Length::new::<meter>(self.state.position.z as f32),
+            linear_acceleration: vector(linear, Acceleration::new::<meter_per_second_squared>),
+            gravity_acceleration: vector(gravity, Acceleration::new::<meter_per_second_squared>),
+            both_acceleration: vector(
+                linear + gravity,
+                Acceleration::new::<meter_per_second_squared>,
+            ),
+            orientation: vector(Vector3::new(roll, pitch, yaw), Angle::new::<radian>),
+            attitude: self.attitude(),
+            angular_velocity: vector(
+                self.state.angular_velocity,
+                AngularVelocity::new::<radian_per_second>,
+            ),
+            magnetic_field: vector(
+                to_body * Vector3::from(MAGNETIC_FIELD),
+                MagneticFluxDensity::new::<microtesla>,
+            ),
+            gnss: self.gnss.reading(),
+            running_time: Time::new::<second>(self.time as f32),
+        };
+
+        self.faults.apply(self.time, sensors, &mut self.noise)
+    }
+
+    /// Advance the simulation by the given number of seconds
+    pub fn step(&mut self, dt: f64) {
+        if !self.landed {
+            self.step_vehicle(dt);
+        }
+
+        self.time += dt;
+        self.gnss.step(self.time, dt, &self.state, &mut self.noise);
+    }
+
+    /// Advance the motion of the vehicle by the given number of seconds
+    fn step_vehicle(&mut self, dt: f64) {
+        let parameters = &self.parameters;
+        let state = &mut self.state;
+
+        // Time in seconds since the motor was ignited
+        let (time, motor) = (self.time, self.motor);
+        let motor_time = self.ignition.map(|ignition| time - ignition);
+
+        let mut mass = parameters.dry_mass + motor.mass(motor_time.unwrap_or(0.0));
+        if self.separated {
+            mass -= parameters.separation_mass;
+        }
+
+        // Thrust and the torque it produces about the center of mass, in the body frame
+        let thrust = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), self.gimbal.1)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.gimbal.0)
+            * Vector3::y()
+            * motor_time.map_or(0.0, |time| motor.thrust(time));
+        let torque = Vector3::new(0.0, -parameters.gimbal_offset, 0.0).cross(&thrust);
+
+        // The drag of the body and any deployed parachutes, each as a drag coefficient times an area
+        let mut drag_area = parameters.drag_coefficient * parameters.reference_area;
+        if self.drogue_deployed {
+            drag_area += parameters.parachute_drag_coefficient * parameters.drogue_area;
+        }
+        if self.main_deployed {
+            drag_area += parameters.parachute_drag_coefficient * parameters.main_area;
+        }
+        let drag = -0.5 * AIR_DENSITY * drag_area * state.velocity.norm() * state.velocity;
+
+        let force = state.attitude * thrust + drag + Vector3::new(0.0, 0.0, -GRAVITY * mass);
+        let acceleration = force / mass;
+
+        // The vehicle is held on the pad until the thrust overcomes its weight
+        if !self.launched {
+            if acceleration.z <= 0.0 {
+                return;
+            }
+
+            self.launched = true;
+        }
+
+        let angular_acceleration = if self.drogue_deployed || self.main_deployed {
+            -state.angular_velocity / PARACHUTE_DAMPING
+        } else {
+            let inertia = parameters.inertia_tensor();
+            let gyroscopic = state
+                .angular_velocity
+                .cross(&(inertia * state.angular_velocity));
+
+            (torque - gyroscopic).component_div(&Vector3::from(parameters.inertia))
+        };
+
+        state.angular_velocity += angular_acceleration * dt;
+        state.attitude *= UnitQuaternion::from_scaled_axis(state.angular_velocity * dt);
+
+        state.acceleration = acceleration;
+        state.velocity += acceleration * dt;
+        state.position += state.velocity * dt;
+
+        if state.position.z <= 0.0 && state.velocity.z < 0.0 {
+            state.position.z = 0.0;
+            state.velocity = Vector3::zeros();
+            state.acceleration = Vector3::zeros();
+            state.angular_velocity = Vector3::zeros();
+
+            self.landed = true;
+        }
+    }
+}
+
+/// Convert a vector of values in the given unit into a vector of quantities
+fn vector<F: Frame, D: Dimension + ?Sized>(
+    vector: Vector3<f64>,
+    quantity: fn(f32) -> Quantity<D>,
+) -> preflight::Vector3<F, D> {
+    preflight::Vector3::new(
+        quantity(vector.x as f32),
+        quantity(vector.y as f32),
+        quantity(vector.z as f32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(parameters: VehicleParameters) -> Simulation {
+        Simulation::new(
+            parameters,
+            Motor::default(),
+            LaunchSite::default(),
+            GnssParameters::default(),
+            Vec::new(),
+            7,
+        )
+    }
+
+    /// Fly the simulation for the given number of steps, returning what the
+    /// sensors read and the true state of the vehicle at each of them
+    fn fly(simulation: &mut Simulation, steps: usize) -> Vec<String> {
+        (0..steps)
+            .map(|_| {
+                let sensors = simulation.sensors();
+                simulation.step(0.01);
+
+                format!("{:?} {:?}", sensors, simulation.truth())
+            })
+            .collect()
+    }
+
+    /// A vehicle with every device wired to the channel of the same index
+    fn wired() -> VehicleParameters {
+        VehicleParameters {
+            separation_mass: 0.05,
+            pyro: vehicle::PyroWiring {
+                drogue: Some(0),
+                main: Some(1),
+                separation: Some(2),
+                ignition: Some(3),
+            },
+            ..VehicleParameters::default()
+        }
+    }
+
+    fn channel(id: u8) -> PyroChannel {
+        PyroChannel::new(id).unwrap()
+    }
+
+    #[test]
+    fn each_device_has_its_effect() {
+        let mut simulation = simulation(wired());
+        let all = (0..4).fold(PyroCommands::NONE, |pyro, id| pyro.fire(channel(id)));
+
+        simulation.step(0.5);
+        let fired = simulation.fire(all);
+
+        assert_eq!(
+            fired,
+            [
+                (channel(0), Some(PyroDevice::Drogue)),
+                (channel(1), Some(PyroDevice::Main)),
+                (channel(2), Some(PyroDevice::Separation)),
+                (channel(3), Some(PyroDevice::Ignition)),
+            ]
+        );
+        assert!(simulation.drogue_deployed);
+        assert!(simulation.main_deployed);
+        assert!(simulation.separated);
+        assert_eq!(simulation.ignition, Some(0.5));
+    }
+
+    #[test]
+    fn channels_without_a_device_are_still_fired() {
+        let mut simulation = simulation(VehicleParameters::default());
+
+        let fired = simulation.fire(PyroCommands::NONE.fire(channel(5)));
+        assert_eq!(fired, [(channel(5), None)]);
+        assert!(simulation.fired.is_fired(channel(5)));
+    }
+
+    #[test]
+    fn firing_again_does_nothing() {
+        let mut simulation = simulation(wired());
+        let ignition = PyroCommands::NONE.fire(channel(3));
+
+        simulation.step(0.5);
+        assert_eq!(simulation.fire(ignition).len(), 1);
+
+        simulation.step(0.5);
+        assert!(simulation.fire(ignition).is_empty());
+        assert_eq!(simulation.fire(ignition.fire(channel(0))).len(), 1);
+
+        // Even a second igniter can not ignite the motor again
+        simulation.trigger(PyroDevice::Ignition);
+        assert_eq!(simulation.ignition, Some(0.5));
+    }
+
+    #[test]
+    fn the_motor_waits_for_a_wired_igniter() {
+        let mut unwired = simulation(VehicleParameters::default());
+        let mut wired = simulation(wired());
+
+        assert_eq!(unwired.ignition, Some(0.0));
+        assert_eq!(wired.ignition, None);
+
+        fly(&mut unwired, 100);
+        fly(&mut wired, 100);
+        assert!(unwired.altitude() > 0.0);
+        assert_eq!(wired.altitude(), 0.0);
+    }
+
+    #[test]
+    fn separation_leaves_mass_behind() {
+        let ignite = PyroCommands::NONE.fire(channel(3));
+        let mut whole = simulation(wired());
+        let mut separated = simulation(wired());
+
+        whole.fire(ignite);
+        separated.fire(ignite.fire(channel(2)));
+
+        fly(&mut whole, 100);
+        fly(&mut separated, 100);
+        assert!(separated.altitude() > whole.altitude());
+    }
+
+    #[test]
+    fn clone_carries_on_from_the_same_moment() {
+        let mut original = simulation(VehicleParameters::default());
+        fly(&mut original, 200);
+
+        let mut checkpoint = original.clone();
+        assert_eq!(fly(&mut original, 300), fly(&mut checkpoint, 300));
+    }
+
+    #[test]
+    fn branches_are_dispersed_from_the_nominal_vehicle() {
+        let dispersion = Dispersion {
+            dry_mass: 0.1,
+            drag_coefficient: 0.1,
+            ..Dispersion::default()
+        };
+
+        let mut once = simulation(VehicleParameters::default());
+        once.branch(&dispersion, 3);
+
+        let mut twice = simulation(VehicleParameters::default());
+        twice.branch(&dispersion, 5);
+        twice.branch(&dispersion, 3);
+
+        assert_eq!(
+            format!("{:?}", once.parameters),
+            format!("{:?}", twice.parameters)
+        );
+        assert_eq!(fly(&mut once, 300), fly(&mut twice, 300));
+    }
+}
@@ -0,0 +1,93 @@
+use serde::Deserialize;
+use timescale::{InterpolatedData, InterpolatedDataTable, Lerp};
+
+/// A single point on a motor's thrust curve
+#[derive(Debug, InterpolatedData)]
+pub struct MotorDatapoint {
+    /// Thrust produced by the motor in newtons
+    #[data(rename = "Thrust (N)")]
+    pub thrust: f64,
+}
+
+impl Lerp<f64> for MotorDatapoint {
+    fn lerp(self, other: Self, t: f64) -> Self {
+        Self {
+            thrust: self.thrust.lerp(other.thrust, t),
+        }
+    }
+}
+
+/// The thrust curve of an Estes A8 rocket motor
+#[derive(InterpolatedDataTable)]
+#[table(file = "../assets/motors/Estes_A8.csv", st = "MotorDatapoint")]
+pub struct EstesA8;
+
+/// The thrust curve of an Estes B4 rocket motor
+#[derive(InterpolatedDataTable)]
+#[table(file = "../assets/motors/Estes_B4.csv", st = "MotorDatapoint")]
+pub struct EstesB4;
+
+/// The thrust curve of an Estes C6 rocket motor
+#[derive(InterpolatedDataTable)]
+#[table(file = "../assets/motors/Estes_C6.csv", st = "MotorDatapoint")]
+pub struct EstesC6;
+
+/// The motors that are available to propel the simulated vehicle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Motor {
+    A8,
+    B4,
+    C6,
+}
+
+impl Default for Motor {
+    fn default() -> Self {
+        Motor::C6
+    }
+}
+
+impl Motor {
+    /// Thrust in newtons produced by the motor the given number of seconds after ignition
+    pub fn thrust(self, time: f64) -> f64 {
+        match self {
+            Motor::A8 => EstesA8::get(time).thrust,
+            Motor::B4 => EstesB4::get(time).thrust,
+            Motor::C6 => EstesC6::get(time).thrust,
+        }
+    }
+
+    /// The time in seconds after ignition at which the motor burns out
+    pub fn burn_time(self) -> f64 {
+        match self {
+            Motor::A8 => EstesA8::MAX,
+            Motor::B4 => EstesB4::MAX,
+            Motor::C6 => EstesC6::MAX,
+        }
+    }
+
+    /// Mass in kilograms of the motor before ignition
+    fn total_mass(self) -> f64 {
+        match self {
+            Motor::A8 => 0.01635,
+            Motor::B4 => 0.0201,
+            Motor::C6 => 0.0248,
+        }
+    }
+
+    /// Mass in kilograms of the propellant in the motor before ignition
+    fn propellant_mass(self) -> f64 {
+        match self {
+            Motor::A8 => 0.00312,
+            Motor::B4 => 0.006,
+            Motor::C6 => 0.0123,
+        }
+    }
+
+    /// Mass in kilograms of the motor the given number of seconds after ignition,
+    /// assuming that the propellant burns at a constant rate
+    pub fn mass(self, time: f64) -> f64 {
+        let burnt = (time / self.burn_time()).max(0.0).min(1.0);
+
+        self.total_mass() - self.propellant_mass() * burnt
+    }
+}
@@ -0,0 +1,46 @@
+use std::f64::consts::TAU;
+
+/// A small seedable random number generator for sensor noise
+///
+/// The same seed always produces the same noise, on every platform, so that a
+/// scenario flies identically every time it is run
+#[derive(Debug, Clone)]
+pub struct Noise {
+    state: u64,
+}
+
+impl Noise {
+    /// Create a generator from the given seed
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed with splitmix64, since xorshift can not start from zero
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        Self {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    /// The next output of the xorshift64* generator
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A uniformly distributed number in the range (0, 1]
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A normally distributed number with a mean of zero and the given standard deviation
+    pub fn gaussian(&mut self, std_dev: f64) -> f64 {
+        // Box-Muller transform
+        let (u1, u2) = (self.uniform(), self.uniform());
+
+        std_dev * (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::{self, Display, Formatter};
+
+use nalgebra::{Matrix3, UnitQuaternion, Vector3};
+use preflight::PyroChannel;
+use serde::Deserialize;
+
+/// Physical parameters of the simulated vehicle
+///
+/// The vehicle's body frame has the y axis pointing out of the nose, with the
+/// thrust vectoring gimbal rotating the thrust about the body's x and z axes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VehicleParameters {
+    /// Mass of the vehicle without a motor in kilograms
+    pub dry_mass: f64,
+    /// Moments of inertia about the body x, y and z axes in kilogram meters squared
+    pub inertia: [f64; 3],
+    /// Distance from the center of mass to the thrust vectoring gimbal in meters
+    pub gimbal_offset: f64,
+    /// Largest deflection of the thrust vectoring gimbal in degrees
+    pub gimbal_limit: f64,
+    /// Drag coefficient of the body
+    pub drag_coefficient: f64,
+    /// Cross sectional area of the body in meters squared
+    pub reference_area: f64,
+    /// Drag coefficient of the recovery parachutes
+    pub parachute_drag_coefficient: f64,
+    /// Area of the drogue parachute in meters squared
+    pub drogue_area: f64,
+    /// Area of the main parachute in meters squared
+    pub main_area: f64,
+    /// Mass in kilograms that is left behind at stage separation
+    pub separation_mass: f64,
+    /// Angle from vertical that the vehicle starts at on the pad, in degrees
+    pub launch_tilt: f64,
+    /// The devices wired to each of the pyrotechnic channels
+    pub pyro: PyroWiring,
+}
+
+impl Default for VehicleParameters {
+    fn default() -> Self {
+        Self {
+            dry_mass: 0.1,
+            inertia: [0.0026, 0.0001, 0.0026],
+            gimbal_offset: 0.2,
+            gimbal_limit: 5.0,
+            drag_coefficient: 0.5,
+            reference_area: 0.00049,
+            parachute_drag_coefficient: 1.5,
+            drogue_area: 0.01,
+            main_area: 0.07,
+            separation_mass: 0.0,
+            launch_tilt: 0.0,
+            pyro: PyroWiring::default(),
+        }
+    }
+}
+
+impl VehicleParameters {
+    /// Inertia tensor of the vehicle in the body frame
+    pub fn inertia_tensor(&self) -> Matrix3<f64> {
+        Matrix3::from_diagonal(&Vector3::from(self.inertia))
+    }
+}
+
+/// The pyrotechnic channel that each device on the vehicle is wired to
+///
+/// Devices that are not wired to a channel can never be triggered by the
+/// avionics. If the motor igniter is not wired, the motor is ignited by the
+/// launch controller at the start of the flight
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PyroWiring {
+    pub drogue: Option<u8>,
+    pub main: Option<u8>,
+    pub separation: Option<u8>,
+    pub ignition: Option<u8>,
+}
+
+impl PyroWiring {
+    /// Each device along with the id of the channel it is wired to, if any
+    pub fn channels(&self) -> [(PyroDevice, Option<u8>); 4] {
+        [
+            (PyroDevice::Drogue, self.drogue),
+            (PyroDevice::Main, self.main),
+            (PyroDevice::Separation, self.separation),
+            (PyroDevice::Ignition, self.ignition),
+        ]
+    }
+
+    /// The device wired to the given channel
+    pub fn device(&self, channel: PyroChannel) -> Option<PyroDevice> {
+        self.channels()
+            .iter()
+            .find(|(_, id)| *id == Some(channel.id()))
+            .map(|(device, _)| *device)
+    }
+
+    /// Ensure that every wired channel exists and has only one device on it
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let channels = self.channels();
+
+        for (i, (device, id)) in channels.iter().enumerate() {
+            let id = match id {
+                Some(id) => *id,
+                None => continue,
+            };
+
+            if PyroChannel::new(id).is_none() {
+                anyhow::bail!(
+                    "the {} is wired to pyro channel {}, but there are only {} channels",
+                    device,
+                    id,
+                    preflight::PYRO_CHANNELS
+                );
+            }
+
+            if let Some((other, _)) = channels[..i].iter().find(|(_, other)| *other == Some(id)) {
+                anyhow::bail!(
+                    "the {} and the {} are both wired to pyro channel {}",
+                    other,
+                    device,
+                    id
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A device on the vehicle that is triggered by a pyrotechnic channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PyroDevice {
+    /// Deploys the drogue parachute
+    Drogue,
+    /// Deploys the main parachute
+    Main,
+    /// Separates the spent stage from the vehicle
+    Separation,
+    /// Ignites the motor
+    Ignition,
+}
+
+impl Display for PyroDevice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PyroDevice::Drogue => "drogue parachute",
+            PyroDevice::Main => "main parachute",
+            PyroDevice::Separation => "stage separation charge",
+            PyroDevice::Ignition => "motor igniter",
+        })
+    }
+}
+
+/// The true kinematic state of the vehicle
+///
+/// The world frame is east, north, up with the origin at the launch pad
+#[derive(Debug, Clone)]
+pub struct VehicleState {
+    /// Position in the world frame in meters
+    pub position: Vector3<f64>,
+    /// Velocity in the world frame in meters per second
+    pub velocity: Vector3<f64>,
+    /// Acceleration in the world frame in meters per second squared
+    pub acceleration: Vector3<f64>,
+    /// Rotation from the body frame into the world frame
+    pub attitude: UnitQuaternion<f64>,
+    /// Angular velocity in the body frame in radians per second
+    pub angular_velocity: Vector3<f64>,
+}
+
+impl VehicleState {
+    /// The state of a vehicle sitting on the launch pad, tilted from vertical by
+    /// the given angle in degrees
+    pub fn on_pad(launch_tilt: f64) -> Self {
+        Self {
+            position: Vector3::zeros(),
+            velocity: Vector3::zeros(),
+            acceleration: Vector3::zeros(),
+            // Point the body's y axis up, then tilt it towards the east
+            attitude: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), launch_tilt.to_radians())
+                * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f64::consts::FRAC_PI_2),
+            angular_velocity: Vector3::zeros(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Recording of the values that the avionics publish alongside the true state of
+//! the simulated vehicle
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use preflight::telemetry::TelemetryValue;
+
+/// A csv file recording the true state of the vehicle and the telemetry of the
+/// avionics at each step of a scenario
+///
+/// The avionics are free to publish different values on each step, so rather
+/// than a column for each value, every value is written on its own row as the
+/// time, the name of the value and the value itself
+pub struct TelemetryLog {
+    file: BufWriter<File>,
+}
+
+impl TelemetryLog {
+    /// Create the log file, writing the header
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).with_context(|| {
+                format!("failed to create telemetry log directory {:?}", directory)
+            })?;
+        }
+
+        let mut file = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create telemetry log {:?}", path))?,
+        );
+
+        writeln!(file, "time,name,value")?;
+
+        Ok(Self { file })
+    }
+
+    /// Record the true state and the telemetry at the given time in seconds
+    pub fn record(
+        &mut self,
+        time: f64,
+        truth: Vec<(&'static str, f64)>,
+        telemetry: Vec<(String, TelemetryValue)>,
+    ) -> io::Result<()> {
+        for (name, value) in truth {
+            writeln!(self.file, "{},truth.{},{}", time, name, value)?;
+        }
+
+        for (name, value) in telemetry {
+            match value {
+                TelemetryValue::Scalar(value) => {
+                    writeln!(self.file, "{},telemetry.{},{}", time, name, value)?
+                }
+                TelemetryValue::Vector(values) => {
+                    for (axis, value) in ["x", "y", "z"].iter().zip(&values) {
+                        writeln!(self.file, "{},telemetry.{}.{},{}", time, name, axis, value)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write out anything that is still buffered
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
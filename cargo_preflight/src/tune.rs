@@ -0,0 +1,431 @@
+//! Tuning of the avionics' parameters by flying Monte Carlo batches of scenarios
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    args::{SimulationArguments, TuneArguments},
+    flight::{log_event_messages, Flight},
+    harness::{AvionicsHarness, PanicCaught},
+    scenario::{Scenario, ScenarioReport},
+    shell::Shell,
+    watchdog::Watchdog,
+};
+
+/// Cost of each expectation of a scenario that a run fails to meet, which
+/// outweighs any amount of tilt, effort or missed apogee
+const FAILURE_COST: f64 = 1000.0;
+
+/// How badly a Monte Carlo batch was flown, averaged over its runs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cost {
+    /// Root mean square tilt from vertical on the way up, in degrees
+    pub tilt: f64,
+    /// Root mean square gimbal deflection on the way up, in degrees
+    pub effort: f64,
+    /// Distance in meters from the target apogee
+    pub apogee: f64,
+    /// Expectations of the scenarios that were not met
+    pub failures: f64,
+}
+
+impl Cost {
+    /// The cost of a single run of a scenario
+    fn of(scenario: &Scenario, report: &ScenarioReport) -> Self {
+        Self {
+            tilt: report.ascent.tilt,
+            effort: report.ascent.effort,
+            apogee: scenario
+                .target_apogee
+                .map_or(0.0, |target| (report.ascent.apogee - target).abs()),
+            failures: scenario.check(report).len() as f64,
+        }
+    }
+
+    /// The weighted sum of the costs, which is minimized
+    pub fn total(&self, tune: &TuneArguments) -> f64 {
+        let total = tune.tilt_weight * self.tilt
+            + tune.effort_weight * self.effort
+            + tune.apogee_weight * self.apogee
+            + FAILURE_COST * self.failures;
+
+        // A simulation that blew up is as bad as it gets
+        if total.is_nan() {
+            f64::INFINITY
+        } else {
+            total
+        }
+    }
+}
+
+impl Display for Cost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3}° tilt, {:.3}° deflection, {:.2} m from the target apogee",
+            self.tilt, self.effort, self.apogee
+        )?;
+
+        if self.failures > 0.0 {
+            write!(f, ", {:.2} failed expectations", self.failures)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Tune the parameters of the avionics over the given scenarios, returning the
+/// best values found for each of [`TuneArguments::parameters`]
+pub fn tune(
+    harness: &mut AvionicsHarness<PanicCaught>,
+    watchdog: &Watchdog,
+    scenarios: &[Scenario],
+    sim: &SimulationArguments,
+    tune: &TuneArguments,
+    shell: &mut Shell,
+) -> Result<Vec<f64>> {
+    // The value that a scenario sets would otherwise be tuned from the value
+    // that it has before any scenario is flown
+    for name in &tune.parameters {
+        let set = |parameters: &[(String, f64)]| parameters.iter().any(|(set, _)| set == name);
+
+        if let Some(scenario) = scenarios
+            .iter()
+            .find(|scenario| set(&scenario.parameters) && !set(&sim.parameters))
+        {
+            bail!(
+                "parameter `{}` is set by scenario `{}`, so there is no single value to start tuning it from. \
+                give it one with `--set {}=VALUE`",
+                name,
+                scenario.name,
+                name
+            );
+        }
+    }
+
+    for (name, value) in &sim.parameters {
+        harness.set_parameter(name, *value)?;
+    }
+
+    let start = tune
+        .parameters
+        .iter()
+        .map(|name| harness.parameter(name))
+        .collect::<Result<Vec<_>>>()?;
+    let steps = start
+        .iter()
+        .map(|value| {
+            if *value == 0.0 {
+                tune.step
+            } else {
+                value * tune.step
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut quiet = Shell::quiet();
+    let mut batch = |values: &[f64]| -> Result<Cost> {
+        let mut cost = Cost::default();
+        let mut add = |scenario: &Scenario, report: &ScenarioReport| {
+            let run = Cost::of(scenario, report);
+
+            cost.tilt += run.tilt;
+            cost.effort += run.effort;
+            cost.apogee += run.apogee;
+            cost.failures += run.failures;
+        };
+
+        for scenario in scenarios {
+            match scenario.branch {
+                // Every run is flown from the pad with its own vehicle and noise
+                None => {
+                    for run in 0..tune.runs {
+                        let scenario = scenario.dispersed(run);
+
+                        prepare(harness, watchdog, &mut quiet, &scenario, sim, tune, values)?;
+
+                        let report = scenario.run(harness, watchdog, &mut quiet, None, None)?;
+                        add(&scenario, &report);
+                    }
+                }
+                // The runs share a single flight up until they branch off from it
+                Some(branch) => {
+                    prepare(harness, watchdog, &mut quiet, scenario, sim, tune, values)?;
+
+                    let mut flight = Flight::new(scenario, &mut quiet)?;
+                    flight.fly(branch, harness, watchdog, &mut quiet, &mut None, &mut None)?;
+                    let checkpoint = flight.checkpoint(harness, watchdog)?;
+
+                    for run in 0..tune.runs {
+                        let mut flight = checkpoint.resume(harness, watchdog)?;
+                        flight.branch(&scenario.dispersion, scenario.run_seed(run));
+                        flight.fly(
+                            scenario.duration,
+                            harness,
+                            watchdog,
+                            &mut quiet,
+                            &mut None,
+                            &mut None,
+                        )?;
+
+                        let report = flight.finish(&mut quiet, None)?;
+                        add(scenario, &report);
+                    }
+                }
+            }
+        }
+
+        let runs = (scenarios.len() as u64 * tune.runs).max(1) as f64;
+
+        Ok(Cost {
+            tilt: cost.tilt / runs,
+            effort: cost.effort / runs,
+            apogee: cost.apogee / runs,
+            failures: cost.failures / runs,
+        })
+    };
+
+    let initial = batch(&start)?;
+    shell.status(
+        "Flown",
+        format!(
+            "{} runs of {} scenarios per batch, starting at a cost of {:.4} ({})",
+            tune.runs,
+            scenarios.len(),
+            initial.total(tune),
+            initial
+        ),
+    )?;
+
+    let mut best = f64::INFINITY;
+    let (values, _) = nelder_mead(
+        &start,
+        &steps,
+        tune.iterations,
+        |values| Ok(batch(values)?.total(tune)),
+        |iteration, values, cost| {
+            if cost < best {
+                best = cost;
+
+                shell.status(
+                    "Improved",
+                    format!(
+                        "to a cost of {:.4} at iteration {} with {}",
+                        cost,
+                        iteration,
+                        describe(&tune.parameters, values)
+                    ),
+                )?;
+            }
+
+            Ok(())
+        },
+    )?;
+
+    let tuned = batch(&values)?;
+    shell.status(
+        "Tuned",
+        format!(
+            "from a cost of {:.4} to {:.4} ({})",
+            initial.total(tune),
+            tuned.total(tune),
+            tuned
+        ),
+    )?;
+
+    Ok(values)
+}
+
+/// Warm reset the avionics and set their parameters for a run of the scenario,
+/// with the tuned values taking precedence over every other
+fn prepare(
+    harness: &mut AvionicsHarness<PanicCaught>,
+    watchdog: &Watchdog,
+    shell: &mut Shell,
+    scenario: &Scenario,
+    sim: &SimulationArguments,
+    tune: &TuneArguments,
+    values: &[f64],
+) -> Result<()> {
+    watchdog.watch(|| harness.reset());
+    log_event_messages(harness, shell, "reset")?;
+    harness.restore_parameters()?;
+
+    for (name, value) in scenario.parameters.iter().chain(&sim.parameters) {
+        harness.set_parameter(name, *value)?;
+    }
+    for (name, value) in tune.parameters.iter().zip(values) {
+        harness.set_parameter(name, *value)?;
+    }
+
+    Ok(())
+}
+
+/// The parameters along with their values, as `name = value`
+fn describe(names: &[String], values: &[f64]) -> String {
+    names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("`{} = {}`", name, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Minimize the cost with the Nelder–Mead simplex method, which needs nothing
+/// but the cost at each point it tries, starting from the simplex formed by the
+/// start and a step along each axis from it
+///
+/// `progress` is given the best point and its cost after each iteration. The
+/// best point found and its cost are returned.
+fn nelder_mead(
+    start: &[f64],
+    steps: &[f64],
+    iterations: usize,
+    mut cost: impl FnMut(&[f64]) -> Result<f64>,
+    mut progress: impl FnMut(usize, &[f64], f64) -> Result<()>,
+) -> Result<(Vec<f64>, f64)> {
+    /// Costs closer together than this are considered converged
+    const TOLERANCE: f64 = 1e-9;
+
+    // Points along the line from `from` through `to`, `by` times the distance between them
+    let along = |from: &[f64], to: &[f64], by: f64| -> Vec<f64> {
+        from.iter()
+            .zip(to)
+            .map(|(from, to)| from + by * (to - from))
+            .collect()
+    };
+    let by_cost =
+        |a: &(Vec<f64>, f64), b: &(Vec<f64>, f64)| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal);
+
+    let mut simplex = vec![(start.to_vec(), cost(start)?)];
+    for (axis, step) in steps.iter().enumerate() {
+        let mut point = start.to_vec();
+        point[axis] += step;
+
+        let value = cost(&point)?;
+        simplex.push((point, value));
+    }
+
+    for iteration in 1..=iterations {
+        simplex.sort_by(by_cost);
+
+        let (best, worst) = (simplex[0].1, simplex[simplex.len() - 1].1);
+        if (worst - best).abs() <= TOLERANCE {
+            break;
+        }
+
+        // The centroid of every point but the worst
+        let others = &simplex[..simplex.len() - 1];
+        let centroid = (0..start.len())
+            .map(|axis| {
+                others.iter().map(|(point, _)| point[axis]).sum::<f64>() / others.len() as f64
+            })
+            .collect::<Vec<_>>();
+
+        let (worst_point, worst) = simplex.pop().unwrap();
+        let second_worst = simplex[simplex.len() - 1].1;
+
+        let reflected = along(&centroid, &worst_point, -1.0);
+        let reflected_cost = cost(&reflected)?;
+
+        if reflected_cost < best {
+            let expanded = along(&centroid, &worst_point, -2.0);
+            let expanded_cost = cost(&expanded)?;
+
+            simplex.push(if expanded_cost < reflected_cost {
+                (expanded, expanded_cost)
+            } else {
+                (reflected, reflected_cost)
+            });
+        } else if reflected_cost < second_worst {
+            simplex.push((reflected, reflected_cost));
+        } else {
+            let contracted = along(&centroid, &worst_point, 0.5);
+            let contracted_cost = cost(&contracted)?;
+
+            if contracted_cost < worst {
+                simplex.push((contracted, contracted_cost));
+            } else {
+                // Shrink every point towards the best one
+                simplex.push((worst_point, worst));
+
+                let best_point = simplex[0].0.clone();
+                for (point, value) in simplex.iter_mut().skip(1) {
+                    *point = along(&best_point, point, 0.5);
+                    *value = cost(point)?;
+                }
+            }
+        }
+
+        let (point, value) = simplex
+            .iter()
+            .min_by(|a, b| by_cost(a, b))
+            .expect("the simplex is never empty");
+        progress(iteration, point, *value)?;
+    }
+
+    Ok(simplex
+        .into_iter()
+        .min_by(by_cost)
+        .expect("the simplex is never empty"))
+}
+
+#[cfg(test)]
+mod tests {
+    use structopt::StructOpt;
+
+    use super::*;
+
+    /// Minimize the function from the given start, with steps of a tenth
+    fn minimize(start: &[f64], function: fn(&[f64]) -> f64) -> Vec<f64> {
+        let steps = vec![0.1; start.len()];
+        let (point, _) = nelder_mead(
+            start,
+            &steps,
+            1000,
+            |point| Ok(function(point)),
+            |_, _, _| Ok(()),
+        )
+        .unwrap();
+
+        point
+    }
+
+    #[test]
+    fn converges_on_a_quadratic() {
+        let point = minimize(&[0.0, 0.0, 0.0], |point| {
+            (point[0] - 1.0).powi(2) + 2.0 * (point[1] + 2.0).powi(2) + (point[2] - 0.5).powi(2)
+        });
+
+        for (value, expected) in point.iter().zip(&[1.0, -2.0, 0.5]) {
+            assert!((value - expected).abs() < 1e-3, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn converges_on_rosenbrock() {
+        let point = minimize(&[-1.2, 1.0], |point| {
+            (1.0 - point[0]).powi(2) + 100.0 * (point[1] - point[0].powi(2)).powi(2)
+        });
+
+        for value in &point {
+            assert!((value - 1.0).abs() < 1e-2, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn a_cost_that_is_not_a_number_is_infinite() {
+        let tune = TuneArguments::from_iter(&["tune", "--tune", "gain"]);
+        let cost = Cost {
+            tilt: f64::NAN,
+            ..Cost::default()
+        };
+
+        assert_eq!(cost.total(&tune), f64::INFINITY);
+        assert!(Cost::default().total(&tune).is_finite());
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{exit::ExitCode, shell::Shell};
+
+/// A watchdog which terminates the runner if the avionics take too long to
+/// produce a control signal
+pub struct Watchdog {
+    armed_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Watchdog {
+    /// Start a watchdog that will trip once it has been armed for longer than
+    /// the given timeout
+    pub fn spawn(timeout: Duration) -> Self {
+        let armed_at = Arc::new(Mutex::new(None::<Instant>));
+
+        {
+            let armed_at = armed_at.clone();
+
+            thread::spawn(move || loop {
+                thread::sleep(timeout / 10);
+
+                if let Some(armed_at) = *armed_at.lock().unwrap() {
+                    if armed_at.elapsed() > timeout {
+                        Shell::new()
+                            .error(format!(
+                                "the avionics failed to produce a control signal within {:?}, tripping the watchdog",
+                                timeout
+                            ))
+                            .expect("Failed to warn the user of the watchdog trip");
+
+                        ExitCode::WatchdogTripped.exit();
+                    }
+                }
+            });
+        }
+
+        Self { armed_at }
+    }
+
+    /// Run the function with the watchdog armed
+    pub fn watch<T>(&self, f: impl FnOnce() -> T) -> T {
+        *self.armed_at.lock().unwrap() = Some(Instant::now());
+
+        let result = f();
+
+        *self.armed_at.lock().unwrap() = None;
+
+        result
+    }
+}
@@ -1,39 +1,294 @@
 #![no_std]
 #![forbid(unsafe_code)]
 
-use core::f32::consts::TAU;
-
 use preflight::{
     avionics_harness,
+    control::Pid,
+    frame::Enu,
     micromath::F32Ext,
-    uom::si::angle::{degree, Angle},
-    Avionics, Control, Guidance, Sensors, ThrustVector,
+    phase::PhaseTracker,
+    uom::si::{
+        angle::{self, degree, radian},
+        f32::{Angle, Frequency, Length, Ratio, Time},
+        frequency::hertz,
+        length::meter,
+        ratio::ratio,
+        time::second,
+    },
+    AbortCause, Avionics, Command, CommandResponse, Control, EventFlags, FlightPhase, Guidance,
+    Parameters, PyroChannel, PyroCommands, Sensors, Snapshot, Telemetry, ThrustVector, Vector3,
 };
 
-#[derive(Debug)]
-pub struct Controller;
+/// Pyro channel that the drogue parachute is wired to
+const DROGUE: Option<PyroChannel> = PyroChannel::new(0);
+
+/// Largest deflection of the thrust vectoring gimbal in degrees
+const GIMBAL_LIMIT: f32 = 5.0;
+
+/// A loop turning the tilt of the vehicle about one of the gimbal's axes into a
+/// deflection of the gimbal about that axis
+type TiltLoop = Pid<angle::Dimension, angle::Dimension>;
+
+/// Gains that can be tuned without rebuilding the avionics
+#[derive(Debug, Parameters, Snapshot)]
+pub struct Params {
+    tilt: TiltParams,
+}
+
+/// Gains of the loops that hold the vehicle vertical
+#[derive(Debug, Parameters, Snapshot)]
+pub struct TiltParams {
+    /// Degrees of deflection per degree of tilt
+    proportional: f32,
+    /// Rate of integration in Hz
+    integral: f32,
+    /// Derivative time in seconds
+    derivative: f32,
+}
+
+impl Params {
+    const DEFAULT: Self = Params {
+        tilt: TiltParams {
+            // Deflecting the gimbal about an axis turns the vehicle the other way about it
+            proportional: -0.2,
+            integral: 0.2,
+            derivative: 0.15,
+        },
+    };
+}
+
+/// What the flight computer keeps in its backup RAM, which survives it resetting
+#[derive(Debug, Snapshot)]
+pub struct Persistent {
+    /// The phase of flight at the last guidance
+    phase: FlightPhase,
+    /// Altitude of the launch pad in meters
+    pad_altitude: f32,
+}
+
+impl Persistent {
+    const POWER_ON: Self = Persistent {
+        phase: FlightPhase::Pad,
+        pad_altitude: 0.0,
+    };
+}
+
+#[derive(Debug, Snapshot)]
+pub struct Controller {
+    params: Params,
+    persistent: Persistent,
+    /// The loops about the body x and z axes, which are built once the avionics
+    /// are initialized since quantities cannot be created in a `const fn`
+    tilt: Option<[TiltLoop; 2]>,
+    /// The error of the loops about the body x and z axes at the last guidance
+    tilt_error: Option<(Angle, Angle)>,
+    phase: Option<PhaseTracker>,
+    /// Range safety has commanded the flight to be aborted
+    range_safety_abort: bool,
+    /// The pyros are kept from firing while the vehicle is checked out on the pad
+    test_mode: bool,
+}
 
 impl Controller {
     const fn new() -> Self {
-        Controller
+        Controller {
+            params: Params::DEFAULT,
+            persistent: Persistent::POWER_ON,
+            tilt: None,
+            tilt_error: None,
+            phase: None,
+            range_safety_abort: false,
+            test_mode: false,
+        }
     }
 }
 
+fn tilt_loop(params: &TiltParams) -> TiltLoop {
+    Pid::new(
+        Ratio::new::<ratio>(params.proportional),
+        Frequency::new::<hertz>(params.integral),
+        Time::new::<second>(params.derivative),
+    )
+    .with_limits(
+        Angle::new::<degree>(-GIMBAL_LIMIT),
+        Angle::new::<degree>(GIMBAL_LIMIT),
+    )
+    .with_derivative_filter(Time::new::<second>(0.02))
+}
+
+/// The tilt of the vehicle from vertical about its body x and z axes
+fn tilt(sensors: &Sensors) -> (Angle, Angle) {
+    let up = sensors.attitude.inverse().rotate(&Vector3::<Enu, _>::new(
+        Ratio::new::<ratio>(0.0),
+        Ratio::new::<ratio>(0.0),
+        Ratio::new::<ratio>(1.0),
+    ));
+    let (x, y, z) = (
+        up.x().get::<ratio>(),
+        up.y().get::<ratio>(),
+        up.z().get::<ratio>(),
+    );
+
+    (
+        Angle::new::<radian>((-z).atan2(y)),
+        Angle::new::<radian>(x.atan2(y)),
+    )
+}
+
 #[avionics_harness(default = "Controller::new()")]
 impl Avionics for Controller {
+    type Parameters = Params;
+
+    fn init(&mut self) {
+        // The gains are loaded from flash as the flight computer boots, and the
+        // runner sets any that are overridden right after
+        self.params = Params::DEFAULT;
+        self.tilt = Some([tilt_loop(&self.params.tilt), tilt_loop(&self.params.tilt)]);
+
+        let mut phase = PhaseTracker::default();
+        let persistent = &self.persistent;
+
+        // The flight computer has reset after being armed, so carry on where it
+        // left off. Otherwise it stays on the pad until it is commanded to arm
+        if persistent.phase >= FlightPhase::Armed {
+            preflight::warn!(
+                "recovering into phase {} after a reset",
+                persistent.phase as u8
+            );
+
+            phase.resume(
+                persistent.phase,
+                Length::new::<meter>(persistent.pad_altitude),
+            );
+        }
+
+        self.phase = Some(phase);
+    }
+
     fn guide(&mut self, sensors: &Sensors) -> Control {
-        // Produce a sinusoidal TVC control
+        // The gains may have been tuned since the last guidance
+        let gains = &self.params.tilt;
+        for tilt in self.tilt.iter_mut().flatten() {
+            tilt.set_gains(
+                Ratio::new::<ratio>(gains.proportional),
+                Frequency::new::<hertz>(gains.integral),
+                Time::new::<second>(gains.derivative),
+            );
+        }
+
+        let ([x_loop, z_loop], phase) = match (&mut self.tilt, &mut self.phase) {
+            (Some(tilt), Some(phase)) => (tilt, phase),
+            _ => {
+                preflight::warn!("guidance was requested before the avionics were initialized");
+
+                return Control::RecoverableFailure;
+            }
+        };
+
+        if self.range_safety_abort {
+            preflight::error!("aborting the flight as commanded by range safety");
+
+            return Control::ABORT(AbortCause::RangeSafety);
+        }
+
+        if phase.update(sensors) == Some(FlightPhase::Apogee) {
+            preflight::info!(
+                "apogee at {} m above the pad, deploying the drogue",
+                phase.altitude_above_pad()
+            );
+        }
+
+        self.persistent = Persistent {
+            phase: phase.phase(),
+            pad_altitude: phase.pad_altitude().get::<meter>(),
+        };
 
-        // The angle of the seconds hand
-        let time_angle = sensors.running_time.value * TAU;
+        // Hold the vehicle vertical
+        let (x, z) = tilt(sensors);
+        let vertical = Angle::new::<degree>(0.0);
+        self.tilt_error = Some((vertical - x, vertical - z));
+        let tvc = ThrustVector {
+            x: x_loop.update(vertical, x, sensors.running_time),
+            z: z_loop.update(vertical, z, sensors.running_time),
+        };
+
+        let pyro = match DROGUE {
+            Some(drogue) if phase.phase() >= FlightPhase::Apogee && !self.test_mode => {
+                PyroCommands::NONE.fire(drogue)
+            }
+            _ => PyroCommands::NONE,
+        };
 
         Control::Guidance(Guidance {
-            tvc: ThrustVector {
-                x: Angle::new::<degree>(time_angle.sin()),
-                z: Angle::new::<degree>(time_angle.cos()),
-            },
+            tvc,
+            pyro,
+            flags: EventFlags::NONE,
+            phase: phase.phase(),
         })
-        // Some(c)
-        // todo!()
+    }
+
+    fn command(&mut self, command: &Command<'_>) -> CommandResponse {
+        if let Command::SetParameter { name, value } = *command {
+            return preflight::params::command(self.parameters(), name, value);
+        }
+
+        let phase = match &mut self.phase {
+            Some(phase) => phase,
+            None => return CommandResponse::Rejected,
+        };
+
+        match command {
+            Command::Arm if phase.phase() <= FlightPhase::Armed => {
+                self.test_mode = false;
+                phase.arm();
+            }
+            Command::Disarm if phase.phase() <= FlightPhase::Armed => phase.disarm(),
+            Command::Abort => self.range_safety_abort = true,
+            Command::EnterTestMode if phase.phase() == FlightPhase::Pad => self.test_mode = true,
+            _ => return CommandResponse::Rejected,
+        }
+
+        CommandResponse::Accepted
+    }
+
+    fn telemetry(&self, telemetry: &mut Telemetry<'_>) {
+        if let Some(phase) = &self.phase {
+            telemetry.scalar("phase", f64::from(phase.phase() as u8));
+            telemetry.quantity("altitude", phase.filter().altitude());
+            telemetry.quantity("vertical_velocity", phase.filter().vertical_velocity());
+        }
+
+        if let Some((x, z)) = self.tilt_error {
+            telemetry.quantity("tilt_error.x", x);
+            telemetry.quantity("tilt_error.z", z);
+        }
+    }
+
+    fn parameters(&mut self) -> Option<&mut Params> {
+        Some(&mut self.params)
+    }
+
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(self)
+    }
+
+    fn persistent(&mut self) -> Option<&mut dyn Snapshot> {
+        Some(&mut self.persistent)
+    }
+
+    fn reset(&mut self) {
+        self.params = Params::DEFAULT;
+        self.persistent = Persistent::POWER_ON;
+        self.tilt_error = None;
+        self.range_safety_abort = false;
+        self.test_mode = false;
+
+        for tilt in self.tilt.iter_mut().flatten() {
+            tilt.reset();
+        }
+
+        if let Some(phase) = &mut self.phase {
+            *phase = PhaseTracker::default();
+        }
     }
 }
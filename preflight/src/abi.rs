@@ -4,19 +4,524 @@
 //! This module's contents are only made public for use by code generated by the
 //! [`avionics_harness`](macro@crate::avionics_harness) macro
 
-use core::panic::PanicInfo;
+use core::{
+    fmt::{self, Debug, Write},
+    marker::PhantomData,
+    mem::{align_of, size_of},
+    panic::PanicInfo,
+};
 
-use crate::{Avionics, Control, Sensors};
+use crate::{
+    params::Parameters,
+    snapshot::{self, Snapshot, SnapshotError, SnapshotWriter},
+    telemetry::{Telemetry, TelemetryValue},
+    AbortCause, Avionics, ChannelName, Command, CommandResponse, Control, Signals,
+};
+
+/// The version of the ABI spoken by this version of preflight
+///
+/// This must be bumped any time a symbol exported by the
+/// [`avionics_harness`](macro@crate::avionics_harness) macro is added, removed
+/// or has its signature changed.
+pub const ABI_VERSION: u32 = 12;
+
+/// A fingerprint of the memory layout of every type that crosses the ABI
+///
+/// This catches changes to the types below that were not accompanied by a bump
+/// of the [`ABI_VERSION`]. The sensors and guidance cross the ABI as flat
+/// buffers of channels, which are checked by name when the harness is loaded
+pub const LAYOUT_FINGERPRINT: u64 = layout_fingerprint(&[
+    size_of::<GuideOutcome>(),
+    align_of::<GuideOutcome>(),
+    size_of::<AbortCause>(),
+    align_of::<AbortCause>(),
+    size_of::<SignalLayout>(),
+    align_of::<SignalLayout>(),
+    size_of::<FfiStr>(),
+    align_of::<FfiStr>(),
+    size_of::<PanicEvent>(),
+    align_of::<PanicEvent>(),
+    size_of::<ChannelEvent>(),
+    align_of::<ChannelEvent>(),
+    size_of::<TelemetryEvent>(),
+    align_of::<TelemetryEvent>(),
+    size_of::<CommandEvent>(),
+    align_of::<CommandEvent>(),
+    size_of::<CommandResponse>(),
+    align_of::<CommandResponse>(),
+    size_of::<RestoreOutcome>(),
+    align_of::<RestoreOutcome>(),
+]);
+
+/// Fold the given sizes and alignments into a single value using FNV-1a
+const fn layout_fingerprint(layout: &[usize]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    let mut i = 0;
+    while i < layout.len() {
+        hash ^= layout[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+
+        i += 1;
+    }
+
+    hash
+}
+
+/// Description of the ABI that an avionics harness was built against
+///
+/// The harness exports this under the `__PREFLIGHT_ABI` symbol so that the runner
+/// can refuse to load avionics built against an incompatible version of preflight.
+/// **The layout of this structure must never change.**
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiHeader {
+    /// The [`ABI_VERSION`] the harness was built with
+    pub version: u32,
+    /// The [`LAYOUT_FINGERPRINT`] the harness was built with
+    pub layout_fingerprint: u64,
+}
+
+impl AbiHeader {
+    /// The header describing the ABI of this version of preflight
+    pub const CURRENT: Self = Self {
+        version: ABI_VERSION,
+        layout_fingerprint: LAYOUT_FINGERPRINT,
+    };
+}
+
+/// The number of channels in the sensors and guidance of the avionics
+///
+/// The harness exports this under the `__PREFLIGHT_SIGNALS` symbol so that the
+/// runner knows how large of buffers to pass to [`AvionicsGuide`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalLayout {
+    /// [`Signals::CHANNELS`] of [`Avionics::Sensors`]
+    pub sensors: usize,
+    /// [`Signals::CHANNELS`] of [`Avionics::Guidance`]
+    pub guidance: usize,
+}
+
+/// The kind of [`Control`] signal produced by the avionics, with the guidance
+/// itself written into the guidance buffer
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuideOutcome {
+    /// See [`Control::Guidance`]
+    Guidance,
+    /// See [`Control::ABORT`]
+    Abort(AbortCause),
+    /// See [`Control::RecoverableFailure`]
+    RecoverableFailure,
+}
+
+/// A borrowed UTF-8 string that can be passed across the ABI
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiStr<'a> {
+    ptr: *const u8,
+    len: usize,
+    _str: PhantomData<&'a str>,
+}
+
+impl<'a> FfiStr<'a> {
+    /// Borrow the given string
+    pub fn new(string: &'a str) -> Self {
+        Self {
+            ptr: string.as_ptr(),
+            len: string.len(),
+            _str: PhantomData,
+        }
+    }
+
+    /// Pointer to the start of the UTF-8 bytes of the string
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    /// Length of the string in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// If the string has a length of zero
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The string with the given parts, which must be those of a string that
+    /// stays borrowed for `'a`
+    pub(crate) fn from_parts(ptr: *const u8, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            _str: PhantomData,
+        }
+    }
+}
+
+/// A piece of a panic report streamed to the [`PanicCallback`]
+///
+/// Since the avionics are unable to allocate, the report is sent as a series of
+/// string fragments followed by [`PanicEvent::Finished`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum PanicEvent<'a> {
+    /// A fragment of the panic message and its location
+    Info(FfiStr<'a>),
+    /// A fragment of the pretty printed debug representation of the avionics
+    AvionicsState(FfiStr<'a>),
+    /// The report has been completely sent
+    Finished,
+}
 
 /// Callback that is called when the driven avionics panics.
 ///
 /// This callback exists to give the user more information about the state of
 /// their avionics at the site of the panic. Panics in avionics should be avoided
 /// at all costs, since there is no way to safely recover from them
-pub type PanicCallback = fn(panic_info: &PanicInfo, avionics_state: &dyn Avionics);
+pub type PanicCallback = extern "C" fn(event: PanicEvent<'_>);
 
 /// Function exposed to the preflight runner that can set the [`PanicCallback`]
-pub type SetPanicCallback = fn(callback: PanicCallback);
+pub type SetPanicCallback = extern "C" fn(callback: PanicCallback);
+
+/// Callback that is given each message logged by the avionics, as its format
+/// string along with the frame it was encoded into (see [`log`](crate::log))
+pub type LogCallback = extern "C" fn(format: FfiStr<'_>, frame: *const u8, len: usize);
+
+/// Function exposed to the preflight runner that passes every message that the
+/// avionics have logged since it was last called to the [`LogCallback`]
+pub type AvionicsLogs = extern "C" fn(callback: LogCallback);
+
+/// A piece of the name of a channel streamed to the [`ChannelCallback`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum ChannelEvent<'a> {
+    /// A fragment of the name of the channel
+    Name(FfiStr<'a>),
+    /// The name of the channel has been completely sent
+    Finished,
+}
+
+/// Callback that is given the name of each channel of the sensors or guidance,
+/// or of each parameter
+pub type ChannelCallback = extern "C" fn(event: ChannelEvent<'_>);
+
+/// Function exposed to the preflight runner that streams the name of every
+/// channel of the sensors or guidance to the [`ChannelCallback`]
+pub type AvionicsChannels = extern "C" fn(callback: ChannelCallback);
+
+/// Function exposed to the preflight runner that streams the name of every
+/// parameter of the avionics to the [`ChannelCallback`], in the order of their
+/// indices
+pub type AvionicsParameterNames = extern "C" fn(callback: ChannelCallback);
+
+/// Function exposed to the preflight runner that reads the parameter at the
+/// given index into `value`, returning if there is one
+pub type AvionicsGetParameter = extern "C" fn(index: usize, value: &mut f64) -> bool;
+
+/// Function exposed to the preflight runner that sets the parameter at the
+/// given index, returning if there is one
+pub type AvionicsSetParameter = extern "C" fn(index: usize, value: f64) -> bool;
+
+/// A value published by the avionics, streamed to the [`TelemetryCallback`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum TelemetryEvent<'a> {
+    /// See [`TelemetryValue::Scalar`]
+    Scalar(FfiStr<'a>, f64),
+    /// See [`TelemetryValue::Vector`]
+    Vector(FfiStr<'a>, [f64; 3]),
+}
+
+/// Callback that is given each value published by the avionics
+pub type TelemetryCallback = extern "C" fn(event: TelemetryEvent<'_>);
+
+/// Function exposed to the preflight runner that streams every value published
+/// by [`Avionics::telemetry`](crate::Avionics::telemetry) to the [`TelemetryCallback`]
+pub type AvionicsTelemetry = extern "C" fn(callback: TelemetryCallback);
+
+/// A [`Command`] sent to the avionics across the ABI
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum CommandEvent<'a> {
+    /// See [`Command::Arm`]
+    Arm,
+    /// See [`Command::Disarm`]
+    Disarm,
+    /// See [`Command::Abort`]
+    Abort,
+    /// See [`Command::SetParameter`]
+    SetParameter(FfiStr<'a>, f64),
+    /// See [`Command::EnterTestMode`]
+    EnterTestMode,
+}
+
+impl<'a> From<&Command<'a>> for CommandEvent<'a> {
+    fn from(command: &Command<'a>) -> Self {
+        match *command {
+            Command::Arm => Self::Arm,
+            Command::Disarm => Self::Disarm,
+            Command::Abort => Self::Abort,
+            Command::SetParameter { name, value } => Self::SetParameter(FfiStr::new(name), value),
+            Command::EnterTestMode => Self::EnterTestMode,
+        }
+    }
+}
+
+/// Function exposed to the preflight runner that sends a command to the
+/// avionics, see [`Avionics::command`](crate::Avionics::command)
+pub type AvionicsCommand = extern "C" fn(command: CommandEvent<'_>) -> CommandResponse;
+
+/// Callback that is given each piece of a snapshot of the avionics, in order
+pub type SnapshotCallback = extern "C" fn(bytes: *const u8, len: usize);
+
+/// Function exposed to the preflight runner that streams a snapshot of the
+/// avionics to the [`SnapshotCallback`], returning if they are able to be
+/// snapshotted, see [`Avionics::snapshot`](crate::Avionics::snapshot)
+pub type AvionicsSave = extern "C" fn(callback: SnapshotCallback) -> bool;
+
+/// The result of restoring a snapshot of the avionics
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreOutcome {
+    /// The avionics are back in the state the snapshot was taken in
+    Restored,
+    /// The snapshot could not be restored, leaving the avionics in an unknown
+    /// state until they are reset
+    Failed(SnapshotError),
+}
+
+/// Function exposed to the preflight runner that restores the avionics from a
+/// snapshot taken by [`AvionicsSave`]
+pub type AvionicsRestore = extern "C" fn(snapshot: *const u8, len: usize) -> RestoreOutcome;
+
+/// Function exposed to the preflight runner that streams the persistent storage
+/// of the avionics to the [`SnapshotCallback`], returning if they have any, see
+/// [`Avionics::persistent`](crate::Avionics::persistent)
+pub type AvionicsSavePersistent = extern "C" fn(callback: SnapshotCallback) -> bool;
+
+/// Function exposed to the preflight runner that restores the persistent storage
+/// of the avionics saved by [`AvionicsSavePersistent`]
+pub type AvionicsRestorePersistent =
+    extern "C" fn(persistent: *const u8, len: usize) -> RestoreOutcome;
+
+/// Function exposed to the preflight runner that tears down the avionics and
+/// creates them anew from the `default` of the harness, as when the flight
+/// computer resets
+pub type AvionicsRecreate = extern "C" fn();
+
+/// Lifecycle hook called when the flight computer boots, see [`Avionics::init`](crate::Avionics::init)
+pub type AvionicsInit = extern "C" fn();
 
 /// The entry point into the user's avionics
-pub type AvionicsGuide = fn(sensors: &Sensors) -> Control;
+///
+/// The sensors are read from, and the guidance written to, buffers of the lengths
+/// given by the [`SignalLayout`]
+pub type AvionicsGuide = extern "C" fn(sensors: *const f64, guidance: *mut f64) -> GuideOutcome;
+
+/// Lifecycle hook called after an abort, see [`Avionics::abort`](crate::Avionics::abort)
+pub type AvionicsAbort = extern "C" fn(cause: &AbortCause);
+
+/// Lifecycle hook called on a warm reset, see [`Avionics::reset`](crate::Avionics::reset)
+pub type AvionicsReset = extern "C" fn();
+
+/// Lifecycle hook called before power off, see [`Avionics::shutdown`](crate::Avionics::shutdown)
+pub type AvionicsShutdown = extern "C" fn();
+
+/// Run the avionics' guidance on the flattened sensors, flattening the guidance
+/// that they produce into the guidance buffer
+pub fn guide<A: Avionics>(avionics: &mut A, sensors: &[f64], guidance: &mut [f64]) -> GuideOutcome {
+    match avionics.guide(&A::Sensors::read(sensors)) {
+        Control::Guidance(signal) => {
+            signal.write(guidance);
+
+            GuideOutcome::Guidance
+        }
+        Control::ABORT(cause) => GuideOutcome::Abort(cause),
+        Control::RecoverableFailure => GuideOutcome::RecoverableFailure,
+    }
+}
+
+/// Pass the command sent across the ABI on to the avionics, with `borrow`
+/// turning each string of the command back into a `&str`
+pub fn command<'a, A: Avionics>(
+    avionics: &mut A,
+    command: CommandEvent<'a>,
+    borrow: fn(FfiStr<'a>) -> &'a str,
+) -> CommandResponse {
+    avionics.command(&match command {
+        CommandEvent::Arm => Command::Arm,
+        CommandEvent::Disarm => Command::Disarm,
+        CommandEvent::Abort => Command::Abort,
+        CommandEvent::SetParameter(name, value) => Command::SetParameter {
+            name: borrow(name),
+            value,
+        },
+        CommandEvent::EnterTestMode => Command::EnterTestMode,
+    })
+}
+
+/// Stream the name of every channel of the given signals to the callback
+pub fn report_channels<S: Signals>(callback: ChannelCallback) {
+    S::channels(None, &mut |name| report_name(callback, name));
+}
+
+/// Stream the name of every parameter of the avionics to the callback
+pub fn report_parameters<A: Avionics>(callback: ChannelCallback) {
+    A::Parameters::names(None, &mut |name| report_name(callback, name));
+}
+
+/// Stream a single name to the callback
+fn report_name(callback: ChannelCallback, name: &ChannelName<'_>) {
+    // Errors can not be reported across the ABI, and writing never fails anyways
+    let _ = write!(ChannelNameWriter { callback }, "{}", name);
+
+    callback(ChannelEvent::Finished);
+}
+
+/// Read the parameter of the avionics at the given index
+pub fn get_parameter<A: Avionics>(avionics: &mut A, index: usize, value: &mut f64) -> bool {
+    match avionics.parameters().and_then(|params| params.get(index)) {
+        Some(parameter) => {
+            *value = parameter;
+
+            true
+        }
+        None => false,
+    }
+}
+
+/// Set the parameter of the avionics at the given index
+pub fn set_parameter<A: Avionics>(avionics: &mut A, index: usize, value: f64) -> bool {
+    match avionics.parameters() {
+        Some(params) => params.set(index, value),
+        None => false,
+    }
+}
+
+/// Stream a snapshot of the avionics to the callback
+pub fn save_snapshot<A: Avionics>(avionics: &mut A, callback: SnapshotCallback) -> bool {
+    save(avionics.snapshot(), callback)
+}
+
+/// Restore the avionics from a snapshot
+pub fn restore_snapshot<A: Avionics>(avionics: &mut A, bytes: &[u8]) -> RestoreOutcome {
+    restore(avionics.snapshot(), bytes)
+}
+
+/// Stream the persistent storage of the avionics to the callback
+pub fn save_persistent<A: Avionics>(avionics: &mut A, callback: SnapshotCallback) -> bool {
+    save(avionics.persistent(), callback)
+}
+
+/// Restore the persistent storage of the avionics
+pub fn restore_persistent<A: Avionics>(avionics: &mut A, bytes: &[u8]) -> RestoreOutcome {
+    restore(avionics.persistent(), bytes)
+}
+
+/// Stream the state to the callback, if there is any
+fn save(state: Option<&mut dyn Snapshot>, callback: SnapshotCallback) -> bool {
+    match state {
+        Some(state) => {
+            state.save(&mut SnapshotWriter::new(&mut |bytes| {
+                callback(bytes.as_ptr(), bytes.len())
+            }));
+
+            true
+        }
+        None => false,
+    }
+}
+
+/// Restore the state from the bytes, if there is any
+fn restore(state: Option<&mut dyn Snapshot>, bytes: &[u8]) -> RestoreOutcome {
+    let restored = match state {
+        Some(state) => snapshot::restore(state, bytes),
+        None => Err(SnapshotError::Unsupported),
+    };
+
+    match restored {
+        Ok(()) => RestoreOutcome::Restored,
+        Err(err) => RestoreOutcome::Failed(err),
+    }
+}
+
+/// Adapter to send formatted text through the [`ChannelCallback`]
+struct ChannelNameWriter {
+    callback: ChannelCallback,
+}
+
+impl Write for ChannelNameWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        (self.callback)(ChannelEvent::Name(FfiStr::new(s)));
+
+        Ok(())
+    }
+}
+
+/// Stream every value that the avionics publish to the callback
+pub fn report_telemetry<A: Avionics>(avionics: &A, callback: TelemetryCallback) {
+    avionics.telemetry(&mut Telemetry::new(&mut |name, value| {
+        let name = FfiStr::new(name);
+
+        callback(match value {
+            TelemetryValue::Scalar(value) => TelemetryEvent::Scalar(name, value),
+            TelemetryValue::Vector(value) => TelemetryEvent::Vector(name, value),
+        })
+    }));
+}
+
+/// Send every message that the avionics have logged since this was last called
+/// to the callback, in the order that they were logged
+pub fn report_logs(callback: LogCallback) {
+    crate::log::drain(|(ptr, len), frame| {
+        // The queue only ever holds the parts of `'static` format strings
+        callback(FfiStr::from_parts(ptr, len), frame.as_ptr(), frame.len())
+    })
+}
+
+/// Pass every message that the avionics have logged since this was last called
+/// to the `logger` of the flight hardware, in the order that they were logged
+pub fn pass_logs(mut logger: impl FnMut(&[u8])) {
+    crate::log::drain(|_, frame| logger(frame))
+}
+
+/// Stream a report of the panic and the avionics state at the time of the panic
+/// to the given callback
+pub fn report_panic(callback: PanicCallback, panic_info: &PanicInfo, avionics: &dyn Debug) {
+    // Errors can not be reported from within the panic handler, so they are ignored
+    let _ = write!(
+        PanicEventWriter {
+            callback,
+            event: |fragment| PanicEvent::Info(fragment),
+        },
+        "{}",
+        panic_info
+    );
+    let _ = write!(
+        PanicEventWriter {
+            callback,
+            event: |fragment| PanicEvent::AvionicsState(fragment),
+        },
+        "{:#?}",
+        avionics
+    );
+
+    callback(PanicEvent::Finished);
+}
+
+/// Adapter to send formatted text through the [`PanicCallback`]
+struct PanicEventWriter {
+    callback: PanicCallback,
+    event: for<'a> fn(FfiStr<'a>) -> PanicEvent<'a>,
+}
+
+impl Write for PanicEventWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        (self.callback)((self.event)(FfiStr::new(s)));
+
+        Ok(())
+    }
+}
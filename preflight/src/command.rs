@@ -0,0 +1,45 @@
+//! Commands sent up to the avionics from the ground
+//!
+//! Between calls to [`Avionics::guide`](crate::Avionics::guide), the flight
+//! computer passes each command that it has received from the ground station to
+//! [`Avionics::command`](crate::Avionics::command). The preflight runner sends
+//! the commands scheduled by a scenario in the same way, so that the avionics'
+//! handling of them, such as a range safety abort, can be tested.
+
+/// A command from the ground
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command<'a> {
+    /// Get ready for launch
+    Arm,
+    /// Stand down from launch
+    Disarm,
+    /// Abort the flight, as commanded by range safety
+    ///
+    /// The avionics are expected to return [`Control::ABORT`](crate::Control::ABORT)
+    /// with [`AbortCause::RangeSafety`](crate::AbortCause::RangeSafety) from the
+    /// next call to [`Avionics::guide`](crate::Avionics::guide)
+    Abort,
+    /// Set the parameter with the given name to the given value
+    SetParameter {
+        /// Name of the parameter
+        name: &'a str,
+        /// The new value of the parameter
+        value: f64,
+    },
+    /// Enter the test mode of the avionics, for checking out the vehicle on the
+    /// ground
+    EnterTestMode,
+}
+
+/// How the avionics responded to a [`Command`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandResponse {
+    /// The command was carried out
+    Accepted,
+    /// The command can not be carried out right now, such as arming a vehicle
+    /// that is already flying
+    Rejected,
+    /// The avionics do not handle this command
+    Unsupported,
+}
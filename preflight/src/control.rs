@@ -0,0 +1,274 @@
+//! Building blocks for the control loops of the avionics
+//!
+//! The [`Pid`] controller works in [`uom`] quantities, so the gains of a loop
+//! that turns an angle error into a gimbal deflection cannot be handed an angular
+//! velocity by mistake. Like the filters of [`estimation`](crate::estimation), it
+//! is `no_std` and never allocates.
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::Add,
+};
+
+use uom::si::{
+    f32::{Frequency, Time},
+    frequency::hertz,
+    time::second,
+    Dimension,
+};
+
+use crate::{vector::quantity, Quantity, Snapshot};
+
+/// How a [`Pid`] keeps its integral term from winding up while the output is
+/// saturated
+///
+/// Without either, the integral keeps growing while the gimbal sits against its
+/// limit, and the vehicle overshoots once the error finally changes sign.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AntiWindup {
+    /// Stop integrating while the output is saturated and the error would push
+    /// it further past the limit
+    Clamping,
+    /// Bleed the integral off at the given rate, in proportion to how far the
+    /// output is past the limit
+    BackCalculation(Frequency),
+}
+
+/// A proportional, integral, derivative controller that turns the error of a
+/// quantity of dimension `E` into an output of dimension `O`
+///
+/// The integral and derivative gains are given relative to the proportional gain,
+/// as a rate of integration and a derivative time, which keeps them in the same
+/// units whatever the dimensions of the loop. The derivative is taken of the
+/// measurement rather than the error, so that a step of the setpoint does not
+/// kick the output, and is passed through a first order low pass filter.
+#[derive(Snapshot)]
+pub struct Pid<E: Dimension + ?Sized, O: Dimension + ?Sized> {
+    /// Gain of the error, in the base SI units of the output per those of the error
+    proportional: f32,
+    /// Rate of integration in 1/s
+    integral: f32,
+    /// Derivative time in s
+    derivative: f32,
+    /// Time constant of the derivative filter in s
+    filter: f32,
+    /// Smallest and largest output
+    limits: (f32, f32),
+    anti_windup: AntiWindup,
+    /// The integral term of the output
+    accumulated: f32,
+    /// The filtered derivative term of the output
+    damping: f32,
+    /// The time and measurement of the previous update
+    last: Option<(f32, f32)>,
+    dimensions: PhantomData<(Quantity<E>, Quantity<O>)>,
+}
+
+impl<E: Dimension + ?Sized, O: Dimension + ?Sized> Pid<E, O> {
+    /// A controller that outputs the error times the `proportional` gain, plus
+    /// its integral at the `integral` rate and its derivative over the
+    /// `derivative` time, each times the `proportional` gain
+    ///
+    /// The gain times the error has to have the dimension of the output, though
+    /// not its kind, so an angle can be the output of a ratio times an angle.
+    /// The output is unlimited and its derivative unfiltered until
+    /// [`Pid::with_limits`] and [`Pid::with_derivative_filter`] are used.
+    pub fn new<G>(proportional: Quantity<G>, integral: Frequency, derivative: Time) -> Self
+    where
+        G: Dimension + ?Sized,
+        G::L: Add<E::L, Output = O::L>,
+        G::M: Add<E::M, Output = O::M>,
+        G::T: Add<E::T, Output = O::T>,
+        G::I: Add<E::I, Output = O::I>,
+        G::Th: Add<E::Th, Output = O::Th>,
+        G::N: Add<E::N, Output = O::N>,
+        G::J: Add<E::J, Output = O::J>,
+    {
+        Self {
+            proportional: proportional.value,
+            integral: integral.get::<hertz>(),
+            derivative: derivative.get::<second>(),
+            filter: 0.0,
+            limits: (f32::NEG_INFINITY, f32::INFINITY),
+            anti_windup: AntiWindup::Clamping,
+            accumulated: 0.0,
+            damping: 0.0,
+            last: None,
+            dimensions: PhantomData,
+        }
+    }
+
+    /// Change the gains of the controller, such as when they are tuned in flight,
+    /// without forgetting its integral or previous measurement
+    pub fn set_gains<G>(&mut self, proportional: Quantity<G>, integral: Frequency, derivative: Time)
+    where
+        G: Dimension + ?Sized,
+        G::L: Add<E::L, Output = O::L>,
+        G::M: Add<E::M, Output = O::M>,
+        G::T: Add<E::T, Output = O::T>,
+        G::I: Add<E::I, Output = O::I>,
+        G::Th: Add<E::Th, Output = O::Th>,
+        G::N: Add<E::N, Output = O::N>,
+        G::J: Add<E::J, Output = O::J>,
+    {
+        self.proportional = proportional.value;
+        self.integral = integral.get::<hertz>();
+        self.derivative = derivative.get::<second>();
+    }
+
+    /// Saturate the output between `min` and `max`, such as the range of motion
+    /// of a thrust vectoring gimbal
+    pub fn with_limits(mut self, min: Quantity<O>, max: Quantity<O>) -> Self {
+        debug_assert!(min.value <= max.value, "the output limits are reversed");
+
+        self.limits = (min.value, max.value);
+        self
+    }
+
+    /// Filter the derivative term with the given time constant, which should be
+    /// a fraction of the derivative time to keep sensor noise out of the output
+    pub fn with_derivative_filter(mut self, time_constant: Time) -> Self {
+        self.filter = time_constant.get::<second>();
+        self
+    }
+
+    /// Keep the integral from winding up in the given way, rather than by
+    /// [`AntiWindup::Clamping`]
+    pub fn with_anti_windup(mut self, anti_windup: AntiWindup) -> Self {
+        self.anti_windup = anti_windup;
+        self
+    }
+
+    /// Produce the output that drives the `measurement` towards the `setpoint`,
+    /// given the [`Sensors::running_time`](crate::Sensors::running_time) that the
+    /// measurement was taken at
+    ///
+    /// The first update after a reset has nothing to integrate or differentiate
+    /// over, so only the proportional and existing integral terms are output. An
+    /// error that is not finite, such as from a NaN measurement, is neither
+    /// integrated nor differentiated, and only the integral term is output until
+    /// the measurement recovers, rather than driving the output to a limit.
+    pub fn update(
+        &mut self,
+        setpoint: Quantity<E>,
+        measurement: Quantity<E>,
+        time: Time,
+    ) -> Quantity<O> {
+        let (time, measurement) = (time.get::<second>(), measurement.value);
+        let error = setpoint.value - measurement;
+
+        if !error.is_finite() {
+            return quantity(self.accumulated.max(self.limits.0).min(self.limits.1));
+        }
+
+        let elapsed = self
+            .last
+            .map(|(last_time, last_measurement)| ((time - last_time).max(0.0), last_measurement));
+
+        if let Some((dt, last_measurement)) = elapsed {
+            if dt > 0.0 {
+                let rate = (measurement - last_measurement) / dt;
+                let damping = -self.proportional * self.derivative * rate;
+
+                self.damping += (damping - self.damping) * dt / (self.filter + dt);
+            }
+        }
+
+        let unsaturated = self.proportional * error + self.accumulated + self.damping;
+        let output = unsaturated.max(self.limits.0).min(self.limits.1);
+
+        if let Some((dt, _)) = elapsed {
+            let increment = self.proportional * self.integral * error * dt;
+
+            match self.anti_windup {
+                AntiWindup::Clamping => {
+                    let winding_up = (unsaturated > self.limits.1 && increment > 0.0)
+                        || (unsaturated < self.limits.0 && increment < 0.0);
+
+                    if !winding_up {
+                        self.accumulated += increment;
+                    }
+                }
+                AntiWindup::BackCalculation(rate) => {
+                    self.accumulated +=
+                        increment + rate.get::<hertz>() * (output - unsaturated) * dt;
+                }
+            }
+        }
+
+        self.last = Some((time, measurement));
+
+        quantity(output)
+    }
+
+    /// The integral term of the output
+    pub fn integral(&self) -> Quantity<O> {
+        quantity(self.accumulated)
+    }
+
+    /// Forget the integral and the previous measurement, as if the controller
+    /// had just been created
+    pub fn reset(&mut self) {
+        self.accumulated = 0.0;
+        self.damping = 0.0;
+        self.last = None;
+    }
+
+    /// Take over from whatever was producing `output` without a jump, such as
+    /// when switching from a manually commanded gimbal to the control loop
+    ///
+    /// The integral is set so that the next update with the same `setpoint` and
+    /// `measurement` outputs exactly `output`.
+    pub fn reset_bumpless(
+        &mut self,
+        output: Quantity<O>,
+        setpoint: Quantity<E>,
+        measurement: Quantity<E>,
+    ) {
+        self.reset();
+        self.accumulated = output.value - self.proportional * (setpoint.value - measurement.value);
+    }
+}
+
+impl<E: Dimension + ?Sized, O: Dimension + ?Sized> Debug for Pid<E, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pid")
+            .field("proportional", &self.proportional)
+            .field("integral", &self.integral)
+            .field("derivative", &self.derivative)
+            .field("filter", &self.filter)
+            .field("limits", &self.limits)
+            .field("anti_windup", &self.anti_windup)
+            .field("accumulated", &self.accumulated)
+            .field("damping", &self.damping)
+            .finish()
+    }
+}
+
+impl<E: Dimension + ?Sized, O: Dimension + ?Sized> Clone for Pid<E, O> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E: Dimension + ?Sized, O: Dimension + ?Sized> Copy for Pid<E, O> {}
+
+/// A controller with every gain at zero, which outputs nothing until it is
+/// given gains with [`Pid::set_gains`], or replaced by one from [`Pid::new`]
+impl<E: Dimension + ?Sized, O: Dimension + ?Sized> Default for Pid<E, O> {
+    fn default() -> Self {
+        Self {
+            proportional: 0.0,
+            integral: 0.0,
+            derivative: 0.0,
+            filter: 0.0,
+            limits: (f32::NEG_INFINITY, f32::INFINITY),
+            anti_windup: AntiWindup::Clamping,
+            accumulated: 0.0,
+            damping: 0.0,
+            last: None,
+            dimensions: PhantomData,
+        }
+    }
+}
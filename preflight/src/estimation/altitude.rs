@@ -0,0 +1,318 @@
+use uom::si::{
+    acceleration::meter_per_second_squared,
+    f32::{Acceleration, Jerk, Length, Time, Velocity},
+    jerk::meter_per_second_cubed,
+    length::meter,
+    time::second,
+    velocity::meter_per_second,
+};
+
+use super::Clock;
+use crate::{vector::sqrt, SensorHealth, Sensors, Snapshot};
+
+/// A linear Kalman filter that fuses the altimeter with the vertical acceleration
+/// into estimates of the altitude, vertical velocity and vertical acceleration
+///
+/// The vertical acceleration is found by rotating [`Sensors::linear_acceleration`]
+/// into the world frame with [`Sensors::attitude`], so it is only used while
+/// both the accelerometer and orientation are healthy.
+#[derive(Debug, Clone, Snapshot)]
+pub struct AltitudeKalman {
+    /// Altitude in m, vertical velocity in m/s and vertical acceleration in m/s^2
+    state: [f32; 3],
+    covariance: [[f32; 3]; 3],
+    /// Variance of the altimeter in m^2
+    altitude_variance: f32,
+    /// Variance of the vertical acceleration in m^2/s^4
+    acceleration_variance: f32,
+    /// Spectral density of the white jerk in m^2/s^5, rather than a variance in
+    /// m^2/s^6, since the variance that it adds to the acceleration grows with the
+    /// length of each step
+    jerk_variance: f32,
+    clock: Clock,
+}
+
+impl AltitudeKalman {
+    /// A filter for an altimeter and accelerometer with the given standard
+    /// deviations of noise, on a vehicle whose acceleration changes by about
+    /// `jerk` each second
+    ///
+    /// The square of `jerk` is taken as the spectral density of the jerk per
+    /// second, so that the acceleration wanders by a standard deviation of `jerk`
+    /// times one second over each second without a measurement.
+    pub fn new(altitude_noise: Length, acceleration_noise: Acceleration, jerk: Jerk) -> Self {
+        let altitude_variance = squared(altitude_noise.get::<meter>());
+        let acceleration_variance = squared(acceleration_noise.get::<meter_per_second_squared>());
+
+        Self {
+            state: [0.0; 3],
+            covariance: [
+                [altitude_variance, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, acceleration_variance],
+            ],
+            altitude_variance,
+            acceleration_variance,
+            jerk_variance: squared(jerk.get::<meter_per_second_cubed>()),
+            clock: Clock::default(),
+        }
+    }
+
+    /// The estimated altitude, relative to the same reference as
+    /// [`Sensors::altitude`]
+    pub fn altitude(&self) -> Length {
+        Length::new::<meter>(self.state[0])
+    }
+
+    /// The estimated vertical velocity, positive upwards
+    pub fn vertical_velocity(&self) -> Velocity {
+        Velocity::new::<meter_per_second>(self.state[1])
+    }
+
+    /// The estimated vertical acceleration, positive upwards and not including
+    /// gravity
+    pub fn vertical_acceleration(&self) -> Acceleration {
+        Acceleration::new::<meter_per_second_squared>(self.state[2])
+    }
+
+    /// The standard deviation of the estimated altitude
+    pub fn altitude_uncertainty(&self) -> Length {
+        Length::new::<meter>(sqrt(self.covariance[0][0]))
+    }
+
+    /// Stop trusting the estimate of the vertical velocity, such as when the
+    /// filter is started while the vehicle is already flying rather than at rest
+    pub fn forget_velocity(&mut self) {
+        /// Standard deviation in m/s of a vertical velocity that nothing is known about
+        const UNKNOWN_VELOCITY: f32 = 100.0;
+
+        for i in 0..3 {
+            self.covariance[1][i] = 0.0;
+            self.covariance[i][1] = 0.0;
+        }
+        self.covariance[1][1] = squared(UNKNOWN_VELOCITY);
+    }
+
+    /// Update the estimate with the latest readings, which are timestamped by
+    /// [`Sensors::running_time`]
+    pub fn update(&mut self, sensors: &Sensors) {
+        match self.clock.tick(sensors) {
+            Some(dt) => self.predict(dt),
+            // Start from the first reading of the altimeter, at rest
+            None if sensors.health.contains(SensorHealth::ALTIMETER) => {
+                self.state = [sensors.altitude.get::<meter>(), 0.0, 0.0];
+                return;
+            }
+            None => {}
+        }
+
+        if sensors.health.contains(SensorHealth::ALTIMETER) {
+            self.correct(0, sensors.altitude.get::<meter>(), self.altitude_variance);
+        }
+
+        if sensors
+            .health
+            .contains(SensorHealth::ACCELEROMETER | SensorHealth::ORIENTATION)
+        {
+            let acceleration = sensors
+                .attitude
+                .rotate(&sensors.linear_acceleration)
+                .z()
+                .get::<meter_per_second_squared>();
+
+            self.correct(2, acceleration, self.acceleration_variance);
+        }
+    }
+
+    /// Carry the estimate forward by the given number of seconds, assuming a
+    /// constant acceleration disturbed by random jerk
+    fn predict(&mut self, dt: f32) {
+        let [altitude, velocity, acceleration] = self.state;
+        self.state = [
+            altitude + velocity * dt + acceleration * dt * dt / 2.0,
+            velocity + acceleration * dt,
+            acceleration,
+        ];
+
+        let transition = [[1.0, dt, dt * dt / 2.0], [0.0, 1.0, dt], [0.0, 0.0, 1.0]];
+        let p = self.covariance;
+
+        // F P F^T
+        let mut covariance = [[0.0; 3]; 3];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                for k in 0..3 {
+                    for l in 0..3 {
+                        *value += transition[i][k] * p[k][l] * transition[j][l];
+                    }
+                }
+            }
+        }
+
+        // The process noise of white jerk integrated over the step
+        let (dt2, dt3) = (dt * dt, dt * dt * dt);
+        let q = self.jerk_variance;
+        let noise = [
+            [dt3 * dt2 / 20.0, dt2 * dt2 / 8.0, dt3 / 6.0],
+            [dt2 * dt2 / 8.0, dt3 / 3.0, dt2 / 2.0],
+            [dt3 / 6.0, dt2 / 2.0, dt],
+        ];
+        for (row, noise) in covariance.iter_mut().zip(&noise) {
+            for (value, noise) in row.iter_mut().zip(noise) {
+                *value += q * noise;
+            }
+        }
+
+        self.covariance = covariance;
+    }
+
+    /// Correct the estimate with a measurement of a single part of the state
+    fn correct(&mut self, index: usize, measurement: f32, variance: f32) {
+        let p = self.covariance;
+        let innovation = measurement - self.state[index];
+        let innovation_variance = p[index][index] + variance;
+
+        let mut gain = [0.0; 3];
+        for (gain, row) in gain.iter_mut().zip(&p) {
+            *gain = row[index] / innovation_variance;
+        }
+
+        for (state, gain) in self.state.iter_mut().zip(&gain) {
+            *state += gain * innovation;
+        }
+
+        for (i, row) in self.covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value -= gain[i] * p[index][j];
+            }
+        }
+    }
+}
+
+impl Default for AltitudeKalman {
+    fn default() -> Self {
+        Self::new(
+            Length::new::<meter>(0.5),
+            Acceleration::new::<meter_per_second_squared>(0.5),
+            Jerk::new::<meter_per_second_cubed>(10.0),
+        )
+    }
+}
+
+/// The highest point of a flight
+#[derive(Debug, Clone, Copy, PartialEq, Default, Snapshot)]
+pub struct Apogee {
+    /// Altitude at apogee, relative to the same reference as [`Sensors::altitude`]
+    pub altitude: Length,
+    /// [`Sensors::running_time`] at apogee
+    pub time: Time,
+}
+
+/// Detection and prediction of apogee from an [`AltitudeKalman`]
+///
+/// Apogee is detected once the vertical velocity stops being positive, after it
+/// has first risen past the launch velocity so that the vehicle sitting on the
+/// pad is not mistaken for one at apogee.
+#[derive(Debug, Clone, Snapshot)]
+pub struct ApogeePredictor {
+    filter: AltitudeKalman,
+    /// Vertical velocity in m/s above which the vehicle has launched
+    launch_velocity: f32,
+    launched: bool,
+    /// The highest estimated point since launch
+    highest: Option<Apogee>,
+    apogee: Option<Apogee>,
+    /// [`Sensors::running_time`] of the latest readings
+    time: Time,
+}
+
+impl ApogeePredictor {
+    /// A predictor built on the given filter, which counts the vehicle as having
+    /// launched once its vertical velocity passes `launch_velocity`
+    pub fn new(filter: AltitudeKalman, launch_velocity: Velocity) -> Self {
+        Self {
+            filter,
+            launch_velocity: launch_velocity.get::<meter_per_second>(),
+            launched: false,
+            highest: None,
+            apogee: None,
+            time: Time::new::<second>(0.0),
+        }
+    }
+
+    /// Update the filter with the latest readings, returning the apogee when
+    /// these are the readings that it is detected in
+    pub fn update(&mut self, sensors: &Sensors) -> Option<Apogee> {
+        self.filter.update(sensors);
+        self.time = sensors.running_time;
+
+        let velocity = self.filter.vertical_velocity().get::<meter_per_second>();
+        self.launched |= velocity > self.launch_velocity;
+
+        if !self.launched || self.apogee.is_some() {
+            return None;
+        }
+
+        let altitude = self.filter.altitude();
+        match self.highest {
+            Some(highest) if highest.altitude >= altitude => {}
+            _ => {
+                self.highest = Some(Apogee {
+                    altitude,
+                    time: self.time,
+                })
+            }
+        }
+
+        if velocity <= 0.0 {
+            self.apogee = self.highest;
+            return self.apogee;
+        }
+
+        None
+    }
+
+    /// The filter that the predictions are made from
+    pub fn filter(&self) -> &AltitudeKalman {
+        &self.filter
+    }
+
+    /// The apogee, once it has been detected
+    pub fn apogee(&self) -> Option<Apogee> {
+        self.apogee
+    }
+
+    /// Where the upcoming apogee will be should the current deceleration hold,
+    /// which is only known while the vehicle is rising and slowing down
+    pub fn predicted(&self) -> Option<Apogee> {
+        let velocity = self.filter.vertical_velocity().get::<meter_per_second>();
+        let acceleration = self
+            .filter
+            .vertical_acceleration()
+            .get::<meter_per_second_squared>();
+
+        if !self.launched || self.apogee.is_some() || velocity <= 0.0 || acceleration >= 0.0 {
+            return None;
+        }
+
+        let remaining = -velocity / acceleration;
+
+        Some(Apogee {
+            altitude: self.filter.altitude() + Length::new::<meter>(velocity * remaining / 2.0),
+            time: self.time + Time::new::<second>(remaining),
+        })
+    }
+}
+
+impl Default for ApogeePredictor {
+    fn default() -> Self {
+        Self::new(
+            AltitudeKalman::default(),
+            Velocity::new::<meter_per_second>(5.0),
+        )
+    }
+}
+
+fn squared(value: f32) -> f32 {
+    value * value
+}
@@ -0,0 +1,93 @@
+use uom::si::{f32::Time, time::second};
+
+use super::{field, initial_attitude, integrate, rates, up, AttitudeEstimator, Clock};
+use crate::{
+    frame::{Body, Enu, Rotation},
+    vector::sqrt,
+    Quaternion, Sensors, Snapshot,
+};
+
+/// The simplest of the filters, which integrates the gyroscope and then nudges
+/// the estimate towards the attitude that the accelerometer and magnetometer
+/// point to
+///
+/// The accelerometer only corrects the tilt of the estimate, and the magnetometer
+/// only corrects its heading.
+#[derive(Debug, Clone, Snapshot)]
+pub struct Complementary {
+    attitude: Quaternion,
+    /// Fraction of the error that is corrected each second
+    gain: f32,
+    clock: Clock,
+}
+
+impl Complementary {
+    /// A filter that corrects its error with the given time constant, trading
+    /// rejection of accelerometer and magnetometer noise for quicker correction of
+    /// gyroscope drift
+    pub fn new(time_constant: Time) -> Self {
+        Self {
+            attitude: Quaternion::IDENTITY,
+            gain: 1.0 / time_constant.get::<second>(),
+            clock: Clock::default(),
+        }
+    }
+}
+
+impl Default for Complementary {
+    fn default() -> Self {
+        Self::new(Time::new::<second>(1.0))
+    }
+}
+
+impl AttitudeEstimator for Complementary {
+    fn update(&mut self, sensors: &Sensors) {
+        let dt = match self.clock.tick(sensors) {
+            Some(dt) => dt,
+            None => {
+                self.attitude = initial_attitude(sensors);
+                return;
+            }
+        };
+
+        if let Some(rates) = rates(sensors) {
+            self.attitude = integrate(self.attitude, rates, dt);
+        }
+
+        // The small rotation, in the world frame, that would correct the error
+        let attitude = self.attitude();
+        let mut correction = [0.0; 3];
+
+        if let Some(up) = up(sensors) {
+            // The measured up crossed with the true up
+            let up = attitude.rotate(&up);
+            correction[0] += up.y().value;
+            correction[1] -= up.x().value;
+        }
+
+        if let Some(field) = field(sensors) {
+            // The horizontal part of the field crossed with north
+            let field = attitude.rotate(&field);
+            let horizontal =
+                sqrt(field.x().value * field.x().value + field.y().value * field.y().value);
+
+            if horizontal > 0.0 {
+                correction[2] += field.x().value / horizontal;
+            }
+        }
+
+        let scale = (self.gain * dt).min(1.0) / 2.0;
+        let correction = Quaternion::new(
+            1.0,
+            correction[0] * scale,
+            correction[1] * scale,
+            correction[2] * scale,
+        );
+
+        self.attitude = (correction * self.attitude).normalize();
+    }
+
+    fn attitude(&self) -> Rotation<Body, Enu> {
+        Rotation::new_unchecked(self.attitude)
+    }
+}
@@ -0,0 +1,139 @@
+use uom::si::{angular_velocity::radian_per_second, f32::AngularVelocity};
+
+use super::{
+    field, initial_attitude, rates, reference_field, up, values, AttitudeEstimator, Clock,
+};
+use crate::{
+    frame::{Body, Enu, Rotation},
+    vector::sqrt,
+    Quaternion, Sensors, Snapshot,
+};
+
+/// Madgwick's gradient descent filter, which steps the estimate down the
+/// gradient of the error between the measured and estimated directions of
+/// gravity and the magnetic field
+#[derive(Debug, Clone, Snapshot)]
+pub struct Madgwick {
+    attitude: Quaternion,
+    /// Size of each step down the gradient in rad/s
+    beta: f32,
+    clock: Clock,
+}
+
+impl Madgwick {
+    /// A filter that steps down the gradient of its error at the rate `beta`,
+    /// which should be about the error of the gyroscope
+    pub fn new(beta: AngularVelocity) -> Self {
+        Self {
+            attitude: Quaternion::IDENTITY,
+            beta: beta.get::<radian_per_second>(),
+            clock: Clock::default(),
+        }
+    }
+}
+
+impl Default for Madgwick {
+    fn default() -> Self {
+        Self::new(AngularVelocity::new::<radian_per_second>(0.1))
+    }
+}
+
+impl AttitudeEstimator for Madgwick {
+    fn update(&mut self, sensors: &Sensors) {
+        let dt = match self.clock.tick(sensors) {
+            Some(dt) => dt,
+            None => {
+                self.attitude = initial_attitude(sensors);
+                return;
+            }
+        };
+
+        let mut step = [0.0; 4];
+
+        if let Some(up) = up(sensors) {
+            add(
+                &mut step,
+                gradient(self.attitude, [0.0, 0.0, 1.0], values(&up)),
+            );
+        }
+
+        if let Some(field) = field(sensors) {
+            let reference = reference_field(self.attitude, &field);
+            add(
+                &mut step,
+                gradient(self.attitude, reference, values(&field)),
+            );
+        }
+
+        let norm = sqrt(step.iter().map(|part| part * part).sum());
+        let step = if norm > 0.0 {
+            Quaternion::new(step[0], step[1], step[2], step[3]) * (self.beta / norm)
+        } else {
+            Quaternion::new(0.0, 0.0, 0.0, 0.0)
+        };
+
+        let [x, y, z] = rates(sensors).unwrap_or([0.0; 3]);
+        let derivative = self.attitude * Quaternion::new(0.0, x, y, z) * 0.5 - step;
+
+        self.attitude = (self.attitude + derivative * dt).normalize();
+    }
+
+    fn attitude(&self) -> Rotation<Body, Enu> {
+        Rotation::new_unchecked(self.attitude)
+    }
+}
+
+/// The gradient, with respect to each part of the attitude, of the squared error
+/// between the `reference` direction in the world frame rotated into the body
+/// frame and the `measured` direction in the body frame
+fn gradient(attitude: Quaternion, reference: [f32; 3], measured: [f32; 3]) -> [f32; 4] {
+    let Quaternion { w, x, y, z } = attitude;
+    let [dx, dy, dz] = reference;
+    let m = attitude.to_rotation_matrix();
+
+    // The reference in the body frame, through the transpose of the rotation,
+    // minus the measurement
+    let error = [
+        m[0][0] * dx + m[1][0] * dy + m[2][0] * dz - measured[0],
+        m[0][1] * dx + m[1][1] * dy + m[2][1] * dz - measured[1],
+        m[0][2] * dx + m[1][2] * dy + m[2][2] * dz - measured[2],
+    ];
+
+    // The jacobian of the reference in the body frame, one row for each of w, x,
+    // y and z
+    let jacobian = [
+        [
+            2.0 * (z * dy - y * dz),
+            2.0 * (x * dz - z * dx),
+            2.0 * (y * dx - x * dy),
+        ],
+        [
+            2.0 * (y * dy + z * dz),
+            2.0 * (y * dx + w * dz) - 4.0 * x * dy,
+            2.0 * (z * dx - w * dy) - 4.0 * x * dz,
+        ],
+        [
+            2.0 * (x * dy - w * dz) - 4.0 * y * dx,
+            2.0 * (x * dx + z * dz),
+            2.0 * (w * dx + z * dy) - 4.0 * y * dz,
+        ],
+        [
+            2.0 * (w * dy + x * dz) - 4.0 * z * dx,
+            2.0 * (y * dz - w * dx) - 4.0 * z * dy,
+            2.0 * (x * dx + y * dy),
+        ],
+    ];
+
+    let mut gradient = [0.0; 4];
+    for (gradient, row) in gradient.iter_mut().zip(&jacobian) {
+        *gradient = row[0] * error[0] + row[1] * error[1] + row[2] * error[2];
+    }
+
+    gradient
+}
+
+fn add(a: &mut [f32; 4], b: [f32; 4]) {
+    for (a, b) in a.iter_mut().zip(&b) {
+        *a += b;
+    }
+}
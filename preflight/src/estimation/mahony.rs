@@ -0,0 +1,105 @@
+use uom::si::{f32::Frequency, frequency::hertz};
+
+use super::{
+    cross, field, initial_attitude, integrate, rates, reference_field, up, values,
+    AttitudeEstimator, Clock, Direction,
+};
+use crate::{
+    frame::{Body, Enu, Rotation},
+    Quaternion, Sensors, Snapshot,
+};
+
+/// Mahony's nonlinear complementary filter, which feeds the error between the
+/// measured and estimated directions of gravity and the magnetic field back into
+/// the gyroscope like a PI controller
+///
+/// Given an `integral` rate, the filter also learns the bias of the gyroscope, so
+/// the estimate drifts less once the accelerometer stops being trusted during the
+/// boost. The default filter leaves it out, since the bias that it learns while
+/// the estimate converges from a large error takes a long time to unwind again.
+#[derive(Debug, Clone, Snapshot)]
+pub struct Mahony {
+    attitude: Quaternion,
+    /// Gain of the error in 1/s
+    proportional: f32,
+    /// Gain of the accumulated error in 1/s^2
+    integral: f32,
+    /// Estimated bias of the gyroscope in rad/s, with the sign flipped
+    bias: [f32; 3],
+    clock: Clock,
+}
+
+impl Mahony {
+    /// A filter that corrects its error at the `proportional` rate, and learns the
+    /// bias of the gyroscope at the `integral` rate relative to that
+    pub fn new(proportional: Frequency, integral: Frequency) -> Self {
+        let proportional = proportional.get::<hertz>();
+
+        Self {
+            attitude: Quaternion::IDENTITY,
+            proportional,
+            integral: proportional * integral.get::<hertz>(),
+            bias: [0.0; 3],
+            clock: Clock::default(),
+        }
+    }
+}
+
+impl Default for Mahony {
+    fn default() -> Self {
+        Self::new(Frequency::new::<hertz>(2.0), Frequency::new::<hertz>(0.0))
+    }
+}
+
+impl AttitudeEstimator for Mahony {
+    fn update(&mut self, sensors: &Sensors) {
+        let dt = match self.clock.tick(sensors) {
+            Some(dt) => dt,
+            None => {
+                self.attitude = initial_attitude(sensors);
+                return;
+            }
+        };
+
+        let to_body = self.attitude().inverse();
+        let mut error = [0.0; 3];
+
+        // The measured direction crossed with the estimated direction, in the body
+        // frame, for both gravity and the magnetic field
+        if let Some(up) = up(sensors) {
+            let estimated = to_body.rotate(&Direction::from_values(0.0, 0.0, 1.0));
+            add(&mut error, cross(values(&up), values(&estimated)));
+        }
+
+        if let Some(field) = field(sensors) {
+            let [x, y, z] = reference_field(self.attitude, &field);
+            let estimated = to_body.rotate(&Direction::from_values(x, y, z));
+            add(&mut error, cross(values(&field), values(&estimated)));
+        }
+
+        // The bias belongs to the gyroscope, so it is only learned and corrected
+        // for while the gyroscope is healthy
+        let gyroscope = rates(sensors);
+        let mut corrected = gyroscope.unwrap_or([0.0; 3]);
+        for axis in 0..3 {
+            corrected[axis] += self.proportional * error[axis];
+
+            if gyroscope.is_some() {
+                self.bias[axis] += self.integral * error[axis] * dt;
+                corrected[axis] += self.bias[axis];
+            }
+        }
+
+        self.attitude = integrate(self.attitude, corrected, dt);
+    }
+
+    fn attitude(&self) -> Rotation<Body, Enu> {
+        Rotation::new_unchecked(self.attitude)
+    }
+}
+
+fn add(a: &mut [f32; 3], b: [f32; 3]) {
+    for (a, b) in a.iter_mut().zip(&b) {
+        *a += b;
+    }
+}
@@ -0,0 +1,182 @@
+//! Estimation of the state of the vehicle from the readings of [`Sensors`]
+//!
+//! Each attitude filter integrates the gyroscope, and corrects the drift of that with the
+//! accelerometer, which points up whenever the vehicle is not accelerating, and
+//! the magnetometer, which points north. They are `no_std` and never allocate, so
+//! the same filter is able to run on the flight computer and under the preflight
+//! runner, which checks them against the true attitude of the simulated vehicle
+//! in scenarios.
+//!
+//! The [`AltitudeKalman`] filter fuses the altimeter with the vertical
+//! acceleration, and the [`ApogeePredictor`] built on it detects apogee, so that
+//! every vehicle deploys its parachute on the same estimate of the flight.
+//!
+//! The readings of a sensor are only used while its [`SensorHealth`] flag is
+//! raised. While the gyroscope is unhealthy, each attitude filter holds its
+//! estimate still rather than integrating it, but keeps correcting it with the
+//! accelerometer and magnetometer.
+
+use uom::si::{
+    acceleration::meter_per_second_squared, angular_velocity::radian_per_second, f32::Time, ratio,
+    time::second,
+};
+
+use crate::{
+    frame::{Body, Enu, Rotation},
+    vector::sqrt,
+    Quaternion, SensorHealth, Sensors, Snapshot, Vector3,
+};
+
+mod altitude;
+mod complementary;
+mod madgwick;
+mod mahony;
+
+pub use altitude::{AltitudeKalman, Apogee, ApogeePredictor};
+pub use complementary::Complementary;
+pub use madgwick::Madgwick;
+pub use mahony::Mahony;
+
+/// Fraction that the magnitude of the measured acceleration may differ from
+/// gravity by for the accelerometer to be trusted to point up
+///
+/// While the motor is burning the accelerometer mostly measures thrust, which
+/// would drag the estimate towards the direction of travel
+pub const ACCELERATION_TOLERANCE: f32 = 0.1;
+
+/// Standard gravity in m/s^2
+const GRAVITY: f32 = 9.80665;
+
+/// A filter that estimates the attitude of the vehicle
+pub trait AttitudeEstimator {
+    /// Update the estimate with the latest readings, which are timestamped by
+    /// [`Sensors::running_time`]
+    ///
+    /// The first readings are used to start the estimate from the attitude that
+    /// the accelerometer and magnetometer point to, so they should be taken while
+    /// the vehicle is at rest.
+    fn update(&mut self, sensors: &Sensors);
+
+    /// The estimated rotation from the body frame into the world frame
+    fn attitude(&self) -> Rotation<Body, Enu>;
+}
+
+/// A unitless vector
+type Direction<F> = Vector3<F, ratio::Dimension>;
+
+/// The time between readings, from [`Sensors::running_time`]
+#[derive(Debug, Clone, Copy, Default, Snapshot)]
+struct Clock {
+    last: Option<Time>,
+}
+
+impl Clock {
+    /// Seconds since the last readings, or `None` for the first readings
+    fn tick(&mut self, sensors: &Sensors) -> Option<f32> {
+        let elapsed = self
+            .last
+            .map(|last| (sensors.running_time - last).get::<second>().max(0.0));
+
+        self.last = Some(sensors.running_time);
+
+        elapsed
+    }
+}
+
+/// The body rates in rad/s, if the gyroscope is healthy
+fn rates(sensors: &Sensors) -> Option<[f32; 3]> {
+    let rates = &sensors.angular_velocity;
+
+    sensors.health.contains(SensorHealth::GYROSCOPE).then(|| {
+        [
+            rates.x().get::<radian_per_second>(),
+            rates.y().get::<radian_per_second>(),
+            rates.z().get::<radian_per_second>(),
+        ]
+    })
+}
+
+/// The direction that the accelerometer reads as up, if it is healthy and the
+/// vehicle is close enough to not accelerating
+fn up(sensors: &Sensors) -> Option<Direction<Body>> {
+    let magnitude = sensors
+        .both_acceleration
+        .norm()
+        .get::<meter_per_second_squared>();
+
+    (sensors.health.contains(SensorHealth::ACCELEROMETER)
+        && (magnitude - GRAVITY).abs() <= GRAVITY * ACCELERATION_TOLERANCE)
+        .then(|| sensors.both_acceleration.normalize())
+}
+
+/// The direction of the magnetic field, if the magnetometer is healthy
+fn field(sensors: &Sensors) -> Option<Direction<Body>> {
+    (sensors.health.contains(SensorHealth::MAGNETOMETER)
+        && sensors.magnetic_field.norm().value > 0.0)
+        .then(|| sensors.magnetic_field.normalize())
+}
+
+/// The direction of the magnetic field in the world frame, with its horizontal
+/// part turned to point north, so that it is the field that the estimate should
+/// measure were its heading right
+fn reference_field(attitude: Quaternion, field: &Direction<Body>) -> [f32; 3] {
+    let field = Rotation::<Body, Enu>::new_unchecked(attitude).rotate(field);
+    let horizontal = sqrt(field.x().value * field.x().value + field.y().value * field.y().value);
+
+    [0.0, horizontal, field.z().value]
+}
+
+/// The attitude that a single reading of the accelerometer and magnetometer
+/// points to, or the identity if either of them cannot be trusted
+fn initial_attitude(sensors: &Sensors) -> Quaternion {
+    let (up, field) = match (up(sensors), field(sensors)) {
+        (Some(up), Some(field)) => (values(&up), values(&field)),
+        _ => return Quaternion::IDENTITY,
+    };
+
+    // North is what remains of the field once its vertical part is removed
+    let vertical = dot(field, up);
+    let north = normalize([
+        field[0] - up[0] * vertical,
+        field[1] - up[1] * vertical,
+        field[2] - up[2] * vertical,
+    ]);
+    let east = cross(north, up);
+
+    // The rows of the rotation from the body frame into the world frame are the
+    // world axes as seen from the body
+    Quaternion::from_rotation_matrix(&[east, north, up])
+}
+
+/// Integrate the body rates in rad/s over the given number of seconds
+fn integrate(attitude: Quaternion, rates: [f32; 3], dt: f32) -> Quaternion {
+    let derivative = attitude * Quaternion::new(0.0, rates[0], rates[1], rates[2]) * 0.5;
+
+    (attitude + derivative * dt).normalize()
+}
+
+fn values<D: uom::si::Dimension + ?Sized>(vector: &Vector3<Body, D>) -> [f32; 3] {
+    [vector.x.value, vector.y.value, vector.z.value]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let norm = sqrt(dot(a, a));
+
+    if norm == 0.0 {
+        return a;
+    }
+
+    [a[0] / norm, a[1] / norm, a[2] / norm]
+}
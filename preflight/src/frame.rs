@@ -0,0 +1,147 @@
+//! Frames of reference that vectors are expressed in
+//!
+//! Every [`Vector3`] carries the frame that its axes belong to, so adding a body
+//! acceleration to a NED acceleration is a compile error rather than a lost
+//! vehicle. Vectors are moved between frames with a [`Rotation`], or for the
+//! fixed relationship between NED and ENU, with [`Vector3::to_enu`] and
+//! [`Vector3::to_ned`].
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::Mul,
+};
+
+use uom::si::Dimension;
+
+use crate::{Quaternion, Vector3};
+
+/// A frame of reference
+pub trait Frame {
+    /// A short name for the frame, used when formatting
+    const NAME: &'static str;
+}
+
+/// The frame fixed to the vehicle, with y along the axis of the vehicle towards
+/// its nose, and x and z along the axes that the thrust vector is gimballed about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Body {}
+
+/// The local tangent frame with x pointing north, y east and z down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ned {}
+
+/// The local tangent frame with x pointing east, y north and z up, which is the
+/// world frame of the preflight simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enu {}
+
+impl Frame for Body {
+    const NAME: &'static str = "body";
+}
+
+impl Frame for Ned {
+    const NAME: &'static str = "NED";
+}
+
+impl Frame for Enu {
+    const NAME: &'static str = "ENU";
+}
+
+/// A rotation that takes vectors from the `From` frame into the `To` frame
+#[repr(transparent)]
+pub struct Rotation<From: Frame, To: Frame> {
+    quaternion: Quaternion,
+    frames: PhantomData<(From, To)>,
+}
+
+impl<From: Frame, To: Frame> Rotation<From, To> {
+    /// The rotation described by the given quaternion, which is normalized
+    pub fn new(quaternion: Quaternion) -> Self {
+        Self {
+            quaternion: quaternion.normalize(),
+            frames: PhantomData,
+        }
+    }
+
+    /// The rotation described by the given quaternion, which is trusted to
+    /// already be normalized
+    pub const fn new_unchecked(quaternion: Quaternion) -> Self {
+        Self {
+            quaternion,
+            frames: PhantomData,
+        }
+    }
+
+    /// The quaternion describing the rotation
+    pub fn quaternion(&self) -> Quaternion {
+        self.quaternion
+    }
+
+    /// Express the given vector in the `To` frame
+    pub fn rotate<D: Dimension + ?Sized>(&self, vector: &Vector3<From, D>) -> Vector3<To, D> {
+        let rotated = self.quaternion.rotate(vector);
+
+        Vector3::from_values(rotated.x.value, rotated.y.value, rotated.z.value)
+    }
+
+    /// The rotation from the `To` frame back into the `From` frame
+    pub fn inverse(&self) -> Rotation<To, From> {
+        Rotation::new_unchecked(self.quaternion.inverse())
+    }
+}
+
+impl<From: Frame, To: Frame> Debug for Rotation<From, To> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Rotation<{}, {}>({:?})",
+            From::NAME,
+            To::NAME,
+            self.quaternion
+        )
+    }
+}
+
+impl<From: Frame, To: Frame> Clone for Rotation<From, To> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<From: Frame, To: Frame> Copy for Rotation<From, To> {}
+
+impl<From: Frame, To: Frame> PartialEq for Rotation<From, To> {
+    fn eq(&self, other: &Self) -> bool {
+        self.quaternion == other.quaternion
+    }
+}
+
+impl<From: Frame> Default for Rotation<From, From> {
+    fn default() -> Self {
+        Self::new_unchecked(Quaternion::IDENTITY)
+    }
+}
+
+impl<From: Frame, Via: Frame, To: Frame> Mul<Rotation<From, Via>> for Rotation<Via, To> {
+    type Output = Rotation<From, To>;
+
+    /// Chain two rotations, with `rhs` being applied first
+    fn mul(self, rhs: Rotation<From, Via>) -> Rotation<From, To> {
+        Rotation::new_unchecked(self.quaternion * rhs.quaternion)
+    }
+}
+
+impl<D: Dimension + ?Sized> Vector3<Ned, D> {
+    /// Express the vector in east, north, up axes
+    pub fn to_enu(&self) -> Vector3<Enu, D> {
+        Vector3::from_values(self.y.value, self.x.value, -self.z.value)
+    }
+}
+
+impl<D: Dimension + ?Sized> Vector3<Enu, D> {
+    /// Express the vector in north, east, down axes
+    pub fn to_ned(&self) -> Vector3<Ned, D> {
+        Vector3::from_values(self.y.value, self.x.value, -self.z.value)
+    }
+}
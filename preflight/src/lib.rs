@@ -12,101 +12,219 @@
 
 use core::{
     fmt::{self, Debug, Formatter},
-    marker::PhantomData,
+    ops::{BitOr, BitOrAssign},
 };
 
+use frame::{Body, Enu, Ned, Rotation};
 use uom::si::{
-    acceleration, angle, angular_velocity, length, magnetic_flux_density, time, Dimension, SI,
+    acceleration, angle, angular_velocity, length, magnetic_flux_density, time, velocity, SI,
 };
 
+// Allow the derive macros to refer to this crate as `preflight` from within it
+extern crate self as preflight;
+
+pub use command::{Command, CommandResponse};
 pub use micromath;
-pub use preflight_macros::avionics_harness;
+pub use params::Parameters;
+pub use phase::FlightPhase;
+pub use preflight_macros::{avionics_harness, Parameters, Signals, Snapshot};
+pub use quaternion::{Quaternion, RotationMatrix};
+pub use signals::{ChannelName, Signals};
+pub use snapshot::Snapshot;
+pub use telemetry::Telemetry;
 pub use uom; // FIXME: libcore math kinda lackin: https://github.com/rust-lang/rfcs/issues/2505 https://github.com/rust-lang/rust/issues/50145
+pub use vector::Vector3;
 
 pub mod abi;
+pub mod command;
+pub mod control;
+pub mod estimation;
+pub mod frame;
+pub mod log;
+pub mod params;
+pub mod phase;
+mod quaternion;
+pub mod signals;
+pub mod snapshot;
+pub mod telemetry;
+mod vector;
 
 /// Generic [`uom`] quantity using f32 as the storage type
 pub type Quantity<T> = uom::si::Quantity<T, SI<f32>, f32>;
 
+/// Generic [`uom`] quantity using f64 as the storage type, for the few values
+/// that f32 is too coarse for, such as a latitude to within a meter
+pub type PreciseQuantity<T> = uom::si::Quantity<T, SI<f64>, f64>;
+
 /// Generic sensor values that are collected from the flight hardware which would
 /// be useful to calculate position and velocity.
+///
+/// These are the sensors that the preflight runner simulates. Avionics with
+/// other sensors can use their own [`Avionics::Sensors`], any channels of which
+/// share a name with these will be filled by the runner.
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Signals)]
 pub struct Sensors {
-    // TODO: MAKE THESE MORE GENERIC
     // TODO: LESS JANK VECTORS https://github.com/iliekturtles/uom/issues/231
+    /// Which of the sensors below are producing valid readings
+    pub health: SensorHealth,
     /// Calculated altitude
     pub altitude: Quantity<length::Dimension>,
     /// Three axis of linear acceleration data (acceleration minus gravity) in m/s^2
-    pub linear_acceleration: Vector3<acceleration::Dimension>,
+    pub linear_acceleration: Vector3<Body, acceleration::Dimension>,
     /// Three axis of gravitational acceleration (minus any movement) in m/s^2
-    pub gravity_acceleration: Vector3<acceleration::Dimension>,
+    pub gravity_acceleration: Vector3<Body, acceleration::Dimension>,
     /// Three axis of acceleration (gravity + linear motion) in m/s^2
-    pub both_acceleration: Vector3<acceleration::Dimension>,
+    pub both_acceleration: Vector3<Body, acceleration::Dimension>,
     /// Three axis orientation data based on a 360° sphere
-    pub orientation: Vector3<angle::Dimension>,
+    ///
+    /// These Euler angles, about the fixed axes of the world frame, are unable to
+    /// describe a vehicle pointing straight up, so prefer [`Sensors::attitude`]
+    pub orientation: Vector3<Enu, angle::Dimension>,
+    /// The rotation from the body frame of the vehicle into the world frame
+    pub attitude: Rotation<Body, Enu>,
     /// Three axis of 'rotation speed' in rad/s
-    pub angular_velocity: Vector3<angular_velocity::Dimension>,
+    pub angular_velocity: Vector3<Body, angular_velocity::Dimension>,
     /// Three axis of magnetic field sensing in micro Tesla (uT)
-    pub magnetic_field: Vector3<magnetic_flux_density::Dimension>,
+    pub magnetic_field: Vector3<Body, magnetic_flux_density::Dimension>,
+    /// The most recent position and velocity fix from the GNSS receiver
+    pub gnss: Gnss,
     /// The running time of the flight computer, from the moment the avionics have started up
     pub running_time: Quantity<time::Dimension>,
 }
 
-/// A vector representing a quantity in 3 dimensional space
-#[repr(C)]
-pub struct Vector3<T: Dimension + ?Sized> {
-    x: Quantity<T>,
-    y: Quantity<T>,
-    z: Quantity<T>,
+/// Which of the sensors are producing valid readings
+///
+/// The readings of a sensor whose flag is not raised must not be trusted, since
+/// the sensor has failed or has not yet started up. Firmware should raise each
+/// flag from the status reported by the sensor's driver, so zeroed health means
+/// that no sensor can be trusted
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SensorHealth(u32);
+
+impl SensorHealth {
+    /// No sensors are healthy
+    pub const NONE: Self = Self(0);
+    /// [`Sensors::altitude`]
+    pub const ALTIMETER: Self = Self(1 << 0);
+    /// [`Sensors::linear_acceleration`], [`Sensors::gravity_acceleration`] and
+    /// [`Sensors::both_acceleration`]
+    pub const ACCELEROMETER: Self = Self(1 << 1);
+    /// [`Sensors::angular_velocity`]
+    pub const GYROSCOPE: Self = Self(1 << 2);
+    /// [`Sensors::orientation`] and [`Sensors::attitude`]
+    pub const ORIENTATION: Self = Self(1 << 3);
+    /// [`Sensors::magnetic_field`]
+    pub const MAGNETOMETER: Self = Self(1 << 4);
+    /// [`Sensors::gnss`]
+    pub const GNSS: Self = Self(1 << 5);
+    /// Every sensor is healthy
+    pub const ALL: Self = Self(
+        Self::ALTIMETER.0
+            | Self::ACCELEROMETER.0
+            | Self::GYROSCOPE.0
+            | Self::ORIENTATION.0
+            | Self::MAGNETOMETER.0
+            | Self::GNSS.0,
+    );
+
+    /// Names of each of the sensors
+    const NAMED: [(Self, &'static str); 6] = [
+        (Self::ALTIMETER, "ALTIMETER"),
+        (Self::ACCELEROMETER, "ACCELEROMETER"),
+        (Self::GYROSCOPE, "GYROSCOPE"),
+        (Self::ORIENTATION, "ORIENTATION"),
+        (Self::MAGNETOMETER, "MAGNETOMETER"),
+        (Self::GNSS, "GNSS"),
+    ];
+
+    /// If every sensor in `other` is healthy in `self`
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The sensors that are healthy in `self` but not in `other`
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// The raw bits of the flags
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
 }
 
-impl<T: Dimension + ?Sized> Debug for Vector3<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        // if f.alternate() {
-
-        // }
-        // write!(f, "({}, {}, {})", self.x.value, self.y.value, self.z);
-        f.debug_struct("Vector3")
-            .field("x", &self.x) //Quantity::format_args(self.x, Abbreviation)
-            .field("y", &self.y)
-            .field("z", &self.z)
-            .finish()
+impl BitOr for SensorHealth {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for SensorHealth {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
     }
 }
 
-impl<T: Dimension + ?Sized> Vector3<T> {
-    /// Create a 3 dimensional vector from 3 quantities
-    pub fn new(x: Quantity<T>, y: Quantity<T>, z: Quantity<T>) -> Self {
-        Self { x, y, z }
-    }
-
-    /// Create a zeroed vector
-    pub fn zero() -> Self {
-        Self {
-            x: Quantity {
-                dimension: PhantomData,
-                units: PhantomData,
-                value: 0.0,
-            },
-            y: Quantity {
-                dimension: PhantomData,
-                units: PhantomData,
-                value: 0.0,
-            },
-            z: Quantity {
-                dimension: PhantomData,
-                units: PhantomData,
-                value: 0.0,
-            },
+impl Debug for SensorHealth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+
+        for (flag, name) in &Self::NAMED {
+            if self.contains(*flag) {
+                set.entry(&format_args!("{}", name));
+            }
         }
+
+        set.finish()
     }
 }
 
+/// A position and velocity fix from a GNSS receiver
+///
+/// GNSS receivers update far slower than the rest of the sensors, so this holds
+/// the most recent fix. A new fix can be told apart by its [`Gnss::time_of_week`]
+#[repr(C)]
+#[derive(Debug, Signals)]
+pub struct Gnss {
+    /// If the receiver has a fix. The rest of the fields are only meaningful
+    /// while this is true
+    pub available: bool,
+    /// The kind of fix that the receiver has
+    pub fix: GnssFix,
+    /// Number of satellites used in the fix
+    pub satellites: u8,
+    /// Latitude, positive north of the equator
+    pub latitude: PreciseQuantity<angle::Dimension>,
+    /// Longitude, positive east of the prime meridian
+    pub longitude: PreciseQuantity<angle::Dimension>,
+    /// Altitude above mean sea level
+    pub altitude: Quantity<length::Dimension>,
+    /// Velocity in the north, east, down frame
+    pub velocity: Vector3<Ned, velocity::Dimension>,
+    /// Time of the fix in milliseconds since the start of the GPS week
+    pub time_of_week: u32,
+}
+
+/// The kind of fix that a GNSS receiver has
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GnssFix {
+    /// The receiver is not tracking enough satellites to produce a position
+    NoFix = 0,
+    /// A position without an altitude
+    TwoDimensional = 2,
+    /// A position and altitude
+    ThreeDimensional = 3,
+}
+
 /// Various control signals that the avionics can produce
 #[repr(C)]
 #[derive(Debug)]
-pub enum Control {
+pub enum Control<G = Guidance> {
     /// An abort signal
     ///
     /// This will stop the system from calling the guidance avionics, and attempt
@@ -115,7 +233,7 @@ pub enum Control {
     /// A firmware agnostic guidance control signal for the underlying firmware
     /// to translate into flight hardware specific servo movements of pyro channel
     /// fires
-    Guidance(Guidance),
+    Guidance(G),
     /// A signal to the underlying flight system that the avionics was unable to
     /// compute a valid control signal for the time. The flight system will normally
     /// choose to request another guidance control immediately.
@@ -127,25 +245,45 @@ pub enum Control {
 /// This cause can signal to the abort handler what recovery mode or actions need
 /// to be taken and the severity of the abort
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AbortCause {
-    /// TODO: No abort causes exist currently
-    TODO,
+    /// The vehicle has tilted further from vertical than it can safely fly
+    ExcessiveTilt,
+    /// A sensor that is required for safe flight has failed
+    SensorFailure,
+    /// The motor is not producing the thrust that was expected of it
+    LossOfThrust,
+    /// The vehicle has left the safe flight corridor or range safety has
+    /// commanded an abort
+    RangeSafety,
+    /// The state estimate has diverged and can no longer be trusted
+    EstimatorDivergence,
+    /// A cause specific to the avionics, identified by a user defined code
+    User(u32),
 }
 
 /// A hardware agnostic guidance signal
+///
+/// This is the guidance that the preflight runner simulates. Avionics with other
+/// actuators can use their own [`Avionics::Guidance`], any channels of which
+/// share a name with these will be acted on by the runner.
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Signals)]
 pub struct Guidance {
     /// Thrust vectoring control
     pub tvc: ThrustVector,
-    // TODO: pyro/flags
+    /// Pyrotechnic channels to fire
+    pub pyro: PyroCommands,
+    /// Discrete events that the avionics have detected
+    pub flags: EventFlags,
+    /// The phase of flight that the avionics believe the vehicle is in
+    pub phase: FlightPhase,
 }
 
 /// A call for thrust vectoring hardware to produce a thrust at the given
-/// vector
+/// vector, as angles about the axes of the [`Body`] frame
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Signals)]
 pub struct ThrustVector {
     /// The thrust on the x axis
     pub x: Quantity<angle::Dimension>,
@@ -153,16 +291,267 @@ pub struct ThrustVector {
     pub z: Quantity<angle::Dimension>,
 }
 
+/// Number of pyrotechnic channels that the avionics are able to command
+pub const PYRO_CHANNELS: u8 = 8;
+
+/// Identifier of one of the [`PYRO_CHANNELS`] pyrotechnic channels
+///
+/// What is wired to each channel (parachutes, separation charges, motor igniters)
+/// is decided by the flight hardware, not by preflight
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PyroChannel(u8);
+
+impl PyroChannel {
+    /// The channel with the given id, if the flight hardware could have it
+    pub const fn new(id: u8) -> Option<Self> {
+        if id < PYRO_CHANNELS {
+            Some(Self(id))
+        } else {
+            None
+        }
+    }
+
+    /// The id of this channel, less than [`PYRO_CHANNELS`]
+    pub const fn id(self) -> u8 {
+        self.0
+    }
+}
+
+/// The set of pyrotechnic channels that the avionics are commanding to fire
+///
+/// A channel is fired on the first guidance signal that includes it. Channels
+/// are one shot, so continuing to command a channel that has already fired has
+/// no further effect
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PyroCommands(u8);
+
+impl PyroCommands {
+    /// Command no channels to fire
+    pub const NONE: Self = Self(0);
+
+    /// Add the given channel to the channels commanded to fire
+    pub const fn fire(self, channel: PyroChannel) -> Self {
+        Self(self.0 | 1 << channel.0)
+    }
+
+    /// If the given channel is commanded to fire
+    pub const fn is_fired(self, channel: PyroChannel) -> bool {
+        self.0 & 1 << channel.0 != 0
+    }
+
+    /// Iterate over the channels commanded to fire, in order of their ids
+    pub fn channels(self) -> impl Iterator<Item = PyroChannel> {
+        (0..PYRO_CHANNELS)
+            .map(PyroChannel)
+            .filter(move |&channel| self.is_fired(channel))
+    }
+}
+
+impl Debug for PyroCommands {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.channels().map(PyroChannel::id))
+            .finish()
+    }
+}
+
+/// Discrete events that the avionics signal to the flight system
+///
+/// The first few flags have a meaning shared with the preflight runner, which
+/// logs the moment each flag is raised. The remaining
+/// [`EventFlags::USER_FLAGS`] are free for the avionics to use
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct EventFlags(u32);
+
+impl EventFlags {
+    /// No events
+    pub const NONE: Self = Self(0);
+    /// The vehicle has left the launch pad
+    pub const LIFTOFF: Self = Self(1 << 0);
+    /// The motor has stopped producing thrust
+    pub const BURNOUT: Self = Self(1 << 1);
+    /// The vehicle has reached the top of its flight
+    pub const APOGEE: Self = Self(1 << 2);
+    /// The vehicle is back on the ground
+    pub const LANDED: Self = Self(1 << 3);
+
+    /// Names of the flags with a shared meaning
+    const NAMED: [(Self, &'static str); 4] = [
+        (Self::LIFTOFF, "LIFTOFF"),
+        (Self::BURNOUT, "BURNOUT"),
+        (Self::APOGEE, "APOGEE"),
+        (Self::LANDED, "LANDED"),
+    ];
+
+    /// The number of flags that are free for the avionics to use
+    pub const USER_FLAGS: u8 = 16;
+
+    /// A flag with a meaning specific to the avionics, if the index is less than
+    /// [`EventFlags::USER_FLAGS`]
+    pub const fn user(index: u8) -> Option<Self> {
+        if index < Self::USER_FLAGS {
+            Some(Self(1 << (16 + index)))
+        } else {
+            None
+        }
+    }
+
+    /// If every flag in `other` is also raised in `self`
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The flags that are raised in `self` but not in `other`
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// If no flags are raised
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The raw bits of the flags
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for EventFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for EventFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Debug for EventFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+
+        for (flag, name) in &Self::NAMED {
+            if self.contains(*flag) {
+                set.entry(&format_args!("{}", name));
+            }
+        }
+
+        for index in 0..Self::USER_FLAGS {
+            if self.contains(Self(1 << (16 + index))) {
+                set.entry(&format_args!("USER({})", index));
+            }
+        }
+
+        set.finish()
+    }
+}
+
 /// Hardware agnostic avionics system
 ///
 /// Implementations of this trait should have the [`avionics_harness`] attribute
 /// macro preceding them. Alone, this trait has little use.
+///
+/// The macro defaults [`Avionics::Sensors`] to [`Sensors`],
+/// [`Avionics::Guidance`] to [`Guidance`] and [`Avionics::Parameters`] to `()`
+/// if the implementation leaves them out.
 pub trait Avionics: Debug + Send + Sync {
+    /// The sensor readings that the avionics are guided by
+    type Sensors: Signals;
+
+    /// The guidance signal that the avionics produce
+    type Guidance: Signals;
+
+    /// The parameters that can be changed without rebuilding the avionics
+    type Parameters: Parameters;
+
+    /// Initialize the avionics once the flight computer has booted
+    ///
+    /// This is called exactly once, before the first call to [`Avionics::guide`]
+    fn init(&mut self) {}
+
     /// Produce a control signal given the current sensor values
     ///
     /// This function can be thought of as the control signal generation step
     /// in a control loop
-    fn guide(&mut self, sensors: &Sensors) -> Control;
-    // TODO: ABORT HANDLE
-    // TODO: CUSTOM SENSORS OR CONTROL STRUCT/ENUM?
+    fn guide(&mut self, sensors: &Self::Sensors) -> Control<Self::Guidance>;
+
+    /// Handle an abort signaled by the avionics
+    ///
+    /// This is called once, after [`Avionics::guide`] has returned
+    /// [`Control::ABORT`]. Guidance will not be requested again until the
+    /// avionics are reset
+    fn abort(&mut self, _cause: &AbortCause) {}
+
+    /// Handle a command sent from the ground
+    ///
+    /// This is called between calls to [`Avionics::guide`], including after an
+    /// abort, with each command as it is received
+    ///
+    /// By default, [`Command::SetParameter`] sets one of the
+    /// [`Avionics::parameters`] and every other command is unsupported
+    fn command(&mut self, command: &Command<'_>) -> CommandResponse {
+        match *command {
+            Command::SetParameter { name, value } => {
+                params::command(self.parameters(), name, value)
+            }
+            _ => CommandResponse::Unsupported,
+        }
+    }
+
+    /// The parameters of the avionics, if they have any
+    ///
+    /// The runner sets these right after [`Avionics::init`] and
+    /// [`Avionics::reset`], and can set them between calls to
+    /// [`Avionics::guide`] as well, so the avionics should read them every time
+    /// that they are used rather than only when they are initialized
+    fn parameters(&mut self) -> Option<&mut Self::Parameters> {
+        None
+    }
+
+    /// Publish values that are worth watching but are not part of the guidance
+    ///
+    /// This is called after each call to [`Avionics::guide`], with the avionics
+    /// publishing whatever they want watched into the [`Telemetry`], such as
+    /// their estimates and the error of their controllers
+    fn telemetry(&self, _telemetry: &mut Telemetry<'_>) {}
+
+    /// The state of the avionics, if they are able to be snapshotted
+    ///
+    /// The runner snapshots the avionics between calls to [`Avionics::guide`],
+    /// along with the simulated vehicle, so that a flight can be resumed from
+    /// the middle of it. Restoring a snapshot has to leave the avionics exactly
+    /// as they were when it was taken, so this is usually the avionics
+    /// themselves deriving [`Snapshot`](macro@Snapshot)
+    fn snapshot(&mut self) -> Option<&mut dyn Snapshot> {
+        None
+    }
+
+    /// The part of the state of the avionics that is kept in storage which
+    /// survives the flight computer resetting, such as backup RAM, if they have
+    /// any
+    ///
+    /// When the flight computer resets in flight, such as from a brownout, the
+    /// avionics are created anew and this storage is restored into them before
+    /// [`Avionics::init`], so that they can recover into the phase of flight
+    /// that they were in
+    fn persistent(&mut self) -> Option<&mut dyn Snapshot> {
+        None
+    }
+
+    /// Perform a warm reset of the avionics
+    ///
+    /// The flight computer has not lost power, so the avionics should return
+    /// to the state they were in right after [`Avionics::init`]
+    fn reset(&mut self) {}
+
+    /// Shut down the avionics before the flight computer powers off
+    fn shutdown(&mut self) {}
 }
@@ -0,0 +1,641 @@
+//! Logging from the avionics, which are unable to print
+//!
+//! The [`log!`](crate::log!) family of macros does not format its message on the
+//! flight computer. Each message is instead encoded into a compact frame holding
+//! its [`Level`], a hash of its format string and each of its arguments in
+//! binary, leaving the formatting to whoever reads the frames. This is far
+//! cheaper than formatting, and keeps the frames small enough to be sent over a
+//! radio link
+//! ```ignore
+//! preflight::info!("apogee at {} above the pad", tracker.altitude_above_pad());
+//! ```
+//!
+//! Frames wait in a small queue until the harness generated by
+//! [`avionics_harness`](macro@crate::avionics_harness) passes them on, and are
+//! dropped if the queue is full. Under the preflight runner the frames are sent
+//! through the ABI along with their format string after each call into the
+//! avionics, and are printed with the simulation time that they were logged at.
+//! On flight hardware they are passed to the `logger` given to the harness, and
+//! can be decoded on the ground as a [`Record`] by looking the format string up by
+//! its [`format_id`].
+//!
+//! Format strings only support `{}` placeholders, which are filled with the
+//! arguments in order. Quantities are logged in the base SI unit of their
+//! dimension.
+//!
+//! Messages can be logged from interrupts without taking a lock, since each one
+//! claims its slot in the queue with an atomic compare and swap. This crate is
+//! therefore only supported on targets with compare and swap on pointer sized
+//! atomics, and does not build for targets such as `thumbv6m-none-eabi` that
+//! only have atomic loads and stores.
+
+use core::{
+    fmt::{self, Display, Formatter},
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+};
+
+use uom::si::Dimension;
+
+use crate::Quantity;
+
+/// Longest frame that a message is encoded into, past which the arguments that
+/// do not fit are left out
+pub const MAX_FRAME_LEN: usize = 128;
+
+/// Length of the level and format id at the start of every frame
+const HEADER_LEN: usize = 5;
+
+/// Number of messages that are held until the harness passes them on, past
+/// which further messages are dropped
+pub const QUEUE_LEN: usize = 16;
+
+/// A message waiting to be passed on by the harness
+struct Slot {
+    /// Set once the message has been written, and cleared once it is taken
+    ready: AtomicBool,
+    /// Address of the format string, which is always `'static`
+    format_ptr: AtomicUsize,
+    format_len: AtomicUsize,
+    frame: [AtomicU8; MAX_FRAME_LEN],
+    frame_len: AtomicUsize,
+}
+
+// Only used to initialize the queue, each element of which is then its own atomic
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = {
+    const ZERO: AtomicU8 = AtomicU8::new(0);
+
+    Slot {
+        ready: AtomicBool::new(false),
+        format_ptr: AtomicUsize::new(0),
+        format_len: AtomicUsize::new(0),
+        frame: [ZERO; MAX_FRAME_LEN],
+        frame_len: AtomicUsize::new(0),
+    }
+};
+
+/// The messages that have been logged but not yet passed on, as a ring buffer
+/// so that the avionics never need to lock anything to log
+static QUEUE: [Slot; QUEUE_LEN] = [EMPTY_SLOT; QUEUE_LEN];
+/// Number of messages that have ever been queued, wrapping around
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+/// Number of messages that have ever been taken from the queue, wrapping around
+static TAKEN: AtomicUsize = AtomicUsize::new(0);
+
+/// The severity of a logged message
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Very detailed information, such as the values of every update
+    Trace = 0,
+    /// Information useful while debugging the avionics
+    Debug = 1,
+    /// Events in the normal operation of the avionics
+    Info = 2,
+    /// Something unexpected that the avionics are able to handle
+    Warn = 3,
+    /// Something that the avionics are unable to handle
+    Error = 4,
+}
+
+impl Level {
+    /// Every level, from the least to the most severe
+    pub const ALL: [Self; 5] = [
+        Self::Trace,
+        Self::Debug,
+        Self::Info,
+        Self::Warn,
+        Self::Error,
+    ];
+
+    /// The name of the level in capitals
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+}
+
+/// Identifier of a format string, which is a 32 bit FNV-1a hash of it
+pub const fn format_id(format: &str) -> u32 {
+    let format = format.as_bytes();
+    let mut hash: u32 = 0x811c_9dc5;
+
+    let mut i = 0;
+    while i < format.len() {
+        hash ^= format[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+
+        i += 1;
+    }
+
+    hash
+}
+
+/// The tag that precedes each encoded argument, describing how it was encoded
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    Bool = 0,
+    /// A LEB128 variable length integer
+    Unsigned = 1,
+    /// A zigzag encoded LEB128 variable length integer
+    Signed = 2,
+    F32 = 3,
+    F64 = 4,
+    /// A LEB128 length followed by that many bytes of UTF-8
+    Str = 5,
+}
+
+/// Encoder of a message into a frame
+///
+/// An argument that does not fit into the frame is left out along with every
+/// argument after it, so that a full frame still decodes
+pub struct Encoder<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    full: bool,
+}
+
+impl<'a> Encoder<'a> {
+    /// Start a frame in the given buffer, for a message of the given level and
+    /// format
+    pub fn new(buffer: &'a mut [u8], level: Level, format_id: u32) -> Self {
+        let mut encoder = Self {
+            buffer,
+            len: 0,
+            full: false,
+        };
+
+        encoder.argument(|encoder| {
+            encoder.byte(level as u8);
+            encoder.bytes(&format_id.to_le_bytes());
+        });
+
+        encoder
+    }
+
+    /// Encode a boolean
+    pub fn bool(&mut self, value: bool) {
+        self.argument(|encoder| {
+            encoder.byte(Tag::Bool as u8);
+            encoder.byte(value as u8);
+        });
+    }
+
+    /// Encode an unsigned integer, which takes fewer bytes the smaller it is
+    pub fn unsigned(&mut self, value: u64) {
+        self.argument(|encoder| {
+            encoder.byte(Tag::Unsigned as u8);
+            encoder.varint(value);
+        });
+    }
+
+    /// Encode a signed integer, which takes fewer bytes the closer to zero it is
+    pub fn signed(&mut self, value: i64) {
+        self.argument(|encoder| {
+            encoder.byte(Tag::Signed as u8);
+            encoder.varint(((value << 1) ^ (value >> 63)) as u64);
+        });
+    }
+
+    /// Encode a single precision float
+    pub fn f32(&mut self, value: f32) {
+        self.argument(|encoder| {
+            encoder.byte(Tag::F32 as u8);
+            encoder.bytes(&value.to_le_bytes());
+        });
+    }
+
+    /// Encode a double precision float
+    pub fn f64(&mut self, value: f64) {
+        self.argument(|encoder| {
+            encoder.byte(Tag::F64 as u8);
+            encoder.bytes(&value.to_le_bytes());
+        });
+    }
+
+    /// Encode a string, which is copied into the frame
+    pub fn str(&mut self, value: &str) {
+        self.argument(|encoder| {
+            encoder.byte(Tag::Str as u8);
+            encoder.varint(value.len() as u64);
+            encoder.bytes(value.as_bytes());
+        });
+    }
+
+    /// The encoded frame
+    pub fn finish(self) -> &'a [u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// Encode a whole argument, or none of it if it does not fit
+    fn argument(&mut self, encode: impl FnOnce(&mut Self)) {
+        if self.full {
+            return;
+        }
+
+        let start = self.len;
+        encode(self);
+
+        if self.len > self.buffer.len() {
+            self.len = start;
+            self.full = true;
+        }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        if let Some(slot) = self.buffer.get_mut(self.len) {
+            *slot = byte;
+        }
+
+        self.len += 1;
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.byte(*byte);
+        }
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80);
+            value >>= 7;
+        }
+
+        self.byte(value as u8);
+    }
+}
+
+/// A value that can be logged as an argument of the [`log!`](crate::log!) macros
+pub trait Encode {
+    /// Encode the value into the frame
+    fn encode(&self, encoder: &mut Encoder<'_>);
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        (**self).encode(encoder)
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.bool(*self)
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.str(self)
+    }
+}
+
+impl Encode for f32 {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.f32(*self)
+    }
+}
+
+impl Encode for f64 {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.f64(*self)
+    }
+}
+
+macro_rules! impl_encode_integer {
+    ($method:ident as $wide:ty: $($integer:ty),*) => {
+        $(
+            impl Encode for $integer {
+                fn encode(&self, encoder: &mut Encoder<'_>) {
+                    encoder.$method(*self as $wide)
+                }
+            }
+        )*
+    };
+}
+
+impl_encode_integer!(unsigned as u64: u8, u16, u32, u64, usize);
+impl_encode_integer!(signed as i64: i8, i16, i32, i64, isize);
+
+impl<D: Dimension + ?Sized> Encode for Quantity<D> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.f32(self.value)
+    }
+}
+
+/// A decoded argument of a message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Argument<'a> {
+    /// See [`Encoder::bool`]
+    Bool(bool),
+    /// See [`Encoder::unsigned`]
+    Unsigned(u64),
+    /// See [`Encoder::signed`]
+    Signed(i64),
+    /// See [`Encoder::f32`]
+    F32(f32),
+    /// See [`Encoder::f64`]
+    F64(f64),
+    /// See [`Encoder::str`]
+    Str(&'a str),
+}
+
+impl Display for Argument<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => value.fmt(f),
+            Self::Unsigned(value) => value.fmt(f),
+            Self::Signed(value) => value.fmt(f),
+            Self::F32(value) => value.fmt(f),
+            Self::F64(value) => value.fmt(f),
+            Self::Str(value) => value.fmt(f),
+        }
+    }
+}
+
+/// A frame decoded into the level, format and arguments of its message
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    /// The severity of the message
+    pub level: Level,
+    /// The [`format_id`] of the format string of the message
+    pub format_id: u32,
+    arguments: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Decode the level and format of the message in the frame, if it is well
+    /// formed
+    pub fn decode(frame: &'a [u8]) -> Option<Self> {
+        if frame.len() < HEADER_LEN {
+            return None;
+        }
+
+        let level = *Level::ALL.get(usize::from(frame[0]))?;
+        let mut format_id = [0; 4];
+        format_id.copy_from_slice(&frame[1..HEADER_LEN]);
+
+        Some(Self {
+            level,
+            format_id: u32::from_le_bytes(format_id),
+            arguments: &frame[HEADER_LEN..],
+        })
+    }
+
+    /// The arguments of the message, in order
+    ///
+    /// These stop early at the first argument that is not well formed
+    pub fn arguments(&self) -> Arguments<'a> {
+        Arguments {
+            remaining: self.arguments,
+        }
+    }
+
+    /// The message, with the arguments filled into the given format string
+    ///
+    /// Placeholders that are left without an argument are shown as `{?}`
+    pub fn message(&self, format: &'a str) -> Message<'a> {
+        Message {
+            format,
+            record: *self,
+        }
+    }
+}
+
+/// Iterator over the decoded arguments of a [`Record`]
+#[derive(Debug, Clone)]
+pub struct Arguments<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> Arguments<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.remaining.len() < len {
+            return None;
+        }
+
+        let (taken, remaining) = self.remaining.split_at(len);
+        self.remaining = remaining;
+
+        Some(taken)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a> Iterator for Arguments<'a> {
+    type Item = Argument<'a>;
+
+    fn next(&mut self) -> Option<Argument<'a>> {
+        let tag = self.take(1)?[0];
+
+        let argument = if tag == Tag::Bool as u8 {
+            Argument::Bool(self.take(1)?[0] != 0)
+        } else if tag == Tag::Unsigned as u8 {
+            Argument::Unsigned(self.varint()?)
+        } else if tag == Tag::Signed as u8 {
+            let zigzag = self.varint()?;
+            Argument::Signed((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+        } else if tag == Tag::F32 as u8 {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(self.take(4)?);
+            Argument::F32(f32::from_le_bytes(bytes))
+        } else if tag == Tag::F64 as u8 {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(self.take(8)?);
+            Argument::F64(f64::from_le_bytes(bytes))
+        } else if tag == Tag::Str as u8 {
+            let len = self.varint()? as usize;
+            Argument::Str(core::str::from_utf8(self.take(len)?).ok()?)
+        } else {
+            return None;
+        };
+
+        Some(argument)
+    }
+}
+
+/// The message of a [`Record`], formatted when it is displayed
+#[derive(Debug, Clone, Copy)]
+pub struct Message<'a> {
+    format: &'a str,
+    record: Record<'a>,
+}
+
+impl Display for Message<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut arguments = self.record.arguments();
+        let mut format = self.format;
+
+        while let Some(brace) = format.find(&['{', '}'][..]) {
+            f.write_str(&format[..brace])?;
+            let rest = &format[brace..];
+
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                f.write_str(&rest[..1])?;
+                format = &rest[2..];
+            } else if let (true, Some(end)) = (rest.starts_with('{'), rest.find('}')) {
+                match arguments.next() {
+                    Some(argument) => argument.fmt(f)?,
+                    None => f.write_str("{?}")?,
+                }
+                format = &rest[end + 1..];
+            } else {
+                f.write_str(&rest[..1])?;
+                format = &rest[1..];
+            }
+        }
+
+        f.write_str(format)
+    }
+}
+
+/// Encode a message and queue it for the harness to pass on, used by the
+/// [`log!`](crate::log!) macros
+#[doc(hidden)]
+pub fn write(level: Level, format: &'static str, format_id: u32, arguments: &[&dyn Encode]) {
+    let mut buffer = [0; MAX_FRAME_LEN];
+    let mut encoder = Encoder::new(&mut buffer, level, format_id);
+    for argument in arguments {
+        argument.encode(&mut encoder);
+    }
+    let frame = encoder.finish();
+
+    // Claim the next slot, unless every slot still holds a message
+    let mut queued = QUEUED.load(Ordering::Relaxed);
+    loop {
+        if queued.wrapping_sub(TAKEN.load(Ordering::Acquire)) >= QUEUE_LEN {
+            return;
+        }
+
+        match QUEUED.compare_exchange_weak(
+            queued,
+            queued.wrapping_add(1),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => break,
+            Err(actual) => queued = actual,
+        }
+    }
+
+    let slot = &QUEUE[queued % QUEUE_LEN];
+    slot.format_ptr
+        .store(format.as_ptr() as usize, Ordering::Relaxed);
+    slot.format_len.store(format.len(), Ordering::Relaxed);
+    for (byte, value) in slot.frame.iter().zip(frame) {
+        byte.store(*value, Ordering::Relaxed);
+    }
+    slot.frame_len.store(frame.len(), Ordering::Relaxed);
+    slot.ready.store(true, Ordering::Release);
+}
+
+/// Take every queued message in the order that they were logged, passing the
+/// address and length of its format string along with its frame to `message`
+pub(crate) fn drain(mut message: impl FnMut((*const u8, usize), &[u8])) {
+    let mut taken = TAKEN.load(Ordering::Relaxed);
+
+    loop {
+        let slot = &QUEUE[taken % QUEUE_LEN];
+
+        // A message that is still being written is left for the next time
+        if taken == QUEUED.load(Ordering::Acquire) || !slot.ready.load(Ordering::Acquire) {
+            return;
+        }
+
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = slot.frame_len.load(Ordering::Relaxed);
+        for (value, byte) in frame.iter_mut().zip(&slot.frame[..len]) {
+            *value = byte.load(Ordering::Relaxed);
+        }
+        let format = (
+            slot.format_ptr.load(Ordering::Relaxed) as *const u8,
+            slot.format_len.load(Ordering::Relaxed),
+        );
+
+        slot.ready.store(false, Ordering::Relaxed);
+        taken = taken.wrapping_add(1);
+        TAKEN.store(taken, Ordering::Release);
+
+        message(format, &frame[..len]);
+    }
+}
+
+/// Log a message at the given [`Level`], with `{}` placeholders in the format
+/// string filled by the arguments that follow it
+///
+/// ```ignore
+/// preflight::log!(Level::Info, "entered {} at {}", phase as u8, altitude);
+/// ```
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $format:literal $(, $argument:expr)* $(,)?) => {{
+        const FORMAT_ID: u32 = $crate::log::format_id($format);
+
+        $crate::log::write(
+            $level,
+            $format,
+            FORMAT_ID,
+            &[$(&$argument as &dyn $crate::log::Encode),*],
+        )
+    }};
+}
+
+/// Log a message at [`Level::Trace`], see [`log!`](crate::log!)
+#[macro_export]
+macro_rules! trace {
+    ($($arguments:tt)+) => {
+        $crate::log!($crate::log::Level::Trace, $($arguments)+)
+    };
+}
+
+/// Log a message at [`Level::Debug`], see [`log!`](crate::log!)
+#[macro_export]
+macro_rules! debug {
+    ($($arguments:tt)+) => {
+        $crate::log!($crate::log::Level::Debug, $($arguments)+)
+    };
+}
+
+/// Log a message at [`Level::Info`], see [`log!`](crate::log!)
+#[macro_export]
+macro_rules! info {
+    ($($arguments:tt)+) => {
+        $crate::log!($crate::log::Level::Info, $($arguments)+)
+    };
+}
+
+/// Log a message at [`Level::Warn`], see [`log!`](crate::log!)
+#[macro_export]
+macro_rules! warn {
+    ($($arguments:tt)+) => {
+        $crate::log!($crate::log::Level::Warn, $($arguments)+)
+    };
+}
+
+/// Log a message at [`Level::Error`], see [`log!`](crate::log!)
+#[macro_export]
+macro_rules! error {
+    ($($arguments:tt)+) => {
+        $crate::log!($crate::log::Level::Error, $($arguments)+)
+    };
+}
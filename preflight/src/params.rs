@@ -0,0 +1,188 @@
+//! Parameters of the avionics that can be changed without rebuilding them
+//!
+//! Gains and thresholds compiled into the avionics as constants can only be
+//! swept by recompiling. The avionics can instead keep them in a struct deriving
+//! [`Parameters`](macro@crate::Parameters), which flattens its fields into named
+//! parameters in the same way that [`Signals`](crate::Signals) flattens channels.
+//! Through [`Avionics::parameters`](crate::Avionics::parameters), the preflight
+//! runner is able to list, read and set them from the command line and from
+//! scenario files, and a ground station is able to set them with
+//! [`Command::SetParameter`](crate::Command::SetParameter).
+//!
+//! Since the avionics have to be created in a `const fn`, parameters are best
+//! kept as plain numbers, in the base SI unit of whatever they describe.
+
+use uom::si::Dimension;
+
+use crate::{ChannelName, CommandResponse, Quantity};
+
+/// A type that is able to be flattened into a fixed number of named parameters
+///
+/// This should almost always be derived with
+/// [`derive(Parameters)`](macro@crate::Parameters) which flattens each of the
+/// fields of a struct in order.
+pub trait Parameters {
+    /// The number of parameters that this type flattens into
+    const PARAMETERS: usize;
+
+    /// Call `parameter` with the name of each of the parameters in order
+    ///
+    /// `parent` is the name of the field that holds this value, if there is one
+    fn names(parent: Option<&ChannelName<'_>>, parameter: &mut dyn FnMut(&ChannelName<'_>));
+
+    /// The value of the parameter at the given index, if there is one
+    fn get(&self, index: usize) -> Option<f64>;
+
+    /// Set the value of the parameter at the given index, returning if there is
+    /// one that is able to hold the value
+    fn set(&mut self, index: usize, value: f64) -> bool;
+}
+
+/// The index of the parameter with the given name, if there is one
+pub fn index_of<P: Parameters>(name: &str) -> Option<usize> {
+    let mut index = 0;
+    let mut found = None;
+
+    P::names(None, &mut |parameter| {
+        if found.is_none() && parameter.matches(name) {
+            found = Some(index);
+        }
+
+        index += 1;
+    });
+
+    found
+}
+
+/// Set the parameter with the given name, returning if there is one that is
+/// able to hold the value
+pub fn set_by_name<P: Parameters>(parameters: &mut P, name: &str, value: f64) -> bool {
+    match index_of::<P>(name) {
+        Some(index) => parameters.set(index, value),
+        None => false,
+    }
+}
+
+/// Carry out a [`Command::SetParameter`](crate::Command::SetParameter) on the
+/// parameters of the avionics, if they have any
+pub fn command<P: Parameters>(
+    parameters: Option<&mut P>,
+    name: &str,
+    value: f64,
+) -> CommandResponse {
+    match parameters {
+        None => CommandResponse::Unsupported,
+        Some(parameters) => {
+            if set_by_name(parameters, name, value) {
+                CommandResponse::Accepted
+            } else {
+                CommandResponse::Rejected
+            }
+        }
+    }
+}
+
+impl Parameters for () {
+    const PARAMETERS: usize = 0;
+
+    fn names(_: Option<&ChannelName<'_>>, _: &mut dyn FnMut(&ChannelName<'_>)) {}
+
+    fn get(&self, _: usize) -> Option<f64> {
+        None
+    }
+
+    fn set(&mut self, _: usize, _: f64) -> bool {
+        false
+    }
+}
+
+/// Call the `parameter` callback with the name of a single parameter
+fn leaf(parent: Option<&ChannelName<'_>>, parameter: &mut dyn FnMut(&ChannelName<'_>)) {
+    match parent {
+        Some(name) => parameter(name),
+        None => parameter(&ChannelName::new("value", None)),
+    }
+}
+
+macro_rules! impl_parameters_for_primitive {
+    ($($ty:ty: $fits:expr),*) => {$(
+        impl Parameters for $ty {
+            const PARAMETERS: usize = 1;
+
+            fn names(parent: Option<&ChannelName<'_>>, parameter: &mut dyn FnMut(&ChannelName<'_>)) {
+                leaf(parent, parameter)
+            }
+
+            fn get(&self, index: usize) -> Option<f64> {
+                (index == 0).then(|| *self as f64)
+            }
+
+            fn set(&mut self, index: usize, value: f64) -> bool {
+                let fits: fn(f64) -> bool = $fits;
+
+                if index == 0 && fits(value) {
+                    *self = value as $ty;
+
+                    return true;
+                }
+
+                false
+            }
+        }
+    )*};
+}
+
+// Values that the type can not hold are rejected rather than saturated, and
+// integers are only set to whole numbers
+impl_parameters_for_primitive!(
+    f32: |value| value.is_finite() && value.abs() <= f64::from(f32::MAX),
+    f64: |value| value.is_finite(),
+    u8: |value| f64::from(value as u8) == value,
+    u16: |value| f64::from(value as u16) == value,
+    u32: |value| f64::from(value as u32) == value,
+    i8: |value| f64::from(value as i8) == value,
+    i16: |value| f64::from(value as i16) == value,
+    i32: |value| f64::from(value as i32) == value
+);
+
+impl Parameters for bool {
+    const PARAMETERS: usize = 1;
+
+    fn names(parent: Option<&ChannelName<'_>>, parameter: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, parameter)
+    }
+
+    fn get(&self, index: usize) -> Option<f64> {
+        match index {
+            0 if *self => Some(1.0),
+            0 => Some(0.0),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, index: usize, value: f64) -> bool {
+        if index == 0 && !value.is_nan() {
+            *self = value != 0.0;
+
+            return true;
+        }
+
+        false
+    }
+}
+
+impl<D: Dimension + ?Sized> Parameters for Quantity<D> {
+    const PARAMETERS: usize = 1;
+
+    fn names(parent: Option<&ChannelName<'_>>, parameter: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, parameter)
+    }
+
+    fn get(&self, index: usize) -> Option<f64> {
+        self.value.get(index)
+    }
+
+    fn set(&mut self, index: usize, value: f64) -> bool {
+        self.value.set(index, value)
+    }
+}
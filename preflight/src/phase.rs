@@ -0,0 +1,291 @@
+//! Tracking of the phase of flight that the vehicle is in
+//!
+//! Most of what the avionics do depends on the phase of flight: the gimbal is
+//! only steered during the boost, and the parachutes are only deployed after
+//! apogee. The [`PhaseTracker`] detects the transitions between the phases from
+//! the [`Sensors`], with each transition having to hold for a while before it is
+//! believed, so that a single noisy reading cannot deploy a parachute on the pad.
+//!
+//! The avionics report the phase they believe the vehicle is in with
+//! [`Guidance::phase`](crate::Guidance::phase), which the preflight runner logs
+//! and checks scenarios against.
+
+use uom::si::{
+    acceleration::meter_per_second_squared,
+    f32::{Acceleration, Length, Time, Velocity},
+    length::meter,
+    time::second,
+    velocity::meter_per_second,
+};
+
+use crate::{estimation::AltitudeKalman, SensorHealth, Sensors, Snapshot};
+
+/// A phase of the flight of the vehicle, in the order that they happen
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlightPhase {
+    /// Sitting on the launch pad, unable to fire any pyrotechnics
+    Pad = 0,
+    /// Sitting on the launch pad, ready for the motor to be ignited
+    Armed = 1,
+    /// Climbing under the thrust of the motor
+    Boost = 2,
+    /// Climbing after the motor has burnt out
+    Coast = 3,
+    /// At the top of the flight, which lasts for a single update
+    Apogee = 4,
+    /// Descending under the drogue parachute
+    DrogueDescent = 5,
+    /// Descending under the main parachute
+    MainDescent = 6,
+    /// Back on the ground
+    Landed = 7,
+}
+
+impl FlightPhase {
+    /// Every phase, in the order that they happen
+    pub const ALL: [Self; 8] = [
+        Self::Pad,
+        Self::Armed,
+        Self::Boost,
+        Self::Coast,
+        Self::Apogee,
+        Self::DrogueDescent,
+        Self::MainDescent,
+        Self::Landed,
+    ];
+}
+
+/// The readings that the [`PhaseTracker`] moves between phases at
+#[derive(Debug, Clone, Copy, Snapshot)]
+pub struct PhaseThresholds {
+    /// Vertical acceleration above which the vehicle has lifted off
+    pub liftoff_acceleration: Acceleration,
+    /// Vertical acceleration below which the motor has burnt out
+    pub burnout_acceleration: Acceleration,
+    /// Altitude above the pad below which the main parachute is deployed
+    pub main_altitude: Length,
+    /// Vertical speed below which the vehicle may have landed
+    pub landed_velocity: Velocity,
+    /// Time that each reading has to stay past its threshold for to be believed
+    pub debounce: Time,
+    /// Time that the vehicle has to stay slower than
+    /// [`PhaseThresholds::landed_velocity`] for to have landed, which is far
+    /// longer than the debounce since the vehicle also hangs still at apogee
+    pub landed_time: Time,
+}
+
+impl Default for PhaseThresholds {
+    fn default() -> Self {
+        Self {
+            liftoff_acceleration: Acceleration::new::<meter_per_second_squared>(20.0),
+            burnout_acceleration: Acceleration::new::<meter_per_second_squared>(0.0),
+            main_altitude: Length::new::<meter>(150.0),
+            landed_velocity: Velocity::new::<meter_per_second>(1.0),
+            debounce: Time::new::<second>(0.05),
+            landed_time: Time::new::<second>(2.0),
+        }
+    }
+}
+
+/// Detection of the [`FlightPhase`] from the [`Sensors`]
+///
+/// The vertical velocity and altitude are estimated with an [`AltitudeKalman`],
+/// and the vertical acceleration is [`Sensors::linear_acceleration`] rotated into
+/// the world frame. The tracker stays on the pad until it is armed, so that
+/// handling the vehicle before launch is never mistaken for a liftoff.
+///
+/// Liftoff is only detected while the accelerometer and orientation are healthy.
+/// If either fails during the boost, burnout is detected from the vertical
+/// acceleration that the filter estimates from the altimeter alone instead, so
+/// that the tracker still reaches apogee.
+#[derive(Debug, Clone, Snapshot)]
+pub struct PhaseTracker {
+    phase: FlightPhase,
+    thresholds: PhaseThresholds,
+    filter: AltitudeKalman,
+    /// Estimated altitude of the launch pad
+    pad_altitude: Length,
+    /// [`Sensors::running_time`] since which the condition for the next
+    /// transition has held
+    since: Option<Time>,
+}
+
+impl PhaseTracker {
+    /// A tracker on the pad, that moves between phases at the given thresholds
+    pub fn new(thresholds: PhaseThresholds) -> Self {
+        Self::with_filter(thresholds, AltitudeKalman::default())
+    }
+
+    /// A tracker that estimates the altitude and vertical velocity with the
+    /// given filter, tuned for the sensors of the vehicle
+    pub fn with_filter(thresholds: PhaseThresholds, filter: AltitudeKalman) -> Self {
+        Self {
+            phase: FlightPhase::Pad,
+            thresholds,
+            filter,
+            pad_altitude: Length::new::<meter>(0.0),
+            since: None,
+        }
+    }
+
+    /// The phase that the vehicle is in
+    pub fn phase(&self) -> FlightPhase {
+        self.phase
+    }
+
+    /// The filter that the altitude and vertical velocity are estimated with
+    pub fn filter(&self) -> &AltitudeKalman {
+        &self.filter
+    }
+
+    /// The estimated altitude of the launch pad, relative to the same reference
+    /// as [`Sensors::altitude`]
+    pub fn pad_altitude(&self) -> Length {
+        self.pad_altitude
+    }
+
+    /// The altitude of the vehicle above the launch pad
+    pub fn altitude_above_pad(&self) -> Length {
+        self.filter.altitude() - self.pad_altitude
+    }
+
+    /// Get ready for launch, if the vehicle is still on the pad
+    pub fn arm(&mut self) {
+        if self.phase == FlightPhase::Pad {
+            self.enter(FlightPhase::Armed);
+        }
+    }
+
+    /// Stand down from launch, if the vehicle has not yet lifted off
+    pub fn disarm(&mut self) {
+        if self.phase == FlightPhase::Armed {
+            self.enter(FlightPhase::Pad);
+        }
+    }
+
+    /// Carry on from the given phase, such as after the flight computer has
+    /// reset in flight, with the altitude of the pad as it was estimated before
+    ///
+    /// The altitude filter starts over from the next readings, without trusting
+    /// its estimate of the vertical velocity until the altimeter has caught it up.
+    pub fn resume(&mut self, phase: FlightPhase, pad_altitude: Length) {
+        if phase > FlightPhase::Armed {
+            self.filter.forget_velocity();
+        }
+
+        self.pad_altitude = pad_altitude;
+        self.enter(phase);
+    }
+
+    /// Update the estimate with the latest readings, returning the phase that
+    /// the vehicle has entered if these readings moved it into a new one
+    pub fn update(&mut self, sensors: &Sensors) -> Option<FlightPhase> {
+        self.filter.update(sensors);
+
+        let thresholds = self.thresholds;
+        let time = sensors.running_time;
+        let velocity = self.filter.vertical_velocity();
+
+        let next = match self.phase {
+            FlightPhase::Pad => {
+                self.pad_altitude = self.filter.altitude();
+                None
+            }
+            FlightPhase::Armed => {
+                self.pad_altitude = self.filter.altitude();
+
+                vertical_acceleration(sensors).and_then(|acceleration| {
+                    self.transition(
+                        acceleration > thresholds.liftoff_acceleration,
+                        time,
+                        thresholds.debounce,
+                        FlightPhase::Boost,
+                    )
+                })
+            }
+            FlightPhase::Boost => {
+                // Without the accelerometer, burnout shows as the vertical velocity
+                // from the altimeter starting to fall, which the filter sees late
+                let acceleration = vertical_acceleration(sensors)
+                    .unwrap_or_else(|| self.filter.vertical_acceleration());
+
+                self.transition(
+                    acceleration < thresholds.burnout_acceleration,
+                    time,
+                    thresholds.debounce,
+                    FlightPhase::Coast,
+                )
+            }
+            FlightPhase::Coast => self.transition(
+                velocity.value <= 0.0,
+                time,
+                thresholds.debounce,
+                FlightPhase::Apogee,
+            ),
+            FlightPhase::Apogee => Some(FlightPhase::DrogueDescent),
+            FlightPhase::DrogueDescent => self.transition(
+                self.altitude_above_pad() < thresholds.main_altitude,
+                time,
+                thresholds.debounce,
+                FlightPhase::MainDescent,
+            ),
+            FlightPhase::MainDescent => self.transition(
+                velocity < thresholds.landed_velocity && velocity > -thresholds.landed_velocity,
+                time,
+                thresholds.landed_time,
+                FlightPhase::Landed,
+            ),
+            FlightPhase::Landed => None,
+        };
+
+        if let Some(next) = next {
+            self.enter(next);
+        }
+
+        next
+    }
+
+    /// The `next` phase, once `condition` has held for at least `hold` up to the
+    /// given time
+    fn transition(
+        &mut self,
+        condition: bool,
+        time: Time,
+        hold: Time,
+        next: FlightPhase,
+    ) -> Option<FlightPhase> {
+        if !condition {
+            self.since = None;
+            return None;
+        }
+
+        let since = *self.since.get_or_insert(time);
+
+        if (time - since).get::<second>() >= hold.get::<second>() {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    fn enter(&mut self, phase: FlightPhase) {
+        self.phase = phase;
+        self.since = None;
+    }
+}
+
+impl Default for PhaseTracker {
+    fn default() -> Self {
+        Self::new(PhaseThresholds::default())
+    }
+}
+
+/// The vertical acceleration, not including gravity, if the accelerometer and
+/// orientation are healthy
+fn vertical_acceleration(sensors: &Sensors) -> Option<Acceleration> {
+    sensors
+        .health
+        .contains(SensorHealth::ACCELEROMETER | SensorHealth::ORIENTATION)
+        .then(|| sensors.attitude.rotate(&sensors.linear_acceleration).z())
+}
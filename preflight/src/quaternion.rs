@@ -0,0 +1,303 @@
+use core::ops::{Add, Mul, Sub};
+
+// std's inherent float methods take precedence when it is linked in for tests
+#[cfg_attr(test, allow(unused_imports))]
+use micromath::F32Ext;
+use uom::si::{angle::radian, f32::Angle, Dimension};
+
+use crate::{frame::Frame, vector::sqrt, Signals, Snapshot, Vector3};
+
+/// A rotation matrix, stored as an array of rows
+pub type RotationMatrix = [[f32; 3]; 3];
+
+/// A rotation in 3 dimensional space, stored as the unit quaternion
+/// `w + xi + yj + zk`
+///
+/// Unlike Euler angles, quaternions have no singularities, so they are able to
+/// represent the attitude of a vehicle pointing straight up
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Signals, Snapshot)]
+pub struct Quaternion {
+    /// The real, or scalar, part
+    pub w: f32,
+    /// The first imaginary part
+    pub x: f32,
+    /// The second imaginary part
+    pub y: f32,
+    /// The third imaginary part
+    pub z: f32,
+}
+
+impl Quaternion {
+    /// The rotation that leaves everything where it is
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 0.0);
+
+    /// Create a quaternion from its parts, without normalizing it
+    pub const fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// The rotation by the given angle about the given axis, which does not
+    /// need to be normalized
+    pub fn from_axis_angle(axis: [f32; 3], angle: Angle) -> Self {
+        let [x, y, z] = axis;
+        let norm = sqrt(x * x + y * y + z * z);
+
+        if norm == 0.0 {
+            return Self::IDENTITY;
+        }
+
+        let half = angle.get::<radian>() / 2.0;
+        let scale = half.sin() / norm;
+
+        Self::new(half.cos(), x * scale, y * scale, z * scale)
+    }
+
+    /// The rotation made up of a roll about the x axis, then a pitch about the
+    /// y axis, then a yaw about the z axis, each about the fixed axes
+    pub fn from_euler_angles(roll: Angle, pitch: Angle, yaw: Angle) -> Self {
+        let (sr, cr) = half_sin_cos(roll);
+        let (sp, cp) = half_sin_cos(pitch);
+        let (sy, cy) = half_sin_cos(yaw);
+
+        Self::new(
+            cr * cp * cy + sr * sp * sy,
+            sr * cp * cy - cr * sp * sy,
+            cr * sp * cy + sr * cp * sy,
+            cr * cp * sy - sr * sp * cy,
+        )
+    }
+
+    /// The roll, pitch and yaw that make up this rotation, in the convention of
+    /// [`Quaternion::from_euler_angles`]
+    ///
+    /// Once the pitch reaches ±90° the roll and yaw describe the same axis, so
+    /// only their difference is meaningful
+    pub fn euler_angles(&self) -> (Angle, Angle, Angle) {
+        let Self { w, x, y, z } = *self;
+
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let sin_pitch = 2.0 * (w * y - z * x);
+        let pitch = if sin_pitch >= 1.0 {
+            core::f32::consts::FRAC_PI_2
+        } else if sin_pitch <= -1.0 {
+            -core::f32::consts::FRAC_PI_2
+        } else {
+            sin_pitch.asin()
+        };
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+
+        (
+            Angle::new::<radian>(roll),
+            Angle::new::<radian>(pitch),
+            Angle::new::<radian>(yaw),
+        )
+    }
+
+    /// The rotation described by the given rotation matrix
+    pub fn from_rotation_matrix(m: &RotationMatrix) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        // Divide by the largest of the parts to stay numerically stable
+        if trace > 0.0 {
+            let s = sqrt(trace + 1.0) * 2.0;
+
+            Self::new(
+                s / 4.0,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]) * 2.0;
+
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]) * 2.0;
+
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]) * 2.0;
+
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+            )
+        }
+    }
+
+    /// The rotation matrix that performs the same rotation
+    pub fn to_rotation_matrix(&self) -> RotationMatrix {
+        let Self { w, x, y, z } = *self;
+
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
+    /// The length of the quaternion, which is 1 for every rotation
+    pub fn norm(&self) -> f32 {
+        sqrt(self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z)
+    }
+
+    /// Scale the quaternion back to a length of 1, correcting for the error that
+    /// builds up when many rotations are composed
+    pub fn normalize(&self) -> Self {
+        let norm = self.norm();
+
+        if norm == 0.0 {
+            return Self::IDENTITY;
+        }
+
+        Self::new(self.w / norm, self.x / norm, self.y / norm, self.z / norm)
+    }
+
+    /// The opposite rotation
+    pub fn inverse(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// The angle of the smallest rotation that takes this rotation to the other,
+    /// such as the error of an estimated attitude
+    pub fn angle_to(&self, other: &Self) -> Angle {
+        let difference = self.inverse() * *other;
+        let sin = sqrt(
+            difference.x * difference.x + difference.y * difference.y + difference.z * difference.z,
+        );
+
+        // q and -q are the same rotation
+        Angle::new::<radian>(2.0 * atan2(sin, difference.w.abs()))
+    }
+
+    /// Rotate the given vector, keeping it in the same frame
+    ///
+    /// To express a vector in another frame, use a [`Rotation`](crate::frame::Rotation)
+    pub fn rotate<F: Frame, D: Dimension + ?Sized>(&self, vector: &Vector3<F, D>) -> Vector3<F, D> {
+        let v = [vector.x.value, vector.y.value, vector.z.value];
+        let u = [self.x, self.y, self.z];
+
+        // v + 2w(u × v) + 2u × (u × v)
+        let t = cross(u, v);
+        let t = [2.0 * t[0], 2.0 * t[1], 2.0 * t[2]];
+        let ut = cross(u, t);
+
+        Vector3::from_values(
+            v[0] + self.w * t[0] + ut[0],
+            v[1] + self.w * t[1] + ut[1],
+            v[2] + self.w * t[2] + ut[2],
+        )
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Self;
+
+    /// Compose two rotations, with `rhs` being applied first
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+impl Add for Quaternion {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(
+            self.w + rhs.w,
+            self.x + rhs.x,
+            self.y + rhs.y,
+            self.z + rhs.z,
+        )
+    }
+}
+
+impl Sub for Quaternion {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + rhs * -1.0
+    }
+}
+
+impl Mul<f32> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.w * rhs, self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+/// The sine and cosine of half of the given angle
+fn half_sin_cos(angle: Angle) -> (f32, f32) {
+    let half = angle.get::<radian>() / 2.0;
+
+    (half.sin(), half.cos())
+}
+
+/// The arctangent of `y / x` for `x` and `y` that are not negative, to within
+/// about 1e-5 radians, which unlike the estimate of [`micromath`] stays accurate
+/// for small angles
+fn atan2(y: f32, x: f32) -> f32 {
+    // Polynomial approximation over [0, 1], from Abramowitz and Stegun 4.4.49
+    let atan = |r: f32| {
+        let r2 = r * r;
+
+        r * (0.999_866
+            + r2 * (-0.330_299_5 + r2 * (0.180_141 + r2 * (-0.085_133 + r2 * 0.020_835_1))))
+    };
+
+    if y <= x {
+        if x == 0.0 {
+            0.0
+        } else {
+            atan(y / x)
+        }
+    } else {
+        core::f32::consts::FRAC_PI_2 - atan(x / y)
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
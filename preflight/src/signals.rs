@@ -0,0 +1,296 @@
+//! Flattening of sensor and guidance types into named channels
+//!
+//! The avionics are free to define their own sensor inputs and guidance outputs
+//! (see [`Avionics::Sensors`](crate::Avionics::Sensors) and
+//! [`Avionics::Guidance`](crate::Avionics::Guidance)). So that the preflight
+//! runner does not need to know their layout, these types cross the ABI as a flat
+//! buffer of `f64` channels, each with a name made up of the path of fields that
+//! lead to it, such as `tvc.x`. The runner fills each sensor channel that has the
+//! same name as one it simulates, and logs every channel.
+//!
+//! Quantities are stored in the base SI unit of their dimension.
+
+use core::fmt::{self, Display, Formatter};
+
+use uom::si::Dimension;
+
+use crate::{
+    frame::{Frame, Rotation},
+    EventFlags, FlightPhase, GnssFix, PreciseQuantity, PyroCommands, Quantity, Quaternion,
+    SensorHealth, Vector3,
+};
+
+/// A type that is able to be flattened into a fixed number of `f64` channels
+///
+/// This should almost always be derived with [`derive(Signals)`](macro@crate::Signals)
+/// which flattens each of the fields of a struct in order.
+pub trait Signals {
+    /// The number of channels that this type flattens into
+    const CHANNELS: usize;
+
+    /// Call `channel` with the name of each of the channels in order
+    ///
+    /// `parent` is the name of the field that holds this value, if there is one
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>));
+
+    /// Write each channel into the buffer, which is exactly [`Signals::CHANNELS`] long
+    fn write(&self, buffer: &mut [f64]);
+
+    /// Read each channel out of the buffer, which is exactly [`Signals::CHANNELS`] long
+    fn read(buffer: &[f64]) -> Self;
+}
+
+/// The name of a channel, made up of the path of fields that lead to it
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelName<'a> {
+    field: &'static str,
+    parent: Option<&'a ChannelName<'a>>,
+}
+
+impl<'a> ChannelName<'a> {
+    /// The name of the given field within the parent
+    pub const fn new(field: &'static str, parent: Option<&'a ChannelName<'a>>) -> Self {
+        Self { field, parent }
+    }
+
+    /// If this is the name given as a dotted path of fields, such as `tvc.x`
+    pub fn matches(&self, name: &str) -> bool {
+        self.strip_from(name) == Some("")
+    }
+
+    /// The rest of the dotted path after this name, if it starts with it
+    fn strip_from<'n>(&self, name: &'n str) -> Option<&'n str> {
+        let name = match self.parent {
+            Some(parent) => parent.strip_from(name)?.strip_prefix('.')?,
+            None => name,
+        };
+
+        name.strip_prefix(self.field)
+    }
+}
+
+impl Display for ChannelName<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(parent) = self.parent {
+            write!(f, "{}.", parent)?;
+        }
+
+        f.write_str(self.field)
+    }
+}
+
+/// Call the `channel` callback with the name of a single channel value
+fn leaf(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+    match parent {
+        Some(name) => channel(name),
+        None => channel(&ChannelName::new("value", None)),
+    }
+}
+
+macro_rules! impl_signals_for_primitive {
+    ($($ty:ty),*) => {$(
+        impl Signals for $ty {
+            const CHANNELS: usize = 1;
+
+            fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+                leaf(parent, channel)
+            }
+
+            fn write(&self, buffer: &mut [f64]) {
+                buffer[0] = *self as f64;
+            }
+
+            fn read(buffer: &[f64]) -> Self {
+                buffer[0] as $ty
+            }
+        }
+    )*};
+}
+
+impl_signals_for_primitive!(f32, f64, u8, u16, u32, i8, i16, i32);
+
+impl Signals for bool {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        buffer[0] = if *self { 1.0 } else { 0.0 };
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        buffer[0] != 0.0
+    }
+}
+
+impl<D: Dimension + ?Sized> Signals for Quantity<D> {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.value.write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        Quantity {
+            dimension: Default::default(),
+            units: Default::default(),
+            value: f32::read(buffer),
+        }
+    }
+}
+
+impl<D: Dimension + ?Sized> Signals for PreciseQuantity<D> {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.value.write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        PreciseQuantity {
+            dimension: Default::default(),
+            units: Default::default(),
+            value: f64::read(buffer),
+        }
+    }
+}
+
+impl<F: Frame, D: Dimension + ?Sized> Signals for Vector3<F, D> {
+    const CHANNELS: usize = 3;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        for axis in &["x", "y", "z"] {
+            channel(&ChannelName::new(axis, parent));
+        }
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.x.write(&mut buffer[0..1]);
+        self.y.write(&mut buffer[1..2]);
+        self.z.write(&mut buffer[2..3]);
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        Vector3::new(
+            Signals::read(&buffer[0..1]),
+            Signals::read(&buffer[1..2]),
+            Signals::read(&buffer[2..3]),
+        )
+    }
+}
+
+impl<From: Frame, To: Frame> Signals for Rotation<From, To> {
+    const CHANNELS: usize = Quaternion::CHANNELS;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        Quaternion::channels(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.quaternion().write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        Rotation::new_unchecked(Quaternion::read(buffer))
+    }
+}
+
+impl Signals for PyroCommands {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.0.write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        PyroCommands(u8::read(buffer))
+    }
+}
+
+impl Signals for EventFlags {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.0.write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        EventFlags(u32::read(buffer))
+    }
+}
+
+impl Signals for SensorHealth {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        self.0.write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        SensorHealth(u32::read(buffer))
+    }
+}
+
+impl Signals for GnssFix {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        (*self as u8).write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        match u8::read(buffer) {
+            2 => GnssFix::TwoDimensional,
+            3 => GnssFix::ThreeDimensional,
+            _ => GnssFix::NoFix,
+        }
+    }
+}
+
+impl Signals for FlightPhase {
+    const CHANNELS: usize = 1;
+
+    fn channels(parent: Option<&ChannelName<'_>>, channel: &mut dyn FnMut(&ChannelName<'_>)) {
+        leaf(parent, channel)
+    }
+
+    fn write(&self, buffer: &mut [f64]) {
+        (*self as u8).write(buffer)
+    }
+
+    fn read(buffer: &[f64]) -> Self {
+        let phase = u8::read(buffer);
+
+        FlightPhase::ALL
+            .iter()
+            .copied()
+            .find(|known| *known as u8 == phase)
+            .unwrap_or(FlightPhase::Pad)
+    }
+}
@@ -0,0 +1,299 @@
+//! Snapshots of the state of the avionics that they can later be restored to
+//!
+//! Avionics whose state implements [`Snapshot`], which should almost always be
+//! derived with [`derive(Snapshot)`](macro@crate::Snapshot), can offer it through
+//! [`Avionics::snapshot`](crate::Avionics::snapshot). The preflight runner is
+//! then able to snapshot the avionics along with the simulated vehicle in the
+//! middle of a flight, and fly on from that moment as many times as it likes,
+//! such as for each run of a Monte Carlo batch.
+//!
+//! A snapshot is a flat series of bytes, with each field written in order in
+//! little endian, so it can only be restored into the same build of the avionics
+//! that it was taken from.
+
+use core::marker::PhantomData;
+
+use uom::si::Dimension;
+
+use crate::{
+    control::AntiWindup,
+    frame::{Frame, Rotation},
+    uom::si::{f32::Frequency, frequency::hertz},
+    FlightPhase, Quantity, Quaternion, Vector3,
+};
+
+/// A value that is able to be snapshotted, and later restored in place from
+/// that snapshot
+pub trait Snapshot {
+    /// Write the state of this value to the snapshot
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>);
+
+    /// Restore the state of this value from the snapshot, in the same order that
+    /// it was written by [`Snapshot::save`]
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError>;
+}
+
+/// Why a snapshot could not be restored
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The avionics do not support being snapshotted
+    Unsupported,
+    /// The snapshot ended before every value was restored
+    Truncated,
+    /// The snapshot had bytes left over once every value was restored
+    TooLong,
+    /// The snapshot held a value that is not valid, such as an unknown phase
+    Invalid,
+}
+
+/// Streams the bytes of a snapshot to a sink, since the avionics are unable to
+/// allocate a buffer for it
+pub struct SnapshotWriter<'a> {
+    sink: &'a mut dyn FnMut(&[u8]),
+}
+
+impl<'a> SnapshotWriter<'a> {
+    /// A writer that passes each piece of the snapshot to the sink
+    pub fn new(sink: &'a mut dyn FnMut(&[u8])) -> Self {
+        Self { sink }
+    }
+
+    /// Write the bytes to the snapshot
+    pub fn write(&mut self, bytes: &[u8]) {
+        (self.sink)(bytes)
+    }
+}
+
+/// Reads the bytes of a snapshot back out in order
+pub struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// A reader over the whole of a snapshot
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Read the next `len` bytes of the snapshot
+    pub fn read(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let (read, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(read)
+    }
+
+    /// Check that every byte of the snapshot has been read
+    pub fn finish(self) -> Result<(), SnapshotError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(SnapshotError::TooLong)
+        }
+    }
+}
+
+/// Restore the whole of a snapshot into the value
+pub fn restore<S: Snapshot + ?Sized>(value: &mut S, bytes: &[u8]) -> Result<(), SnapshotError> {
+    let mut snapshot = SnapshotReader::new(bytes);
+    value.restore(&mut snapshot)?;
+
+    snapshot.finish()
+}
+
+macro_rules! impl_snapshot_for_number {
+    ($($ty:ty),*) => {$(
+        impl Snapshot for $ty {
+            fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+                snapshot.write(&self.to_le_bytes())
+            }
+
+            fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+                let mut bytes = [0; core::mem::size_of::<$ty>()];
+                let len = bytes.len();
+                bytes.copy_from_slice(snapshot.read(len)?);
+                *self = <$ty>::from_le_bytes(bytes);
+
+                Ok(())
+            }
+        }
+    )*};
+}
+
+impl_snapshot_for_number!(f32, f64, u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Snapshot for bool {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        u8::from(*self).save(snapshot)
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        let mut byte = 0_u8;
+        byte.restore(snapshot)?;
+
+        *self = match byte {
+            0 => false,
+            1 => true,
+            _ => return Err(SnapshotError::Invalid),
+        };
+
+        Ok(())
+    }
+}
+
+/// A value that is `None` in the avionics but `Some` in the snapshot is
+/// restored into the default value
+impl<T: Snapshot + Default> Snapshot for Option<T> {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        self.is_some().save(snapshot);
+
+        if let Some(value) = self {
+            value.save(snapshot);
+        }
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        let mut is_some = false;
+        is_some.restore(snapshot)?;
+
+        if is_some {
+            self.get_or_insert_with(T::default).restore(snapshot)
+        } else {
+            *self = None;
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Snapshot, const N: usize> Snapshot for [T; N] {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        for value in self {
+            value.save(snapshot);
+        }
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        for value in self {
+            value.restore(snapshot)?;
+        }
+
+        Ok(())
+    }
+}
+
+macro_rules! impl_snapshot_for_tuple {
+    ($(($($name:ident . $index:tt),*)),*) => {$(
+        impl<$($name: Snapshot),*> Snapshot for ($($name,)*) {
+            fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+                $(self.$index.save(snapshot);)*
+            }
+
+            fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+                $(self.$index.restore(snapshot)?;)*
+
+                Ok(())
+            }
+        }
+    )*};
+}
+
+impl_snapshot_for_tuple!((A.0, B.1), (A.0, B.1, C.2));
+
+impl<T: ?Sized> Snapshot for PhantomData<T> {
+    fn save(&self, _: &mut SnapshotWriter<'_>) {}
+
+    fn restore(&mut self, _: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        Ok(())
+    }
+}
+
+impl<D: Dimension + ?Sized> Snapshot for Quantity<D> {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        self.value.save(snapshot)
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        self.value.restore(snapshot)
+    }
+}
+
+impl<F: Frame, D: Dimension + ?Sized> Snapshot for Vector3<F, D> {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        self.x.save(snapshot);
+        self.y.save(snapshot);
+        self.z.save(snapshot);
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        self.x.restore(snapshot)?;
+        self.y.restore(snapshot)?;
+        self.z.restore(snapshot)
+    }
+}
+
+impl<From: Frame, To: Frame> Snapshot for Rotation<From, To> {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        self.quaternion().save(snapshot)
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        let mut quaternion = Quaternion::IDENTITY;
+        quaternion.restore(snapshot)?;
+        *self = Rotation::new_unchecked(quaternion);
+
+        Ok(())
+    }
+}
+
+impl Snapshot for FlightPhase {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        (*self as u8).save(snapshot)
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        let mut phase = 0_u8;
+        phase.restore(snapshot)?;
+
+        *self = *FlightPhase::ALL
+            .get(usize::from(phase))
+            .ok_or(SnapshotError::Invalid)?;
+
+        Ok(())
+    }
+}
+
+impl Snapshot for AntiWindup {
+    fn save(&self, snapshot: &mut SnapshotWriter<'_>) {
+        match self {
+            AntiWindup::Clamping => 0_u8.save(snapshot),
+            AntiWindup::BackCalculation(rate) => {
+                1_u8.save(snapshot);
+                rate.save(snapshot);
+            }
+        }
+    }
+
+    fn restore(&mut self, snapshot: &mut SnapshotReader<'_>) -> Result<(), SnapshotError> {
+        let mut kind = 0_u8;
+        kind.restore(snapshot)?;
+
+        *self = match kind {
+            0 => AntiWindup::Clamping,
+            1 => {
+                let mut rate = Frequency::new::<hertz>(0.0);
+                rate.restore(snapshot)?;
+
+                AntiWindup::BackCalculation(rate)
+            }
+            _ => return Err(SnapshotError::Invalid),
+        };
+
+        Ok(())
+    }
+}
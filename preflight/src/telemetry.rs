@@ -0,0 +1,63 @@
+//! Introspection of the avionics through named values that they publish
+//!
+//! Guidance only carries what the flight hardware acts on, which says little
+//! about why the avionics acted. Through [`Avionics::telemetry`] the avionics
+//! publish whatever else is worth watching each cycle, such as the estimates of
+//! their filters, the error of their controllers or their phase of flight.
+//!
+//! Under the preflight runner the telemetry is recorded alongside the true state
+//! of the simulated vehicle, so that the error of an estimator can be plotted
+//! against the truth. On flight hardware the same values can be sent over a
+//! radio by publishing into a [`Telemetry`] with a sink of the firmware's own.
+//!
+//! [`Avionics::telemetry`]: crate::Avionics::telemetry
+
+use uom::si::Dimension;
+
+use crate::{frame::Frame, Quantity, Vector3};
+
+/// A value published to the [`Telemetry`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TelemetryValue {
+    /// A single value
+    Scalar(f64),
+    /// The x, y and z axes of a vector
+    Vector([f64; 3]),
+}
+
+/// A sink that the avionics publish named values into
+///
+/// Quantities are published in the base SI unit of their dimension, so an
+/// altitude is in meters and an angle is in radians
+pub struct Telemetry<'a> {
+    sink: &'a mut dyn FnMut(&str, TelemetryValue),
+}
+
+impl<'a> Telemetry<'a> {
+    /// Telemetry that passes every published value to the given sink
+    pub fn new(sink: &'a mut dyn FnMut(&str, TelemetryValue)) -> Self {
+        Self { sink }
+    }
+
+    /// Publish a single plain value
+    pub fn scalar(&mut self, name: &str, value: f64) {
+        (self.sink)(name, TelemetryValue::Scalar(value));
+    }
+
+    /// Publish a quantity, in the base SI unit of its dimension
+    pub fn quantity<D: Dimension + ?Sized>(&mut self, name: &str, value: Quantity<D>) {
+        self.scalar(name, f64::from(value.value));
+    }
+
+    /// Publish each axis of a vector, in the base SI unit of its dimension
+    pub fn vector<F: Frame, D: Dimension + ?Sized>(&mut self, name: &str, value: &Vector3<F, D>) {
+        (self.sink)(
+            name,
+            TelemetryValue::Vector([
+                f64::from(value.x.value),
+                f64::from(value.y.value),
+                f64::from(value.z.value),
+            ]),
+        );
+    }
+}
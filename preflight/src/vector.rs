@@ -0,0 +1,264 @@
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+};
+
+// std's inherent float methods take precedence when it is linked in for tests
+#[cfg_attr(test, allow(unused_imports))]
+use micromath::F32Ext;
+use uom::si::{ratio, Dimension};
+
+use crate::{frame::Frame, Quantity};
+
+/// A vector representing a quantity in 3 dimensional space, with axes in the
+/// frame `F` (see [`frame`](crate::frame))
+///
+/// Arithmetic keeps track of the dimension of the result, so a length divided by
+/// a time is a velocity, and both the frames and the dimensions of the operands
+/// of addition have to match
+#[repr(C)]
+pub struct Vector3<F: Frame, T: Dimension + ?Sized> {
+    pub(crate) x: Quantity<T>,
+    pub(crate) y: Quantity<T>,
+    pub(crate) z: Quantity<T>,
+    frame: PhantomData<F>,
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Debug for Vector3<F, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // if f.alternate() {
+
+        // }
+        // write!(f, "({}, {}, {})", self.x.value, self.y.value, self.z);
+        f.debug_struct("Vector3")
+            .field("frame", &F::NAME)
+            .field("x", &self.x) //Quantity::format_args(self.x, Abbreviation)
+            .field("y", &self.y)
+            .field("z", &self.z)
+            .finish()
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Vector3<F, T> {
+    /// Create a 3 dimensional vector from 3 quantities
+    pub fn new(x: Quantity<T>, y: Quantity<T>, z: Quantity<T>) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            frame: PhantomData,
+        }
+    }
+
+    /// Create a zeroed vector
+    pub fn zero() -> Self {
+        Self::from_values(0.0, 0.0, 0.0)
+    }
+
+    /// Create a vector from the value of each axis in the base SI unit of its
+    /// dimension
+    pub(crate) fn from_values(x: f32, y: f32, z: f32) -> Self {
+        Self::new(quantity(x), quantity(y), quantity(z))
+    }
+
+    /// The x axis
+    pub fn x(&self) -> Quantity<T> {
+        self.x
+    }
+
+    /// The y axis
+    pub fn y(&self) -> Quantity<T> {
+        self.y
+    }
+
+    /// The z axis
+    pub fn z(&self) -> Quantity<T> {
+        self.z
+    }
+
+    /// The length of the vector
+    pub fn norm(&self) -> Quantity<T> {
+        quantity(sqrt(self.dot_values(self)))
+    }
+
+    /// The unitless vector pointing in the same direction with a length of 1, or
+    /// a zeroed vector if this one is zeroed
+    pub fn normalize(&self) -> Vector3<F, ratio::Dimension> {
+        let norm = self.norm().value;
+
+        if norm == 0.0 {
+            return Vector3::zero();
+        }
+
+        Vector3::from_values(
+            self.x.value / norm,
+            self.y.value / norm,
+            self.z.value / norm,
+        )
+    }
+
+    /// The dot product, which has the dimension of the product of both vectors
+    pub fn dot<R, O>(&self, rhs: &Vector3<F, R>) -> Quantity<O>
+    where
+        R: Dimension + ?Sized,
+        O: Dimension + ?Sized,
+        Quantity<T>: Mul<Quantity<R>, Output = Quantity<O>>,
+    {
+        quantity(self.dot_values(rhs))
+    }
+
+    /// The cross product, which has the dimension of the product of both vectors
+    pub fn cross<R, O>(&self, rhs: &Vector3<F, R>) -> Vector3<F, O>
+    where
+        R: Dimension + ?Sized,
+        O: Dimension + ?Sized,
+        Quantity<T>: Mul<Quantity<R>, Output = Quantity<O>>,
+    {
+        Vector3::from_values(
+            self.y.value * rhs.z.value - self.z.value * rhs.y.value,
+            self.z.value * rhs.x.value - self.x.value * rhs.z.value,
+            self.x.value * rhs.y.value - self.y.value * rhs.x.value,
+        )
+    }
+
+    fn dot_values<R: Dimension + ?Sized>(&self, rhs: &Vector3<F, R>) -> f32 {
+        self.x.value * rhs.x.value + self.y.value * rhs.y.value + self.z.value * rhs.z.value
+    }
+
+    /// Apply `f` to the value of each axis
+    fn map<O: Dimension + ?Sized>(&self, f: impl Fn(f32) -> f32) -> Vector3<F, O> {
+        Vector3::from_values(f(self.x.value), f(self.y.value), f(self.z.value))
+    }
+}
+
+/// The square root, refined from the estimate of [`micromath`] which can be a few
+/// percent off, far too much for normalizing a vector
+pub(crate) fn sqrt(value: f32) -> f32 {
+    let mut root = value.sqrt();
+
+    if root > 0.0 {
+        for _ in 0..2 {
+            root = (root + value / root) / 2.0;
+        }
+    }
+
+    root
+}
+
+/// A quantity with the given value in the base SI unit of its dimension
+pub(crate) fn quantity<T: Dimension + ?Sized>(value: f32) -> Quantity<T> {
+    Quantity {
+        dimension: PhantomData,
+        units: PhantomData,
+        value,
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Clone for Vector3<F, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Copy for Vector3<F, T> {}
+
+impl<F: Frame, T: Dimension + ?Sized> PartialEq for Vector3<F, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.x.value == other.x.value
+            && self.y.value == other.y.value
+            && self.z.value == other.z.value
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Default for Vector3<F, T> {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Add for Vector3<F, T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::from_values(
+            self.x.value + rhs.x.value,
+            self.y.value + rhs.y.value,
+            self.z.value + rhs.z.value,
+        )
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> AddAssign for Vector3<F, T> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Sub for Vector3<F, T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> SubAssign for Vector3<F, T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Neg for Vector3<F, T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.map(|value| -value)
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Mul<f32> for Vector3<F, T> {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        self.map(|value| value * rhs)
+    }
+}
+
+impl<F: Frame, T: Dimension + ?Sized> Div<f32> for Vector3<F, T> {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self {
+        self.map(|value| value / rhs)
+    }
+}
+
+impl<F, T, R, O> Mul<Quantity<R>> for Vector3<F, T>
+where
+    F: Frame,
+    T: Dimension + ?Sized,
+    R: Dimension + ?Sized,
+    O: Dimension + ?Sized,
+    Quantity<T>: Mul<Quantity<R>, Output = Quantity<O>>,
+{
+    type Output = Vector3<F, O>;
+
+    fn mul(self, rhs: Quantity<R>) -> Vector3<F, O> {
+        self.map(|value| value * rhs.value)
+    }
+}
+
+impl<F, T, R, O> Div<Quantity<R>> for Vector3<F, T>
+where
+    F: Frame,
+    T: Dimension + ?Sized,
+    R: Dimension + ?Sized,
+    O: Dimension + ?Sized,
+    Quantity<T>: Div<Quantity<R>, Output = Quantity<O>>,
+{
+    type Output = Vector3<F, O>;
+
+    fn div(self, rhs: Quantity<R>) -> Vector3<F, O> {
+        self.map(|value| value / rhs.value)
+    }
+}
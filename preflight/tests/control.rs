@@ -0,0 +1,166 @@
+use preflight::{
+    control::{AntiWindup, Pid},
+    uom::si::{
+        angle::{self, degree},
+        f32::{Angle, Frequency, Length, Ratio, Time, Velocity},
+        frequency::hertz,
+        length::{self, meter},
+        ratio::ratio,
+        time::second,
+        velocity::{self, meter_per_second},
+    },
+};
+
+fn degrees(value: f32) -> Angle {
+    Angle::new::<degree>(value)
+}
+
+fn seconds(value: f32) -> Time {
+    Time::new::<second>(value)
+}
+
+/// A gimbal loop with a proportional gain of 2 that integrates at 1 Hz
+fn gimbal(anti_windup: AntiWindup) -> Pid<angle::Dimension, angle::Dimension> {
+    Pid::new(
+        Ratio::new::<ratio>(2.0),
+        Frequency::new::<hertz>(1.0),
+        seconds(0.0),
+    )
+    .with_limits(degrees(-5.0), degrees(5.0))
+    .with_anti_windup(anti_windup)
+}
+
+#[test]
+fn gains_carry_dimensions() {
+    // A velocity out of a length error needs a gain in 1/s
+    let mut pid: Pid<length::Dimension, velocity::Dimension> = Pid::new(
+        Frequency::new::<hertz>(0.5),
+        Frequency::new::<hertz>(0.0),
+        seconds(0.0),
+    );
+
+    let output = pid.update(
+        Length::new::<meter>(10.0),
+        Length::new::<meter>(4.0),
+        seconds(0.0),
+    );
+    assert!((output.get::<meter_per_second>() - 3.0).abs() < 1e-6);
+    assert_eq!(pid.integral(), Velocity::new::<meter_per_second>(0.0));
+}
+
+#[test]
+fn integral_removes_steady_state_error() {
+    // A plant that drifts away from the setpoint at a constant rate unless the
+    // controller pushes back
+    let mut pid: Pid<length::Dimension, velocity::Dimension> = Pid::new(
+        Frequency::new::<hertz>(2.0),
+        Frequency::new::<hertz>(1.0),
+        seconds(0.0),
+    );
+
+    let (dt, drift) = (0.01, 1.0);
+    let mut position = 0.0;
+    let mut time = 0.0;
+    while time < 20.0 {
+        let output = pid.update(
+            Length::new::<meter>(0.0),
+            Length::new::<meter>(position),
+            seconds(time),
+        );
+
+        position += (drift + output.get::<meter_per_second>()) * dt;
+        time += dt;
+    }
+
+    assert!(position.abs() < 1e-3);
+    assert!((pid.integral().get::<meter_per_second>() + drift).abs() < 1e-2);
+}
+
+#[test]
+fn derivative_ignores_setpoint_steps() {
+    let mut pid: Pid<angle::Dimension, angle::Dimension> = Pid::new(
+        Ratio::new::<ratio>(1.0),
+        Frequency::new::<hertz>(0.0),
+        seconds(1.0),
+    );
+
+    pid.update(degrees(0.0), degrees(0.0), seconds(0.0));
+    let output = pid.update(degrees(10.0), degrees(0.0), seconds(0.01));
+    assert!((output.get::<degree>() - 10.0).abs() < 1e-3);
+
+    // The measurement moving at 1 degree a second is damped by 1 degree
+    let output = pid.update(degrees(10.0), degrees(0.01), seconds(0.02));
+    assert!((output.get::<degree>() - (10.0 - 0.01 - 1.0)).abs() < 1e-3);
+}
+
+#[test]
+fn output_is_saturated() {
+    let mut pid = gimbal(AntiWindup::Clamping);
+
+    let output = pid.update(degrees(90.0), degrees(0.0), seconds(0.0));
+    assert!((output.get::<degree>() - 5.0).abs() < 1e-4);
+
+    let output = pid.update(degrees(-90.0), degrees(0.0), seconds(0.01));
+    assert!((output.get::<degree>() + 5.0).abs() < 1e-4);
+}
+
+#[test]
+fn anti_windup() {
+    for &anti_windup in &[
+        AntiWindup::Clamping,
+        AntiWindup::BackCalculation(Frequency::new::<hertz>(10.0)),
+    ] {
+        let mut pid = gimbal(anti_windup);
+
+        // Held against the limit by a large error for a long time
+        let mut time = 0.0;
+        while time < 10.0 {
+            pid.update(degrees(20.0), degrees(0.0), seconds(time));
+            time += 0.01;
+        }
+        assert!(
+            pid.integral().get::<degree>() < 10.0,
+            "{:?} let the integral wind up to {:?}",
+            anti_windup,
+            pid.integral()
+        );
+
+        // Leaves the limit as soon as the error changes sign
+        let output = pid.update(degrees(-1.0), degrees(0.0), seconds(time));
+        assert!(output.get::<degree>() < 5.0, "{:?}", anti_windup);
+    }
+}
+
+#[test]
+fn bumpless_reset() {
+    let mut pid = gimbal(AntiWindup::Clamping);
+
+    pid.update(degrees(1.0), degrees(0.0), seconds(0.0));
+    pid.update(degrees(1.0), degrees(0.0), seconds(1.0));
+
+    // Taking over from a gimbal that was manually held at 3 degrees
+    pid.reset_bumpless(degrees(3.0), degrees(0.5), degrees(0.0));
+    let output = pid.update(degrees(0.5), degrees(0.0), seconds(2.0));
+    assert!((output.get::<degree>() - 3.0).abs() < 1e-4);
+
+    pid.reset();
+    assert_eq!(pid.integral(), degrees(0.0));
+}
+
+#[test]
+fn recovers_from_nan_measurement() {
+    let mut pid = gimbal(AntiWindup::Clamping);
+
+    pid.update(degrees(1.0), degrees(0.0), seconds(0.0));
+    pid.update(degrees(1.0), degrees(0.0), seconds(0.5));
+
+    // The bad sample holds the integral term rather than saturating the output
+    let output = pid.update(degrees(1.0), degrees(f32::NAN), seconds(1.0));
+    assert!((output.get::<degree>() - 1.0).abs() < 1e-4);
+    let output = pid.update(degrees(f32::NAN), degrees(0.0), seconds(1.0));
+    assert!((output.get::<degree>() - 1.0).abs() < 1e-4);
+
+    let output = pid.update(degrees(1.0), degrees(0.0), seconds(1.5));
+    assert!(output.get::<degree>().is_finite());
+    assert!((pid.integral().get::<degree>() - 3.0).abs() < 1e-4);
+}
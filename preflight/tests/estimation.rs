@@ -0,0 +1,131 @@
+use preflight::{
+    estimation::{ApogeePredictor, AttitudeEstimator, Complementary, Madgwick, Mahony},
+    frame::{Body, Enu, Rotation},
+    uom::si::{
+        acceleration::meter_per_second_squared,
+        angle::degree,
+        f32::{Acceleration, Angle, Length, MagneticFluxDensity, Time},
+        length::meter,
+        magnetic_flux_density::microtesla,
+        time::second,
+    },
+    Gnss, GnssFix, Quaternion, SensorHealth, Sensors, Vector3,
+};
+
+/// The readings of ideal sensors on a vehicle at rest with the given attitude
+fn at_rest(attitude: Rotation<Body, Enu>, time: f32, health: SensorHealth) -> Sensors {
+    let to_body = attitude.inverse();
+    let gravity = to_body.rotate(&Vector3::new(
+        Acceleration::new::<meter_per_second_squared>(0.0),
+        Acceleration::new::<meter_per_second_squared>(0.0),
+        Acceleration::new::<meter_per_second_squared>(9.80665),
+    ));
+    let field = to_body.rotate(&Vector3::new(
+        MagneticFluxDensity::new::<microtesla>(0.0),
+        MagneticFluxDensity::new::<microtesla>(20.0),
+        MagneticFluxDensity::new::<microtesla>(-45.0),
+    ));
+
+    Sensors {
+        health,
+        altitude: Length::default(),
+        linear_acceleration: Vector3::zero(),
+        gravity_acceleration: gravity,
+        both_acceleration: gravity,
+        orientation: Vector3::zero(),
+        attitude,
+        angular_velocity: Vector3::zero(),
+        magnetic_field: field,
+        gnss: Gnss {
+            available: false,
+            fix: GnssFix::NoFix,
+            satellites: 0,
+            latitude: Default::default(),
+            longitude: Default::default(),
+            altitude: Length::default(),
+            velocity: Vector3::zero(),
+            time_of_week: 0,
+        },
+        running_time: Time::new::<second>(time),
+    }
+}
+
+/// The angle in degrees between the estimated and true attitude
+fn error(estimate: Rotation<Body, Enu>, truth: Rotation<Body, Enu>) -> f32 {
+    estimate
+        .quaternion()
+        .angle_to(&truth.quaternion())
+        .get::<degree>()
+}
+
+fn check<E: AttitudeEstimator>(new: impl Fn() -> E, settle: f32) {
+    let truth = Rotation::new(Quaternion::from_euler_angles(
+        Angle::new::<degree>(80.0),
+        Angle::new::<degree>(10.0),
+        Angle::new::<degree>(-30.0),
+    ));
+
+    // Starting from the readings of the accelerometer and magnetometer
+    let mut estimator = new();
+    estimator.update(&at_rest(truth, 0.0, SensorHealth::ALL));
+    assert!(error(estimator.attitude(), truth) < 1.0);
+
+    // Converging from the identity when the first readings cannot be trusted
+    let mut estimator = new();
+    estimator.update(&at_rest(truth, 0.0, SensorHealth::NONE));
+    let mut time = 0.0;
+    while time < settle {
+        time += 0.01;
+        estimator.update(&at_rest(truth, time, SensorHealth::ALL));
+    }
+    assert!(error(estimator.attitude(), truth) < 1.0);
+}
+
+#[test]
+fn complementary() {
+    check(Complementary::default, 10.0);
+}
+
+#[test]
+fn mahony() {
+    check(Mahony::default, 30.0);
+}
+
+#[test]
+fn madgwick() {
+    check(Madgwick::default, 30.0);
+}
+
+#[test]
+fn apogee() {
+    // Coasting straight up at 50 m/s, which tops out at about 127.5 m after 5.1 s
+    let (velocity, gravity) = (50.0, 9.80665);
+    let (time, altitude) = (velocity / gravity, velocity * velocity / (2.0 * gravity));
+
+    let mut predictor = ApogeePredictor::default();
+    let mut detected = None;
+    let mut t = 0.0;
+    while t < 8.0 && detected.is_none() {
+        let mut sensors = at_rest(Rotation::new(Quaternion::IDENTITY), t, SensorHealth::ALL);
+        sensors.altitude = Length::new::<meter>(velocity * t - gravity * t * t / 2.0);
+        sensors.linear_acceleration = Vector3::new(
+            Acceleration::new::<meter_per_second_squared>(0.0),
+            Acceleration::new::<meter_per_second_squared>(0.0),
+            Acceleration::new::<meter_per_second_squared>(-gravity),
+        );
+
+        // The first readings are taken while the filter catches up with the coast
+        if let Some(predicted) = predictor.predicted().filter(|_| t > 2.0) {
+            assert!((predicted.altitude.get::<meter>() - altitude).abs() < 2.0);
+            assert!((predicted.time.get::<second>() - time).abs() < 0.2);
+        }
+
+        detected = predictor.update(&sensors);
+        t += 0.01;
+    }
+
+    let detected = detected.expect("apogee was never detected");
+    assert!((detected.altitude.get::<meter>() - altitude).abs() < 1.0);
+    assert!((detected.time.get::<second>() - time).abs() < 0.2);
+    assert_eq!(predictor.apogee(), Some(detected));
+}
@@ -0,0 +1,116 @@
+use preflight::{
+    abi::pass_logs,
+    log::{format_id, Argument, Encode, Encoder, Level, Record, MAX_FRAME_LEN, QUEUE_LEN},
+    uom::si::{f32::Length, length::kilometer},
+};
+
+fn encode<'a>(
+    buffer: &'a mut [u8],
+    level: Level,
+    format: &str,
+    arguments: &[&dyn Encode],
+) -> &'a [u8] {
+    let mut encoder = Encoder::new(buffer, level, format_id(format));
+    for argument in arguments {
+        argument.encode(&mut encoder);
+    }
+
+    encoder.finish()
+}
+
+#[test]
+fn arguments_survive_a_round_trip() {
+    let mut buffer = [0; MAX_FRAME_LEN];
+    let frame = encode(
+        &mut buffer,
+        Level::Warn,
+        "{} {} {} {} {}",
+        &[&true, &300_u16, &-2_i32, &1.5_f32, &"drogue"],
+    );
+
+    let record = Record::decode(frame).unwrap();
+    assert_eq!(record.level, Level::Warn);
+    assert_eq!(record.format_id, format_id("{} {} {} {} {}"));
+    assert_eq!(
+        record.arguments().collect::<Vec<_>>(),
+        [
+            Argument::Bool(true),
+            Argument::Unsigned(300),
+            Argument::Signed(-2),
+            Argument::F32(1.5),
+            Argument::Str("drogue"),
+        ]
+    );
+}
+
+#[test]
+fn frames_are_compact() {
+    let mut buffer = [0; MAX_FRAME_LEN];
+
+    // The level and format id, then a tag and a byte for each small integer
+    let frame = encode(&mut buffer, Level::Info, "{} {}", &[&1_u32, &-1_i64]);
+    assert_eq!(frame.len(), 5 + 2 + 2);
+}
+
+#[test]
+fn messages_are_formatted_when_decoded() {
+    let mut buffer = [0; MAX_FRAME_LEN];
+    let format = "apogee at {} m {{{}}}, {}";
+    let frame = encode(
+        &mut buffer,
+        Level::Info,
+        format,
+        &[&Length::new::<kilometer>(0.25), &7_u8],
+    );
+
+    let record = Record::decode(frame).unwrap();
+    assert_eq!(
+        record.message(format).to_string(),
+        "apogee at 250 m {7}, {?}"
+    );
+}
+
+#[test]
+fn arguments_that_do_not_fit_are_left_out() {
+    let mut buffer = [0; 12];
+    let frame = encode(&mut buffer, Level::Error, "{} {}", &[&1_u8, &"too long"]);
+
+    let record = Record::decode(frame).unwrap();
+    assert_eq!(
+        record.arguments().collect::<Vec<_>>(),
+        [Argument::Unsigned(1)]
+    );
+    assert!(Record::decode(&[]).is_none());
+}
+
+// The queue is shared by the whole process, so it is only used by this test
+#[test]
+fn messages_wait_in_the_queue() {
+    preflight::info!("{} to go", 3_u8);
+
+    let mut frames = Vec::new();
+    pass_logs(|frame| frames.push(frame.to_vec()));
+    assert_eq!(frames.len(), 1);
+
+    let record = Record::decode(&frames[0]).unwrap();
+    assert_eq!(record.level, Level::Info);
+    assert_eq!(record.message("{} to go").to_string(), "3 to go");
+
+    // Messages past the length of the queue are dropped until it is emptied
+    for i in 0..QUEUE_LEN + 4 {
+        preflight::debug!("message {}", i);
+    }
+
+    let mut frames = Vec::new();
+    pass_logs(|frame| frames.push(frame.to_vec()));
+    assert_eq!(frames.len(), QUEUE_LEN);
+    assert_eq!(
+        Record::decode(frames.last().unwrap())
+            .unwrap()
+            .arguments()
+            .collect::<Vec<_>>(),
+        [Argument::Unsigned(QUEUE_LEN as u64 - 1)]
+    );
+
+    pass_logs(|_| panic!("the queue was emptied"));
+}
@@ -0,0 +1,145 @@
+use preflight::{
+    params,
+    uom::si::{f32::Time, time::second},
+    Avionics, Command, CommandResponse, Control, Guidance, Parameters, Sensors,
+};
+
+#[derive(Debug, Parameters)]
+struct Params {
+    tilt: Gains,
+    timeout: Time,
+    enabled: bool,
+}
+
+#[derive(Debug, Parameters)]
+struct Gains {
+    proportional: f32,
+    integral: f32,
+}
+
+#[derive(Debug, Parameters)]
+struct Limits {
+    retries: u8,
+    offset: i16,
+}
+
+impl Params {
+    fn new() -> Self {
+        Self {
+            tilt: Gains {
+                proportional: -0.2,
+                integral: 0.1,
+            },
+            timeout: Time::new::<second>(2.0),
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Controller {
+    params: Params,
+}
+
+impl Avionics for Controller {
+    type Sensors = Sensors;
+    type Guidance = Guidance;
+    type Parameters = Params;
+
+    fn guide(&mut self, _: &Sensors) -> Control<Guidance> {
+        Control::RecoverableFailure
+    }
+
+    fn parameters(&mut self) -> Option<&mut Params> {
+        Some(&mut self.params)
+    }
+}
+
+#[test]
+fn fields_are_flattened_into_named_parameters() {
+    let mut names = Vec::new();
+    Params::names(None, &mut |name| names.push(name.to_string()));
+
+    assert_eq!(Params::PARAMETERS, 4);
+    assert_eq!(
+        names,
+        ["tilt.proportional", "tilt.integral", "timeout", "enabled"]
+    );
+
+    let params = Params::new();
+    assert_eq!(params.get(0), Some(f64::from(-0.2_f32)));
+    assert_eq!(params.get(2), Some(2.0));
+    assert_eq!(params.get(3), Some(1.0));
+    assert_eq!(params.get(4), None);
+}
+
+#[test]
+fn parameters_are_set_by_name() {
+    let mut params = Params::new();
+
+    assert_eq!(params::index_of::<Params>("tilt.integral"), Some(1));
+    assert_eq!(params::index_of::<Params>("tilt"), None);
+    assert_eq!(params::index_of::<Params>("tilt.integral.x"), None);
+
+    assert!(params::set_by_name(&mut params, "timeout", 3.5));
+    assert!(params::set_by_name(&mut params, "enabled", 0.0));
+    assert!(!params::set_by_name(&mut params, "tilt.derivative", 1.0));
+    assert_eq!(params.timeout.get::<second>(), 3.5);
+    assert!(!params.enabled);
+}
+
+#[test]
+fn set_parameter_commands_set_parameters_by_default() {
+    let mut controller = Controller {
+        params: Params::new(),
+    };
+
+    assert_eq!(
+        controller.command(&Command::SetParameter {
+            name: "tilt.proportional",
+            value: -0.5,
+        }),
+        CommandResponse::Accepted
+    );
+    assert_eq!(
+        controller.command(&Command::SetParameter {
+            name: "tilt.kp",
+            value: -0.5,
+        }),
+        CommandResponse::Rejected
+    );
+    assert_eq!(
+        controller.command(&Command::Arm),
+        CommandResponse::Unsupported
+    );
+    assert_eq!(controller.params.tilt.proportional, -0.5);
+}
+
+#[test]
+fn values_that_do_not_fit_are_rejected() {
+    let mut params = Params::new();
+
+    assert!(!params::set_by_name(&mut params, "tilt.integral", f64::NAN));
+    assert!(!params::set_by_name(
+        &mut params,
+        "tilt.integral",
+        f64::INFINITY
+    ));
+    assert!(!params::set_by_name(&mut params, "tilt.integral", 1e300));
+    assert!(!params::set_by_name(&mut params, "enabled", f64::NAN));
+    assert_eq!(params.tilt.integral, 0.1);
+    assert!(params.enabled);
+
+    let mut limits = Limits {
+        retries: 3,
+        offset: 0,
+    };
+
+    assert!(!params::set_by_name(&mut limits, "retries", 256.0));
+    assert!(!params::set_by_name(&mut limits, "retries", -1.0));
+    assert!(!params::set_by_name(&mut limits, "retries", 2.5));
+    assert!(!params::set_by_name(&mut limits, "offset", f64::NAN));
+    assert!(params::set_by_name(&mut limits, "retries", 255.0));
+    assert!(params::set_by_name(&mut limits, "offset", -300.0));
+    assert_eq!((limits.retries, limits.offset), (255, -300));
+}
@@ -0,0 +1,178 @@
+use preflight::{
+    frame::Rotation,
+    phase::{FlightPhase, PhaseTracker},
+    uom::si::{
+        acceleration::meter_per_second_squared,
+        f32::{Acceleration, Length, Time},
+        length::meter,
+        time::second,
+    },
+    Gnss, GnssFix, Quaternion, SensorHealth, Sensors, Vector3,
+};
+
+/// Time that the motor ignites at and burns for, in seconds
+const IGNITION: f32 = 1.0;
+const BURN: f32 = 1.0;
+/// Acceleration of the motor in m/s^2
+const THRUST: f32 = 50.0;
+/// Descent rate under the parachute in m/s
+const DESCENT: f32 = 10.0;
+const GRAVITY: f32 = 9.80665;
+
+/// The altitude and vertical acceleration of a vehicle flying straight up and
+/// back down under a parachute, at the given time
+fn flight(t: f32) -> (f32, f32) {
+    let burnout = IGNITION + BURN;
+    let (burnout_velocity, burnout_altitude) = (THRUST * BURN, THRUST * BURN * BURN / 2.0);
+    let apogee = burnout + burnout_velocity / GRAVITY;
+    let highest = burnout_altitude + burnout_velocity * burnout_velocity / (2.0 * GRAVITY);
+
+    if t < IGNITION {
+        (0.0, 0.0)
+    } else if t < burnout {
+        let t = t - IGNITION;
+        (THRUST * t * t / 2.0, THRUST)
+    } else if t < apogee {
+        let t = t - burnout;
+        (
+            burnout_altitude + burnout_velocity * t - GRAVITY * t * t / 2.0,
+            -GRAVITY,
+        )
+    } else {
+        ((highest - DESCENT * (t - apogee)).max(0.0), 0.0)
+    }
+}
+
+fn sensors(t: f32) -> Sensors {
+    let (altitude, acceleration) = flight(t);
+
+    Sensors {
+        health: SensorHealth::ALL,
+        altitude: Length::new::<meter>(altitude),
+        // The body frame lines up with the world frame
+        linear_acceleration: Vector3::new(
+            Acceleration::new::<meter_per_second_squared>(0.0),
+            Acceleration::new::<meter_per_second_squared>(0.0),
+            Acceleration::new::<meter_per_second_squared>(acceleration),
+        ),
+        gravity_acceleration: Vector3::zero(),
+        both_acceleration: Vector3::zero(),
+        orientation: Vector3::zero(),
+        attitude: Rotation::new(Quaternion::IDENTITY),
+        angular_velocity: Vector3::zero(),
+        magnetic_field: Vector3::zero(),
+        gnss: Gnss {
+            available: false,
+            fix: GnssFix::NoFix,
+            satellites: 0,
+            latitude: Default::default(),
+            longitude: Default::default(),
+            altitude: Length::default(),
+            velocity: Vector3::zero(),
+            time_of_week: 0,
+        },
+        running_time: Time::new::<second>(t),
+    }
+}
+
+/// Every phase that the tracker enters over the flight, with the time it entered them
+fn fly(tracker: &mut PhaseTracker) -> Vec<(FlightPhase, f32)> {
+    fly_with(tracker, sensors)
+}
+
+/// Every phase that the tracker enters over a flight with the given readings
+fn fly_with(
+    tracker: &mut PhaseTracker,
+    sensors: impl Fn(f32) -> Sensors,
+) -> Vec<(FlightPhase, f32)> {
+    let mut entered = Vec::new();
+
+    let mut t = 0.0;
+    while t < 40.0 {
+        if let Some(phase) = tracker.update(&sensors(t)) {
+            entered.push((phase, t));
+        }
+
+        t += 0.01;
+    }
+
+    entered
+}
+
+#[test]
+fn phases_of_a_flight() {
+    let mut tracker = PhaseTracker::default();
+    tracker.arm();
+    assert_eq!(tracker.phase(), FlightPhase::Armed);
+
+    let entered = fly(&mut tracker);
+    let phases = entered.iter().map(|(phase, _)| *phase).collect::<Vec<_>>();
+    assert_eq!(
+        phases,
+        [
+            FlightPhase::Boost,
+            FlightPhase::Coast,
+            FlightPhase::Apogee,
+            FlightPhase::DrogueDescent,
+            FlightPhase::MainDescent,
+            FlightPhase::Landed,
+        ]
+    );
+
+    let time = |phase| {
+        entered
+            .iter()
+            .find(|(entered, _)| *entered == phase)
+            .map(|(_, time)| *time)
+            .unwrap()
+    };
+    let apogee = IGNITION + BURN + THRUST * BURN / GRAVITY;
+
+    assert!((time(FlightPhase::Boost) - IGNITION).abs() < 0.1);
+    assert!((time(FlightPhase::Coast) - IGNITION - BURN).abs() < 0.1);
+    assert!((time(FlightPhase::Apogee) - apogee).abs() < 0.3);
+    assert!(time(FlightPhase::Landed) > time(FlightPhase::MainDescent) + 2.0);
+}
+
+#[test]
+fn stays_on_the_pad_until_armed() {
+    let mut tracker = PhaseTracker::default();
+    assert!(fly(&mut tracker).is_empty());
+    assert_eq!(tracker.phase(), FlightPhase::Pad);
+
+    let mut tracker = PhaseTracker::default();
+    tracker.arm();
+    tracker.disarm();
+    assert!(fly(&mut tracker).is_empty());
+}
+
+#[test]
+fn burnout_is_detected_from_the_altimeter_when_the_accelerometer_fails() {
+    let mut tracker = PhaseTracker::default();
+    tracker.arm();
+
+    // The accelerometer fails halfway through the boost
+    let failure = IGNITION + BURN / 2.0;
+    let entered = fly_with(&mut tracker, |t| Sensors {
+        health: if t < failure {
+            SensorHealth::ALL
+        } else {
+            SensorHealth::ALL.difference(SensorHealth::ACCELEROMETER)
+        },
+        ..sensors(t)
+    });
+
+    let time = |phase| {
+        entered
+            .iter()
+            .find(|(entered, _)| *entered == phase)
+            .map(|(_, time)| *time)
+            .unwrap_or_else(|| panic!("never entered {:?}: {:?}", phase, entered))
+    };
+
+    let coast = time(FlightPhase::Coast);
+    assert!(coast > IGNITION + BURN, "coasting at {}", coast);
+    assert!(coast < IGNITION + BURN + 1.0, "coasting at {}", coast);
+    assert!(time(FlightPhase::Apogee) > coast);
+    assert!(time(FlightPhase::Landed) > time(FlightPhase::MainDescent));
+}
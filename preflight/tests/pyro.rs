@@ -0,0 +1,72 @@
+use preflight::{EventFlags, PyroChannel, PyroCommands, PYRO_CHANNELS};
+
+fn channel(id: u8) -> PyroChannel {
+    PyroChannel::new(id).unwrap()
+}
+
+#[test]
+fn channels_are_limited_to_the_hardware() {
+    assert_eq!(channel(0).id(), 0);
+    assert_eq!(channel(PYRO_CHANNELS - 1).id(), PYRO_CHANNELS - 1);
+
+    assert_eq!(PyroChannel::new(PYRO_CHANNELS), None);
+    assert_eq!(PyroChannel::new(u8::MAX), None);
+}
+
+#[test]
+fn channels_are_iterated_in_order_of_their_ids() {
+    let commands = PyroCommands::NONE
+        .fire(channel(5))
+        .fire(channel(0))
+        .fire(channel(PYRO_CHANNELS - 1))
+        .fire(channel(2));
+
+    let ids = commands.channels().map(PyroChannel::id).collect::<Vec<_>>();
+    assert_eq!(ids, [0, 2, 5, PYRO_CHANNELS - 1]);
+    assert_eq!(format!("{:?}", commands), "{0, 2, 5, 7}");
+
+    assert_eq!(PyroCommands::NONE.channels().count(), 0);
+}
+
+#[test]
+fn firing_a_channel_again_changes_nothing() {
+    let once = PyroCommands::NONE.fire(channel(3));
+    let twice = once.fire(channel(3));
+
+    assert_eq!(once, twice);
+    assert!(twice.is_fired(channel(3)));
+    assert!(!twice.is_fired(channel(2)));
+    assert!(!twice.is_fired(channel(4)));
+}
+
+#[test]
+fn user_flags_are_kept_apart_from_the_shared_ones() {
+    assert_eq!(EventFlags::user(EventFlags::USER_FLAGS), None);
+
+    let first = EventFlags::user(0).unwrap();
+    let last = EventFlags::user(EventFlags::USER_FLAGS - 1).unwrap();
+
+    let flags = EventFlags::LIFTOFF | first | last;
+    assert!(flags.contains(EventFlags::LIFTOFF | last));
+    assert!(!flags.contains(EventFlags::APOGEE));
+    assert_eq!(format!("{:?}", flags), "{LIFTOFF, USER(0), USER(15)}");
+
+    for flag in &[
+        EventFlags::LIFTOFF,
+        EventFlags::BURNOUT,
+        EventFlags::APOGEE,
+        EventFlags::LANDED,
+    ] {
+        assert_eq!(flag.bits() & (first.bits() | last.bits()), 0);
+    }
+}
+
+#[test]
+fn raised_flags_are_the_difference() {
+    let before = EventFlags::LIFTOFF;
+    let after = EventFlags::LIFTOFF | EventFlags::BURNOUT;
+
+    assert_eq!(after.difference(before), EventFlags::BURNOUT);
+    assert!(before.difference(after).is_empty());
+    assert!(EventFlags::NONE.is_empty());
+}
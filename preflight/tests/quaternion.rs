@@ -0,0 +1,116 @@
+use preflight::{
+    frame::{Body, Enu, Rotation},
+    uom::si::{
+        angle::{degree, radian},
+        f32::{Angle, Length},
+        length::meter,
+    },
+    Quaternion, Vector3,
+};
+
+// micromath trades accuracy for speed, so comparisons need some slack
+const TOLERANCE: f32 = 1e-2;
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < TOLERANCE, "{} is not close to {}", a, b);
+}
+
+fn assert_same_rotation(a: Quaternion, b: Quaternion) {
+    // q and -q describe the same rotation
+    let dot = a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z;
+    assert_close(dot.abs(), 1.0);
+}
+
+#[test]
+fn euler_angles_round_trip() {
+    let (roll, pitch, yaw) = (
+        Angle::new::<degree>(30.0),
+        Angle::new::<degree>(-45.0),
+        Angle::new::<degree>(120.0),
+    );
+    let (r, p, y) = Quaternion::from_euler_angles(roll, pitch, yaw).euler_angles();
+
+    assert_close(r.get::<radian>(), roll.get::<radian>());
+    assert_close(p.get::<radian>(), pitch.get::<radian>());
+    assert_close(y.get::<radian>(), yaw.get::<radian>());
+}
+
+#[test]
+fn rotation_matrix_round_trip() {
+    for q in &[
+        Quaternion::IDENTITY,
+        Quaternion::from_axis_angle([1.0, 0.0, 0.0], Angle::new::<degree>(180.0)),
+        Quaternion::from_axis_angle([0.0, 1.0, 0.0], Angle::new::<degree>(179.0)),
+        Quaternion::from_axis_angle([1.0, -2.0, 3.0], Angle::new::<degree>(75.0)),
+    ] {
+        assert_same_rotation(
+            Quaternion::from_rotation_matrix(&q.to_rotation_matrix()),
+            *q,
+        );
+    }
+}
+
+#[test]
+fn composition_applies_right_hand_side_first() {
+    let pitch = Quaternion::from_axis_angle([0.0, 1.0, 0.0], Angle::new::<degree>(90.0));
+    let yaw = Quaternion::from_axis_angle([0.0, 0.0, 1.0], Angle::new::<degree>(90.0));
+
+    let x: Vector3<Body, _> = Vector3::new(
+        Length::new::<meter>(1.0),
+        Length::new::<meter>(0.0),
+        Length::new::<meter>(0.0),
+    );
+    let composed = (yaw * pitch).rotate(&x);
+    let sequential = yaw.rotate(&pitch.rotate(&x));
+
+    // Pitching +x by 90° about y points it at -z, which yaw leaves alone
+    assert_close(composed.z().get::<meter>(), -1.0);
+    assert_close((composed - sequential).norm().get::<meter>(), 0.0);
+    assert_same_rotation(yaw * yaw.inverse(), Quaternion::IDENTITY);
+}
+
+#[test]
+fn rotation_between_frames() {
+    // A vehicle on the pad, with the nose (body y) pointing up
+    let attitude: Rotation<Body, Enu> = Rotation::new(Quaternion::from_axis_angle(
+        [1.0, 0.0, 0.0],
+        Angle::new::<degree>(90.0),
+    ));
+    let nose: Vector3<Body, _> = Vector3::new(
+        Length::new::<meter>(0.0),
+        Length::new::<meter>(1.0),
+        Length::new::<meter>(0.0),
+    );
+
+    let up: Vector3<Enu, _> = attitude.rotate(&nose);
+    assert_close(up.z().get::<meter>(), 1.0);
+
+    let back: Vector3<Body, _> = attitude.inverse().rotate(&up);
+    assert_close((back - nose).norm().get::<meter>(), 0.0);
+}
+
+#[test]
+fn angle_between_rotations() {
+    let start = Quaternion::from_euler_angles(
+        Angle::new::<degree>(10.0),
+        Angle::new::<degree>(20.0),
+        Angle::new::<degree>(30.0),
+    );
+
+    for &angle in &[0.0, 0.5, 30.0, 135.0, 180.0] {
+        // With std's trigonometry, which is exact enough to compare small angles with
+        let half = f32::to_radians(angle) / 2.0;
+        let turned = Quaternion::new(half.cos(), 0.0, 0.6 * half.sin(), 0.8 * half.sin()) * start;
+
+        assert!(
+            (start.angle_to(&turned).get::<degree>() - angle).abs() < 1e-2,
+            "{:?} is not {}°",
+            start.angle_to(&turned),
+            angle
+        );
+        assert_close(turned.angle_to(&start).get::<degree>(), angle);
+    }
+
+    // Negating a quaternion leaves its rotation the same
+    assert_close(start.angle_to(&(start * -1.0)).get::<degree>(), 0.0);
+}
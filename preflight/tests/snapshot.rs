@@ -0,0 +1,141 @@
+use preflight::{
+    abi::{self, RestoreOutcome},
+    control::Pid,
+    snapshot::{self, SnapshotError, SnapshotWriter},
+    uom::si::{
+        angle::{self, degree},
+        f32::{Angle, Frequency, Ratio, Time},
+        frequency::hertz,
+        ratio::ratio,
+        time::second,
+    },
+    Avionics, Control, FlightPhase, Guidance, Sensors, Snapshot,
+};
+
+#[derive(Debug, Default, Snapshot)]
+struct State {
+    tilt: Option<Pid<angle::Dimension, angle::Dimension>>,
+    phase: Phase,
+    armed: bool,
+    error: (Angle, Angle),
+}
+
+#[derive(Debug, Snapshot)]
+struct Phase(FlightPhase);
+
+impl Default for Phase {
+    fn default() -> Self {
+        Phase(FlightPhase::Pad)
+    }
+}
+
+#[derive(Debug)]
+struct Stateless;
+
+impl Avionics for Stateless {
+    type Sensors = Sensors;
+    type Guidance = Guidance;
+    type Parameters = ();
+
+    fn guide(&mut self, _: &Sensors) -> Control<Guidance> {
+        Control::RecoverableFailure
+    }
+}
+
+fn save(state: &impl Snapshot) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    state.save(&mut SnapshotWriter::new(&mut |piece| {
+        bytes.extend_from_slice(piece)
+    }));
+
+    bytes
+}
+
+fn update(pid: &mut Pid<angle::Dimension, angle::Dimension>, seconds: f32) -> f32 {
+    pid.update(
+        Angle::new::<degree>(0.0),
+        Angle::new::<degree>(seconds.sin()),
+        Time::new::<second>(seconds),
+    )
+    .get::<degree>()
+}
+
+#[test]
+fn restored_state_carries_on_identically() {
+    let mut state = State {
+        tilt: Some(Pid::new(
+            Ratio::new::<ratio>(-0.2),
+            Frequency::new::<hertz>(0.5),
+            Time::new::<second>(0.1),
+        )),
+        phase: Phase(FlightPhase::Boost),
+        armed: true,
+        error: (Angle::new::<degree>(1.0), Angle::new::<degree>(-2.0)),
+    };
+
+    for step in 0..50 {
+        update(state.tilt.as_mut().unwrap(), step as f32 * 0.01);
+    }
+
+    let bytes = save(&state);
+    let mut restored = State::default();
+    snapshot::restore(&mut restored, &bytes).unwrap();
+
+    assert_eq!(restored.phase.0, FlightPhase::Boost);
+    assert!(restored.armed);
+    assert_eq!(restored.error, state.error);
+
+    for step in 50..100 {
+        let time = step as f32 * 0.01;
+
+        assert_eq!(
+            update(restored.tilt.as_mut().unwrap(), time),
+            update(state.tilt.as_mut().unwrap(), time)
+        );
+    }
+}
+
+#[test]
+fn malformed_snapshots_are_rejected() {
+    let bytes = save(&State::default());
+    let mut state = State::default();
+
+    assert_eq!(
+        snapshot::restore(&mut state, &bytes[..bytes.len() - 1]),
+        Err(SnapshotError::Truncated)
+    );
+
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(
+        snapshot::restore(&mut state, &longer),
+        Err(SnapshotError::TooLong)
+    );
+
+    // The first byte says whether there is a controller, so the phase follows it
+    let mut invalid = bytes;
+    invalid[1] = u8::MAX;
+    assert_eq!(
+        snapshot::restore(&mut state, &invalid),
+        Err(SnapshotError::Invalid)
+    );
+}
+
+#[test]
+fn avionics_are_not_snapshotted_by_default() {
+    extern "C" fn callback(_: *const u8, _: usize) {
+        panic!("avionics without a snapshot were snapshotted")
+    }
+
+    assert!(!abi::save_snapshot(&mut Stateless, callback));
+    assert_eq!(
+        abi::restore_snapshot(&mut Stateless, &[]),
+        RestoreOutcome::Failed(SnapshotError::Unsupported)
+    );
+
+    assert!(!abi::save_persistent(&mut Stateless, callback));
+    assert_eq!(
+        abi::restore_persistent(&mut Stateless, &[]),
+        RestoreOutcome::Failed(SnapshotError::Unsupported)
+    );
+}
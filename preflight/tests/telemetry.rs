@@ -0,0 +1,64 @@
+use preflight::{
+    frame::Body,
+    telemetry::TelemetryValue,
+    uom::si::{
+        angle::degree,
+        f32::{Angle, Length, Velocity},
+        length::kilometer,
+        velocity::meter_per_second,
+    },
+    Telemetry, Vector3,
+};
+
+fn publish(publisher: impl FnOnce(&mut Telemetry<'_>)) -> Vec<(String, TelemetryValue)> {
+    let mut published = Vec::new();
+    publisher(&mut Telemetry::new(&mut |name, value| {
+        published.push((name.to_string(), value))
+    }));
+
+    published
+}
+
+#[test]
+fn quantities_are_published_in_si_units() {
+    let published = publish(|telemetry| {
+        telemetry.quantity("altitude", Length::new::<kilometer>(1.5));
+        telemetry.quantity("tilt", Angle::new::<degree>(180.0));
+        telemetry.scalar("phase", 3.0);
+    });
+
+    assert_eq!(
+        published[0],
+        ("altitude".into(), TelemetryValue::Scalar(1500.0))
+    );
+    assert_eq!(published[2], ("phase".into(), TelemetryValue::Scalar(3.0)));
+
+    match published[1] {
+        (ref name, TelemetryValue::Scalar(tilt)) if name == "tilt" => {
+            assert!((tilt - core::f64::consts::PI).abs() < 1e-6)
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn vectors_are_published_by_axis() {
+    let published = publish(|telemetry| {
+        telemetry.vector(
+            "velocity",
+            &Vector3::<Body, _>::new(
+                Velocity::new::<meter_per_second>(1.0),
+                Velocity::new::<meter_per_second>(2.0),
+                Velocity::new::<meter_per_second>(3.0),
+            ),
+        )
+    });
+
+    assert_eq!(
+        published,
+        [(
+            "velocity".to_string(),
+            TelemetryValue::Vector([1.0, 2.0, 3.0])
+        )]
+    );
+}
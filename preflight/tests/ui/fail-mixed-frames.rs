@@ -0,0 +1,11 @@
+use preflight::{
+    frame::{Body, Ned},
+    uom::si::Dimension,
+    Vector3,
+};
+
+fn mix<D: Dimension + ?Sized>(body: Vector3<Body, D>, ned: Vector3<Ned, D>) {
+    let _ = body + ned;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use preflight::Signals;
+
+#[derive(Signals)]
+enum Sensors {
+    Altitude(f32),
+}
+
+fn main() {}
@@ -0,0 +1,51 @@
+use preflight::{
+    avionics_harness,
+    uom::si::f32::{Length, Pressure},
+    Avionics, Control, Signals, ThrustVector,
+};
+
+#[derive(Debug, Signals)]
+pub struct Sensors {
+    altitude: Length,
+    pitot: Pitot,
+    satellites: u8,
+}
+
+#[derive(Debug, Signals)]
+pub struct Pitot {
+    static_pressure: Pressure,
+    total_pressure: Pressure,
+}
+
+#[derive(Debug, Signals)]
+pub struct Guidance {
+    tvc: ThrustVector,
+    fins: Fins,
+}
+
+#[derive(Debug, Signals)]
+pub struct Fins(f32, f32, f32, f32);
+
+#[derive(Debug)]
+struct Controller;
+
+impl Controller {
+    const fn new() -> Self {
+        Controller
+    }
+}
+
+#[avionics_harness(default = "Controller::new()")]
+impl Avionics for Controller {
+    type Sensors = Sensors;
+    type Guidance = Guidance;
+
+    fn guide(&mut self, _: &Sensors) -> Control<Guidance> {
+        todo!()
+    }
+}
+
+fn main() {
+    assert_eq!(<Sensors as Signals>::CHANNELS, 4);
+    assert_eq!(<Guidance as Signals>::CHANNELS, 6);
+}
@@ -0,0 +1,41 @@
+use preflight::{avionics_harness, AbortCause, Avionics, Control, Sensors};
+
+#[derive(Debug)]
+struct Controller {
+    booted: bool,
+    aborted: bool,
+}
+
+impl Controller {
+    const fn new() -> Self {
+        Controller {
+            booted: false,
+            aborted: false,
+        }
+    }
+}
+
+#[avionics_harness(default = "Controller::new()")]
+impl Avionics for Controller {
+    fn init(&mut self) {
+        self.booted = true;
+    }
+
+    fn guide(&mut self, _: &Sensors) -> Control {
+        todo!()
+    }
+
+    fn abort(&mut self, _: &AbortCause) {
+        self.aborted = true;
+    }
+
+    fn reset(&mut self) {
+        self.aborted = false;
+    }
+
+    fn shutdown(&mut self) {
+        self.booted = false;
+    }
+}
+
+fn main() {}
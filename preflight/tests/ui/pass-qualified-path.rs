@@ -0,0 +1,19 @@
+use preflight::{avionics_harness, Control, Sensors};
+
+#[derive(Debug)]
+struct Controller;
+
+impl Controller {
+    const fn new() -> Self {
+        Controller
+    }
+}
+
+#[avionics_harness(default = "Controller::new()")]
+impl preflight::Avionics for Controller {
+    fn guide(&mut self, _: &Sensors) -> Control {
+        todo!()
+    }
+}
+
+fn main() {}
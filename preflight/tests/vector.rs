@@ -0,0 +1,81 @@
+use preflight::{
+    frame::{Body, Enu, Ned},
+    uom::si::{
+        f32::{Force, Length, Ratio, Time, Velocity},
+        force::newton,
+        length::meter,
+        ratio::ratio,
+        time::second,
+        velocity::meter_per_second,
+    },
+    Vector3,
+};
+
+fn length(x: f32, y: f32, z: f32) -> Vector3<Body, preflight::uom::si::length::Dimension> {
+    Vector3::new(
+        Length::new::<meter>(x),
+        Length::new::<meter>(y),
+        Length::new::<meter>(z),
+    )
+}
+
+#[test]
+fn arithmetic_keeps_dimensions() {
+    let a = length(1.0, 2.0, 3.0);
+    let b = length(4.0, 5.0, 6.0);
+
+    assert_eq!(a + b, length(5.0, 7.0, 9.0));
+    assert_eq!(b - a, length(3.0, 3.0, 3.0));
+    assert_eq!(-a * 2.0, length(-2.0, -4.0, -6.0));
+    assert_eq!(b / 2.0, length(2.0, 2.5, 3.0));
+
+    let velocity: Vector3<_, _> = a / Time::new::<second>(2.0);
+    let velocity_x: Velocity = velocity.x();
+    assert_eq!(velocity_x.get::<meter_per_second>(), 0.5);
+}
+
+#[test]
+fn products() {
+    let arm = length(0.0, 1.0, 0.0);
+    let force = Vector3::new(
+        Force::new::<newton>(0.0),
+        Force::new::<newton>(0.0),
+        Force::new::<newton>(2.0),
+    );
+
+    let moment: Vector3<_, _> = arm.cross(&force);
+    assert_eq!(
+        moment.x(),
+        Length::new::<meter>(1.0) * Force::new::<newton>(2.0)
+    );
+    assert_eq!(moment.z().value, 0.0);
+
+    let area = length(1.0, 2.0, 3.0).dot(&length(4.0, 5.0, 6.0));
+    assert_eq!(area, Length::new::<meter>(32.0) * Length::new::<meter>(1.0));
+}
+
+#[test]
+fn norm_and_direction() {
+    let v = length(3.0, 0.0, 4.0);
+    assert!((v.norm().get::<meter>() - 5.0).abs() < 1e-3);
+
+    let direction = v.normalize();
+    let x: Ratio = direction.x();
+    assert!((x.get::<ratio>() - 0.6).abs() < 1e-3);
+    assert_eq!(length(0.0, 0.0, 0.0).normalize(), Vector3::zero());
+}
+
+#[test]
+fn ned_and_enu() {
+    let ned: Vector3<Ned, _> = Vector3::new(
+        Length::new::<meter>(1.0),
+        Length::new::<meter>(2.0),
+        Length::new::<meter>(3.0),
+    );
+    let enu: Vector3<Enu, _> = ned.to_enu();
+
+    assert_eq!(enu.x(), ned.y());
+    assert_eq!(enu.y(), ned.x());
+    assert_eq!(enu.z(), -ned.z());
+    assert_eq!(enu.to_ned(), ned);
+}
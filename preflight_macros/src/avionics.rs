@@ -1,13 +1,15 @@
 use darling::FromMeta;
 use proc_macro2::TokenStream;
 use quote::{quote, quote_spanned};
-use syn::{spanned::Spanned, Error, ItemImpl, Result};
+use syn::{parse_quote, spanned::Spanned, Error, ImplItem, ItemImpl, Path, Result};
 
 #[derive(Debug, FromMeta)]
 pub struct AvionicsParameters {
     default: String,
     #[darling(default)]
     no_panic: bool,
+    #[darling(default)]
+    logger: Option<String>,
 }
 
 pub fn harness(params: AvionicsParameters, input: &ItemImpl) -> Result<TokenStream> {
@@ -20,7 +22,7 @@ pub fn harness(params: AvionicsParameters, input: &ItemImpl) -> Result<TokenStre
             .as_ref()
             .ok_or_else(|| Error::new(input.span(), "no trait was found to implement"))?;
 
-        if !trait_.is_ident("Avionics") {
+        if !is_avionics(trait_) {
             return Err(Error::new(
                 trait_.span(),
                 "expected a trait implementation of `Avionics`",
@@ -45,36 +47,48 @@ pub fn harness(params: AvionicsParameters, input: &ItemImpl) -> Result<TokenStre
         }
     };
 
-    let (avionics_impl, panic_impl) = if std::env::var_os("__PREFLIGHT").is_some() {
+    let logger = match &params.logger {
+        Some(path) => {
+            let logger: TokenStream = path.parse()?;
+
+            quote_spanned! {path.span()=>
+                preflight::abi::pass_logs(|frame| #logger(frame))
+            }
+        }
+        None => quote! {
+            preflight::abi::pass_logs(|_| ())
+        },
+    };
+
+    let preflight = std::env::var_os("__PREFLIGHT").is_some();
+
+    let (avionics_impl, panic_impl, pass_logs) = if preflight {
         (
             quote! {
-                use preflight::abi::*;
+                static mut __PANIC_CALLBACK: Option<PanicCallback> = None;
 
                 #[no_mangle]
-                pub static avionics_guide: AvionicsGuide = |sensors: &Sensors| unsafe {
-                    AVIONICS.guide(sensors)
-                };
-
-                static mut __PANIC_CALLBACK: Option<PanicCallback> = None;
+                pub extern "C" fn set_panic_callback(callback: PanicCallback) {
+                    unsafe { __PANIC_CALLBACK.replace(callback) };
+                }
 
+                // The logs go to the runner rather than the logger of the flight
+                // hardware, whenever it asks for them
                 #[no_mangle]
-                pub static set_panic_callback: SetPanicCallback = |callback: PanicCallback| unsafe {
-                    __PANIC_CALLBACK.replace(callback);
-                };
+                pub extern "C" fn avionics_logs(callback: LogCallback) {
+                    report_logs(callback)
+                }
             },
             quote! {
                 if let Some(callback) = unsafe { __PANIC_CALLBACK } {
-                    callback(_panic_info, unsafe { &AVIONICS })
+                    report_panic(callback, _panic_info, unsafe { &AVIONICS })
                 }
             },
+            quote! {},
         )
     } else {
         (
-            quote! {
-                unsafe extern "C" fn avionics_guide(sensors: &Sensors) -> Control {
-                    AVIONICS.guide(sensors)
-                }
-            },
+            quote! {},
             quote! {
                 extern "C" {
                     fn panic_abort();
@@ -82,13 +96,42 @@ pub fn harness(params: AvionicsParameters, input: &ItemImpl) -> Result<TokenStre
 
                 unsafe { panic_abort() };
             },
+            // The logs are passed on at the end of every call into the avionics
+            quote! {
+                #logger;
+            },
         )
     };
 
+    // The avionics abort rather than unwind, but the precompiled `core` of the host
+    // still references the personality routine that `std` would otherwise provide.
+    // `std` can not be linked alongside the panic handler of the harness, so the
+    // routine is only defined along with it, and refuses every exception
+    let personality = if preflight {
+        quote! {
+            #[cfg(not(any(test, trybuild)))]
+            #[no_mangle]
+            pub extern "C" fn rust_eh_personality(
+                _version: i32,
+                _actions: i32,
+                _exception_class: u64,
+                _exception: *mut core::ffi::c_void,
+                _context: *mut core::ffi::c_void,
+            ) -> i32 {
+                // _URC_FATAL_PHASE1_ERROR
+                3
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let panic_handler = if params.no_panic {
         quote! {}
     } else {
         quote! {
+            #personality
+
             #[cfg(not(any(test, trybuild)))]
             #[panic_handler]
             fn handle_panic(_panic_info: &core::panic::PanicInfo) -> ! {
@@ -106,11 +149,144 @@ pub fn harness(params: AvionicsParameters, input: &ItemImpl) -> Result<TokenStre
         mod __PREFLIGHT {
             use super::*;
 
-            #[allow(unused)]
+            use preflight::abi::*;
+            // The trait may only be in scope by its full path in the user's module
+            use preflight::Avionics as _;
+
             static mut AVIONICS: #st = #default;
 
+            type AvionicsSensors = <#st as preflight::Avionics>::Sensors;
+            type AvionicsGuidance = <#st as preflight::Avionics>::Guidance;
+
+            #[no_mangle]
+            pub static __PREFLIGHT_ABI: AbiHeader = AbiHeader::CURRENT;
+
+            #[no_mangle]
+            pub static __PREFLIGHT_SIGNALS: SignalLayout = SignalLayout {
+                sensors: <AvionicsSensors as preflight::Signals>::CHANNELS,
+                guidance: <AvionicsGuidance as preflight::Signals>::CHANNELS,
+            };
+
+            #[no_mangle]
+            pub extern "C" fn avionics_sensor_channels(callback: ChannelCallback) {
+                report_channels::<AvionicsSensors>(callback)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_guidance_channels(callback: ChannelCallback) {
+                report_channels::<AvionicsGuidance>(callback)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_init() {
+                unsafe { AVIONICS.init() };
+                #pass_logs
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_guide(sensors: *const f64, guidance: *mut f64) -> GuideOutcome {
+                // The caller passes buffers of the lengths given in `__PREFLIGHT_SIGNALS`
+                let (sensors, guidance) = unsafe {
+                    (
+                        core::slice::from_raw_parts(sensors, __PREFLIGHT_SIGNALS.sensors),
+                        core::slice::from_raw_parts_mut(guidance, __PREFLIGHT_SIGNALS.guidance),
+                    )
+                };
+
+                let outcome = guide(unsafe { &mut AVIONICS }, sensors, guidance);
+                #pass_logs
+                outcome
+            }
+
             #[no_mangle]
-            static __PREFLIGHT: bool = cfg!(preflight);
+            pub extern "C" fn avionics_abort(cause: &preflight::AbortCause) {
+                unsafe { AVIONICS.abort(cause) };
+                #pass_logs
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_command(event: CommandEvent<'_>) -> preflight::CommandResponse {
+                // The runner only sends strings borrowed with `FfiStr::new`, which
+                // stay borrowed for the duration of the call
+                fn borrow(string: FfiStr<'_>) -> &str {
+                    unsafe {
+                        core::str::from_utf8_unchecked(core::slice::from_raw_parts(
+                            string.as_ptr(),
+                            string.len(),
+                        ))
+                    }
+                }
+
+                let response = preflight::abi::command(unsafe { &mut AVIONICS }, event, borrow);
+                #pass_logs
+                response
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_parameter_names(callback: ChannelCallback) {
+                report_parameters::<#st>(callback)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_get_parameter(index: usize, value: &mut f64) -> bool {
+                get_parameter(unsafe { &mut AVIONICS }, index, value)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_set_parameter(index: usize, value: f64) -> bool {
+                set_parameter(unsafe { &mut AVIONICS }, index, value)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_telemetry(callback: TelemetryCallback) {
+                report_telemetry(unsafe { &AVIONICS }, callback)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_save(callback: SnapshotCallback) -> bool {
+                save_snapshot(unsafe { &mut AVIONICS }, callback)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_restore(snapshot: *const u8, len: usize) -> RestoreOutcome {
+                // The runner passes a snapshot that it owns for the duration of the call
+                let snapshot = unsafe { core::slice::from_raw_parts(snapshot, len) };
+
+                restore_snapshot(unsafe { &mut AVIONICS }, snapshot)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_save_persistent(callback: SnapshotCallback) -> bool {
+                save_persistent(unsafe { &mut AVIONICS }, callback)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_restore_persistent(
+                persistent: *const u8,
+                len: usize,
+            ) -> RestoreOutcome {
+                // The runner passes storage that it owns for the duration of the call
+                let persistent = unsafe { core::slice::from_raw_parts(persistent, len) };
+
+                restore_persistent(unsafe { &mut AVIONICS }, persistent)
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_recreate() {
+                unsafe { AVIONICS = #default }
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_reset() {
+                unsafe { AVIONICS.reset() };
+                #pass_logs
+            }
+
+            #[no_mangle]
+            pub extern "C" fn avionics_shutdown() {
+                unsafe { AVIONICS.shutdown() };
+                #pass_logs
+            }
 
             #avionics_impl
 
@@ -118,3 +294,49 @@ pub fn harness(params: AvionicsParameters, input: &ItemImpl) -> Result<TokenStre
         }
     })
 }
+
+/// Fill in the types that preflight simulates for any of the associated types
+/// that were left out of an implementation of `Avionics`, since associated types
+/// can not have defaults yet
+pub fn default_types(input: &mut ItemImpl) {
+    let is_avionics = matches!(
+        &input.trait_,
+        Some((None, trait_, _)) if is_avionics(trait_)
+    );
+
+    if !is_avionics {
+        return;
+    }
+
+    let has_type = |input: &ItemImpl, name: &str| {
+        input.items.iter().any(|item| match item {
+            ImplItem::Type(ty) => ty.ident == name,
+            _ => false,
+        })
+    };
+
+    if !has_type(input, "Sensors") {
+        input.items.push(parse_quote!(
+            type Sensors = preflight::Sensors;
+        ));
+    }
+    if !has_type(input, "Guidance") {
+        input.items.push(parse_quote!(
+            type Guidance = preflight::Guidance;
+        ));
+    }
+    if !has_type(input, "Parameters") {
+        input.items.push(parse_quote!(
+            type Parameters = ();
+        ));
+    }
+}
+
+/// If the path names the `Avionics` trait, whether it is imported or written out
+/// in full such as `preflight::Avionics`
+fn is_avionics(trait_: &Path) -> bool {
+    trait_
+        .segments
+        .last()
+        .map_or(false, |segment| segment.ident == "Avionics")
+}
@@ -7,15 +7,20 @@ use avionics::AvionicsParameters;
 use darling::FromMeta;
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, AttributeArgs, ItemImpl};
+use syn::{parse_macro_input, AttributeArgs, DeriveInput, ItemImpl};
 
 mod avionics;
+mod params;
+mod signals;
+mod snapshot;
 
 /// Harness to connect hardware agnostic flight systems to firmware or to the
 /// `preflight_cargo` utility
 #[proc_macro_attribute]
 pub fn avionics_harness(args: TokenStream, input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as ItemImpl);
+    let mut input = parse_macro_input!(input as ItemImpl);
+
+    avionics::default_types(&mut input);
 
     let output = parse_macro_input::parse::<AttributeArgs>(args)
         .map_err(|err| err.to_compile_error())
@@ -30,3 +35,41 @@ pub fn avionics_harness(args: TokenStream, input: TokenStream) -> TokenStream {
     })
     .into()
 }
+
+/// Flatten a struct into named channels so that it can be used as the sensors
+/// or guidance of avionics
+///
+/// Every field of the struct has to implement `Signals` as well
+#[proc_macro_derive(Signals)]
+pub fn derive_signals(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    signals::derive(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Flatten a struct into named parameters so that they can be listed, read and
+/// set from outside of the avionics
+///
+/// Every field of the struct has to implement `Parameters` as well
+#[proc_macro_derive(Parameters)]
+pub fn derive_parameters(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    params::derive(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Snapshot the state of a struct so that it can later be restored in place
+///
+/// Every field of the struct has to implement `Snapshot` as well
+#[proc_macro_derive(Snapshot)]
+pub fn derive_snapshot(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    snapshot::derive(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
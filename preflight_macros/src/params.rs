@@ -0,0 +1,97 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, spanned::Spanned, Data, DeriveInput, Error, Index, LitStr, Member, Result};
+
+pub fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span(),
+                "`Parameters` can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "`Parameters` can only be derived for structs",
+            ))
+        }
+    };
+
+    // The member used to access each field along with the name of its parameter
+    let (members, names): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => (
+                Member::Named(ident.clone()),
+                LitStr::new(&ident.to_string(), ident.span()),
+            ),
+            None => (
+                Member::Unnamed(Index::from(i)),
+                LitStr::new(&i.to_string(), field.span()),
+            ),
+        })
+        .unzip();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    // Every field has to be able to be flattened into parameters as well
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: preflight::params::Parameters));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics preflight::params::Parameters for #ident #ty_generics #where_clause {
+            const PARAMETERS: usize =
+                0 #(+ <#types as preflight::params::Parameters>::PARAMETERS)*;
+
+            #[allow(unused_variables)]
+            fn names(
+                parent: Option<&preflight::ChannelName<'_>>,
+                parameter: &mut dyn FnMut(&preflight::ChannelName<'_>),
+            ) {
+                #(<#types as preflight::params::Parameters>::names(
+                    Some(&preflight::ChannelName::new(#names, parent)),
+                    parameter,
+                );)*
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn get(&self, index: usize) -> Option<f64> {
+                let mut index = index;
+
+                #(
+                    if index < <#types as preflight::params::Parameters>::PARAMETERS {
+                        return preflight::params::Parameters::get(&self.#members, index);
+                    }
+                    index -= <#types as preflight::params::Parameters>::PARAMETERS;
+                )*
+
+                None
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn set(&mut self, index: usize, value: f64) -> bool {
+                let mut index = index;
+
+                #(
+                    if index < <#types as preflight::params::Parameters>::PARAMETERS {
+                        return preflight::params::Parameters::set(&mut self.#members, index, value);
+                    }
+                    index -= <#types as preflight::params::Parameters>::PARAMETERS;
+                )*
+
+                false
+            }
+        }
+    })
+}
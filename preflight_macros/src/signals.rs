@@ -0,0 +1,116 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_quote, spanned::Spanned, Data, DeriveInput, Error, Fields, Index, LitStr, Member, Result,
+};
+
+pub fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span(),
+                "`Signals` can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "`Signals` can only be derived for structs",
+            ))
+        }
+    };
+
+    // The member used to access each field along with the name of its channel
+    let (members, names): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => (
+                Member::Named(ident.clone()),
+                LitStr::new(&ident.to_string(), ident.span()),
+            ),
+            None => (
+                Member::Unnamed(Index::from(i)),
+                LitStr::new(&i.to_string(), field.span()),
+            ),
+        })
+        .unzip();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    // Every field has to be able to be flattened into channels as well
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: preflight::Signals));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let construct = match fields {
+        Fields::Named(_) => quote! {
+            Self {
+                #(#members: {
+                    let value = preflight::Signals::read(
+                        &buffer[offset..offset + <#types as preflight::Signals>::CHANNELS],
+                    );
+                    offset += <#types as preflight::Signals>::CHANNELS;
+                    value
+                },)*
+            }
+        },
+        Fields::Unnamed(_) => quote! {
+            Self(
+                #({
+                    let value = preflight::Signals::read(
+                        &buffer[offset..offset + <#types as preflight::Signals>::CHANNELS],
+                    );
+                    offset += <#types as preflight::Signals>::CHANNELS;
+                    value
+                },)*
+            )
+        },
+        Fields::Unit => quote! { Self },
+    };
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics preflight::Signals for #ident #ty_generics #where_clause {
+            const CHANNELS: usize = 0 #(+ <#types as preflight::Signals>::CHANNELS)*;
+
+            #[allow(unused_variables)]
+            fn channels(
+                parent: Option<&preflight::ChannelName<'_>>,
+                channel: &mut dyn FnMut(&preflight::ChannelName<'_>),
+            ) {
+                #(<#types as preflight::Signals>::channels(
+                    Some(&preflight::ChannelName::new(#names, parent)),
+                    channel,
+                );)*
+            }
+
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn write(&self, buffer: &mut [f64]) {
+                let mut offset = 0;
+
+                #(
+                    preflight::Signals::write(
+                        &self.#members,
+                        &mut buffer[offset..offset + <#types as preflight::Signals>::CHANNELS],
+                    );
+                    offset += <#types as preflight::Signals>::CHANNELS;
+                )*
+            }
+
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn read(buffer: &[f64]) -> Self {
+                let mut offset = 0;
+
+                #construct
+            }
+        }
+    })
+}
@@ -0,0 +1,63 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, spanned::Spanned, Data, DeriveInput, Error, Index, Member, Result};
+
+pub fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span(),
+                "`Snapshot` can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "`Snapshot` can only be derived for structs",
+            ))
+        }
+    };
+
+    let members = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect::<Vec<_>>();
+
+    // Every field has to be able to be snapshotted as well
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field in fields {
+        let ty = &field.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: preflight::snapshot::Snapshot));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics preflight::snapshot::Snapshot for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn save(&self, snapshot: &mut preflight::snapshot::SnapshotWriter<'_>) {
+                #(preflight::snapshot::Snapshot::save(&self.#members, snapshot);)*
+            }
+
+            #[allow(unused_variables)]
+            fn restore(
+                &mut self,
+                snapshot: &mut preflight::snapshot::SnapshotReader<'_>,
+            ) -> core::result::Result<(), preflight::snapshot::SnapshotError> {
+                #(preflight::snapshot::Snapshot::restore(&mut self.#members, snapshot)?;)*
+
+                Ok(())
+            }
+        }
+    })
+}